@@ -0,0 +1,144 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+/// Implements `crile::Component` for the type
+///
+/// The trait has no blanket impl so tuples never count as components, this saves writing
+/// `impl Component for MyType {}` by hand.
+#[proc_macro_derive(Component)]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics crile::Component for #name #type_generics #where_clause {}
+    }
+    .into()
+}
+
+/// Implements `crile::Reflect` for structs with named fields and enums with only unit variants
+///
+/// Struct fields have to implement `Reflect` too unless they are marked with `#[reflect(skip)]`,
+/// which hides them from the serializer, the inspector and scripts.
+/// Enums are reflected as a string value holding the name of the variant.
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let result = match &input.data {
+        Data::Struct(data) => derive_struct(&input, &data.fields),
+        Data::Enum(data) => derive_enum(&input, data.variants.iter()),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &input.ident,
+            "Reflect can't be derived for unions",
+        )),
+    };
+
+    result.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn derive_struct(input: &DeriveInput, fields: &Fields) -> syn::Result<proc_macro2::TokenStream> {
+    let Fields::Named(fields) = fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Reflect can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut idents = Vec::new();
+    for field in &fields.named {
+        if !is_skipped(field)? {
+            idents.push(field.ident.clone().unwrap());
+        }
+    }
+    let names: Vec<String> = idents.iter().map(|ident| ident.to_string()).collect();
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crile::Reflect for #name #type_generics #where_clause {
+            fn field_names(&self) -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+
+            fn field(&self, name: &str) -> Option<&dyn crile::Reflect> {
+                match name {
+                    #(#names => Some(&self.#idents),)*
+                    _ => None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut dyn crile::Reflect> {
+                match name {
+                    #(#names => Some(&mut self.#idents),)*
+                    _ => None,
+                }
+            }
+        }
+    })
+}
+
+fn derive_enum<'a>(
+    input: &DeriveInput,
+    variants: impl Iterator<Item = &'a syn::Variant>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut idents = Vec::new();
+    for variant in variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "Reflect can only be derived for enums with unit variants",
+            ));
+        }
+        idents.push(variant.ident.clone());
+    }
+    let names: Vec<String> = idents.iter().map(|ident| ident.to_string()).collect();
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crile::Reflect for #name #type_generics #where_clause {
+            fn value(&self) -> Option<crile::FieldValue> {
+                let name = match self {
+                    #(Self::#idents => #names,)*
+                };
+                Some(crile::FieldValue::String(name.to_owned()))
+            }
+
+            fn set_value(&mut self, value: crile::FieldValue) -> bool {
+                let crile::FieldValue::String(name) = value else {
+                    return false;
+                };
+                match name.as_str() {
+                    #(#names => *self = Self::#idents,)*
+                    _ => return false,
+                }
+                true
+            }
+
+            fn variants(&self) -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+        }
+    })
+}
+
+/// Checks for `#[reflect(skip)]`
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("reflect"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown reflect attribute"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
@@ -50,8 +50,6 @@ impl EditorCamera2D {
                 offset * (camera_zoom - self.camera.orthographic_zoom) / camera_zoom;
             self.position += scaled_offset;
         }
-
-        self.camera.dirty = true;
     }
 
     pub fn set_viewport(&mut self, viewport_size: glam::Vec2) {
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     editor_camera::EditorCamera2D, preferences::Preferences, project::Project,
@@ -7,7 +7,7 @@ use crate::{
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum Selection {
-    Entity(usize),
+    Entity(crile::Entity),
     None,
 }
 
@@ -24,6 +24,7 @@ pub struct RuntimeData {
     pub game_window_id: crile::WindowId,
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum SceneState {
     Edting,
     Running(RuntimeData),
@@ -75,18 +76,15 @@ impl EditorState {
 
         self.scene_state = SceneState::Running(RuntimeData {
             backup_scene: self.active_scene.clone(),
-            scene_runner: unsafe {
-                crile::SceneRunner::new(crile::ScriptingEngine::new(
-                    &mut self.active_scene,
-                    engine,
-                    game_window_id,
-                ))
-            },
+            scene_runner: crile::SceneRunner::new(
+                unsafe { crile::ScriptingEngine::new(&mut self.active_scene) },
+                game_window_id,
+            ),
             game_window_id,
         });
 
         if let SceneState::Running(runtime_data) = &mut self.scene_state {
-            if let Err(err) = runtime_data.scene_runner.start() {
+            if let Err(err) = runtime_data.scene_runner.start(engine) {
                 log::error!("{err}");
                 self.stop_scene(engine);
             }
@@ -136,8 +134,11 @@ impl EditorState {
             scene_path.or_else(|| self.project.pick_file_relative("Scene", &["scene"]))
         {
             if let Some(source) = crile::read_file(&self.project.make_absolute(&path)) {
-                if let Ok(scene) = crile::SceneSerializer::deserialize(source)
-                    .inspect_err(|err| log::error!("Failed to load scene: {err} "))
+                if let Ok(scene) = crile::SceneSerializer::deserialize_with_registry(
+                    source,
+                    self.active_scene.registry.clone(),
+                )
+                .inspect_err(|err| log::error!("Failed to load scene: {err} "))
                 {
                     self.active_scene = scene;
                     self.editor_scene_path = Some(path);
@@ -146,6 +147,76 @@ impl EditorState {
         }
     }
 
+    /// Turns the entity into an instance of a new prefab saved to a file the user picks
+    pub fn create_prefab(&mut self, engine: &mut crile::Engine, entity: crile::Entity) {
+        let name = &self.active_scene.get_node(entity).unwrap().name;
+        if let Some(path) = self.project.pick_save_relative(&format!("{name}.prefab")) {
+            let prefab = self.active_scene.create_prefab(entity, path.clone());
+            self.save_prefab(engine, &path, prefab);
+        }
+    }
+
+    /// Spawns an instance of a prefab file the user picks under the parent
+    pub fn add_prefab(&mut self, engine: &mut crile::Engine, parent: crile::Entity) {
+        if let Some(path) = self.project.pick_file_relative("Prefab", &["prefab"]) {
+            let absolute_path = self.project.make_absolute(&path);
+            match engine
+                .asset_manager
+                .load_prefab(&absolute_path, &self.active_scene.registry)
+            {
+                Ok(prefab) => {
+                    self.active_scene.instantiate_prefab(prefab, path, parent);
+                }
+                Err(err) => log::error!("{err}"),
+            }
+        }
+    }
+
+    /// Saves the overrides of the instance to its prefab file
+    pub fn apply_prefab_overrides(&mut self, engine: &mut crile::Engine, root: crile::Entity) {
+        let instance = self.active_scene.world.get::<crile::PrefabInstance>(root);
+        if let Some(path) = instance.and_then(|instance| instance.prefab_path.clone()) {
+            let prefab = self.active_scene.apply_prefab_overrides(root);
+            self.save_prefab(engine, &path, prefab);
+        }
+    }
+
+    /// Writes the prefab to the file and syncs the other instances of it
+    fn save_prefab(
+        &mut self,
+        engine: &mut crile::Engine,
+        path: &Path,
+        prefab: crile::RefId<crile::Prefab>,
+    ) {
+        let Ok(data) = crile::SceneSerializer::serialize(&prefab.scene)
+            .inspect_err(|err| log::error!("Failed to save prefab: {err}"))
+        else {
+            return;
+        };
+
+        let absolute_path = self.project.make_absolute(path);
+        crile::write_file(&absolute_path, &data);
+        engine.asset_manager.insert(&absolute_path, prefab.clone());
+
+        let scene = &mut self.active_scene;
+        let instances = scene
+            .world
+            .query::<(&crile::PrefabInstance,)>()
+            .filter(|(_, (instance,))| {
+                instance.prefab_path.as_deref() == Some(path)
+                    && instance.prefab != Some(prefab.clone())
+            })
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        for root in instances {
+            // Changes have to be found against the old prefab before it gets replaced
+            scene.update_prefab_overrides(root);
+            let instance = scene.world.get_mut::<crile::PrefabInstance>(root).unwrap();
+            instance.prefab = Some(prefab.clone());
+            scene.sync_prefab(root);
+        }
+    }
+
     pub fn open_project(&mut self, project_file_path: Option<PathBuf>) {
         if matches!(self.scene_state, SceneState::Running(_)) {
             return;
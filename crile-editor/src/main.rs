@@ -47,13 +47,13 @@ impl crile::Application for CrileEditorApp {
         egui::SidePanel::left("Hierachy")
             .width_range(150.0..=400.0)
             .show(&ctx, |ui| {
-                sections::hierarchy::show(ui, &mut self.state);
+                sections::hierarchy::show(ui, &mut self.state, engine);
             });
 
         egui::SidePanel::right("Inspector")
             .width_range(260.0..=500.0)
             .show(&ctx, |ui| {
-                sections::inspector::show(ui, &mut self.state);
+                sections::inspector::show(ui, &mut self.state, engine);
             });
 
         egui::CentralPanel::default()
@@ -76,16 +76,21 @@ impl crile::Application for CrileEditorApp {
 
         sections::inspector::update_assets(&mut self.state, engine);
         if let SceneState::Running(data) = &mut self.state.scene_state {
-            if let Err(err) = data.scene_runner.update() {
+            if let Err(err) = data.scene_runner.update(engine) {
                 log::error!("{err}");
                 self.state.stop_scene(engine);
             }
         }
     }
 
+    /// Engine systems run on the scene being edited as well as the one being played
+    fn world(&mut self) -> Option<&mut crile::World> {
+        Some(&mut self.state.active_scene.world)
+    }
+
     fn fixed_update(&mut self, engine: &mut crile::Engine) {
         if let SceneState::Running(data) = &mut self.state.scene_state {
-            if let Err(err) = data.scene_runner.fixed_update() {
+            if let Err(err) = data.scene_runner.fixed_update(engine) {
                 log::error!("{err}");
                 self.state.stop_scene(engine);
             }
@@ -106,6 +111,11 @@ impl crile::Application for CrileEditorApp {
                     .render(&mut render_pass, self.state.editor_camera.view_projection());
             }
 
+            // The scene runner tracks the frames itself when running
+            if matches!(self.state.scene_state, SceneState::Edting) {
+                self.state.active_scene.world.advance_tick();
+            }
+
             // Now render onto the window
             let mut render_pass = crile::RenderPass::new(&mut engine.gfx, None, None, None);
             self.egui.render(&mut render_pass);
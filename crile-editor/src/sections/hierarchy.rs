@@ -2,18 +2,33 @@ use crate::{EditorState, Selection};
 
 pub enum HierachyAction {
     None,
-    AddChildEntity(usize),
-    DestroyEntity(usize),
+    AddChildEntity(crile::Entity),
+    DestroyEntity(crile::Entity),
+    AddPrefab(crile::Entity),
+    CreatePrefab(crile::Entity),
+    MoveEntity {
+        entity: crile::Entity,
+        target: crile::Entity,
+        placement: DropPlacement,
+    },
 }
 
-pub fn show(ui: &mut egui::Ui, state: &mut EditorState) {
+/// Where a dragged entity goes relative to the entity it was dropped on
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DropPlacement {
+    Before,
+    After,
+    Child,
+}
+
+pub fn show(ui: &mut egui::Ui, state: &mut EditorState, engine: &mut crile::Engine) {
     ui.add_space(5.);
     let mut action = HierachyAction::None;
 
     display_entity(
         ui,
         &mut state.selection,
-        crile::Scene::ROOT_INDEX,
+        crile::Scene::ROOT,
         &state.active_scene,
         &mut action,
     );
@@ -25,56 +40,148 @@ pub fn show(ui: &mut egui::Ui, state: &mut EditorState) {
     )
     .context_menu(|ui| {
         if ui.button("Add entity").clicked() {
-            action = HierachyAction::AddChildEntity(0);
+            action = HierachyAction::AddChildEntity(crile::Scene::ROOT);
+            ui.close_menu();
+        }
+
+        if ui.button("Add prefab").clicked() {
+            action = HierachyAction::AddPrefab(crile::Scene::ROOT);
             ui.close_menu();
         }
     });
 
     match action {
-        HierachyAction::AddChildEntity(parent_index) => {
-            state.active_scene.spawn(
-                "Empty",
-                (crile::TransformComponent::default(),),
-                parent_index,
-            );
+        HierachyAction::AddChildEntity(parent) => {
+            state
+                .active_scene
+                .spawn("Empty", (crile::TransformComponent::default(),), parent);
         }
-        HierachyAction::DestroyEntity(index) => {
-            state.active_scene.despawn(index);
+        HierachyAction::DestroyEntity(entity) => {
+            state.active_scene.despawn(entity);
         }
+        HierachyAction::AddPrefab(parent) => state.add_prefab(engine, parent),
+        HierachyAction::CreatePrefab(entity) => state.create_prefab(engine, entity),
+        HierachyAction::MoveEntity {
+            entity,
+            target,
+            placement,
+        } => move_entity(&mut state.active_scene, entity, target, placement),
         HierachyAction::None => (),
     }
 }
 
+fn move_entity(
+    scene: &mut crile::Scene,
+    entity: crile::Entity,
+    target: crile::Entity,
+    placement: DropPlacement,
+) {
+    let parent = match placement {
+        DropPlacement::Child => target,
+        _ => scene
+            .parent(target)
+            .expect("Root can only have children dropped on it"),
+    };
+    if entity == target || !scene.is_valid_parent(entity, parent) {
+        return;
+    }
+
+    scene.set_parent(entity, parent, true);
+    if placement != DropPlacement::Child {
+        // The entity is now the last child so it doesn't change the index of the target
+        let target_id = scene.get_node(target).unwrap().id;
+        let siblings = &scene.get_node(parent).unwrap().children;
+        let index = siblings.iter().position(|id| *id == target_id).unwrap();
+        let offset = (placement == DropPlacement::After) as usize;
+        scene.move_sibling(entity, index + offset);
+    }
+}
+
+/// Decides the placement from how far down the label the pointer is
+fn drop_placement(response: &egui::Response, entity: crile::Entity) -> DropPlacement {
+    let pointer_y = response
+        .ctx
+        .pointer_interact_pos()
+        .map_or(response.rect.center().y, |pos| pos.y);
+    let fraction = (pointer_y - response.rect.top()) / response.rect.height();
+
+    if entity == crile::Scene::ROOT || (0.25..0.75).contains(&fraction) {
+        DropPlacement::Child
+    } else if fraction < 0.25 {
+        DropPlacement::Before
+    } else {
+        DropPlacement::After
+    }
+}
+
 fn display_entity(
     ui: &mut egui::Ui,
     selection: &mut Selection,
-    index: usize,
+    entity: crile::Entity,
     scene: &crile::Scene,
     action: &mut HierachyAction,
 ) {
-    let node = scene.get_node(index).unwrap();
+    let node = scene.get_node(entity).unwrap();
 
     let header_state = egui::collapsing_header::CollapsingState::load_with_default_open(
         ui.ctx(),
-        ui.make_persistent_id(index),
+        ui.make_persistent_id(entity),
         true,
     );
 
     let mut show_header = |ui: &mut egui::Ui| {
         ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
-            let response = ui.selectable_label(*selection == Selection::Entity(index), &node.name);
+            let response = ui
+                .selectable_label(*selection == Selection::Entity(entity), &node.name)
+                .interact(egui::Sense::drag());
             if response.clicked() {
-                *selection = Selection::Entity(index)
+                *selection = Selection::Entity(entity)
+            }
+
+            // Entities can be dragged onto another to reparent them or next to it to reorder them
+            if entity != crile::Scene::ROOT {
+                response.dnd_set_drag_payload(entity);
+            }
+            if response.dnd_hover_payload::<crile::Entity>().is_some() {
+                let placement = drop_placement(&response, entity);
+                let stroke = ui.visuals().selection.stroke;
+                let rect = response.rect;
+                let painter = ui.painter();
+                match placement {
+                    DropPlacement::Before => painter.hline(rect.x_range(), rect.top(), stroke),
+                    DropPlacement::After => painter.hline(rect.x_range(), rect.bottom(), stroke),
+                    DropPlacement::Child => {
+                        painter.rect_stroke(rect, 2., stroke, egui::StrokeKind::Inside)
+                    }
+                };
+
+                if let Some(dragged) = response.dnd_release_payload::<crile::Entity>() {
+                    *action = HierachyAction::MoveEntity {
+                        entity: *dragged,
+                        target: entity,
+                        placement,
+                    };
+                }
             }
 
             response.context_menu(|ui| {
                 if ui.button("Add entity").clicked() {
-                    *action = HierachyAction::AddChildEntity(index);
+                    *action = HierachyAction::AddChildEntity(entity);
+                    ui.close_menu();
+                }
+
+                if ui.button("Add prefab").clicked() {
+                    *action = HierachyAction::AddPrefab(entity);
+                    ui.close_menu();
+                }
+
+                if entity != crile::Scene::ROOT && ui.button("Create prefab").clicked() {
+                    *action = HierachyAction::CreatePrefab(entity);
                     ui.close_menu();
                 }
 
-                if index != crile::Scene::ROOT_INDEX && ui.button("Destroy").clicked() {
-                    *action = HierachyAction::DestroyEntity(index);
+                if entity != crile::Scene::ROOT && ui.button("Destroy").clicked() {
+                    *action = HierachyAction::DestroyEntity(entity);
                     ui.close_menu();
                 }
             });
@@ -84,8 +191,8 @@ fn display_entity(
     if !node.children.is_empty() {
         header_state.show_header(ui, show_header).body(|ui| {
             for id in node.children.iter() {
-                let index = scene.id_to_index(*id);
-                display_entity(ui, selection, index, scene, action);
+                let child = scene.id_to_entity(*id);
+                display_entity(ui, selection, child, scene, action);
             }
         });
     } else {
@@ -2,28 +2,100 @@ use crile::AssetPath;
 
 use crate::{project::Project, EditorState, Selection};
 
-pub fn show(ui: &mut egui::Ui, state: &mut EditorState) {
+enum PrefabAction {
+    None,
+    Revert(crile::Entity),
+    Apply(crile::Entity),
+    Reload(crile::Entity),
+}
+
+pub fn show(ui: &mut egui::Ui, state: &mut EditorState, engine: &mut crile::Engine) {
     ui.add_space(5.);
+    let mut action = PrefabAction::None;
 
     ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
-        if let Selection::Entity(id) = state.selection {
-            if let Some(node) = state.active_scene.get_node_mut(id) {
+        if let Selection::Entity(entity) = state.selection {
+            if let Some(node) = state.active_scene.get_node_mut(entity) {
                 ui.text_edit_singleline(&mut node.name);
                 ui.add_space(5.);
 
-                let mut entity = state.active_scene.world.entity_mut(id).unwrap();
-                inspect_entity(ui, &mut entity)
+                if let Some(root) = state.active_scene.prefab_root(entity) {
+                    action = inspect_prefab(ui, &state.active_scene, entity, root);
+                }
+
+                if inspect_entity(ui, &mut state.active_scene, entity) {
+                    if let Some(root) = state.active_scene.prefab_root(entity) {
+                        state.active_scene.update_prefab_overrides(root);
+                    }
+                }
             } else {
                 state.selection = Selection::None;
             }
         }
     });
+
+    match action {
+        PrefabAction::Revert(root) => state.active_scene.revert_prefab_overrides(root),
+        PrefabAction::Apply(root) => state.apply_prefab_overrides(engine, root),
+        PrefabAction::Reload(root) => {
+            let instance = state
+                .active_scene
+                .world
+                .get_mut::<crile::PrefabInstance>(root);
+            instance.unwrap().load_error = None;
+        }
+        PrefabAction::None => (),
+    }
+}
+
+fn inspect_prefab(
+    ui: &mut egui::Ui,
+    scene: &crile::Scene,
+    entity: crile::Entity,
+    root: crile::Entity,
+) -> PrefabAction {
+    let root_instance = scene.world.get::<crile::PrefabInstance>(root).unwrap();
+    let mut action = PrefabAction::None;
+
+    ui.group(|ui| {
+        if let Some(path) = &root_instance.prefab_path {
+            ui.label(format!("Prefab: {}", path.display()));
+        }
+        if let Some(error) = &root_instance.load_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+            if ui.button("Reload prefab").clicked() {
+                action = PrefabAction::Reload(root);
+            }
+        }
+
+        if let Some(instance) = scene.world.get::<crile::PrefabInstance>(entity) {
+            for path in &instance.overrides {
+                ui.weak(path);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Revert overrides").clicked() {
+                action = PrefabAction::Revert(root);
+            }
+            if ui.button("Apply overrides").clicked() {
+                action = PrefabAction::Apply(root);
+            }
+        });
+    });
+    ui.add_space(5.);
+
+    action
 }
 
 pub fn update_assets(state: &mut EditorState, engine: &mut crile::Engine) {
     macro_rules! update_asset_type {
         ($component: ident, $asset_name: ident, $asset_path_name: ident) => {
-            for (_, (asset,)) in state.active_scene.world.query_mut::<(crile::$component,)>() {
+            for (_, (asset,)) in state
+                .active_scene
+                .world
+                .query_mut::<(&mut crile::$component,)>()
+            {
                 update_asset(
                     &mut asset.$asset_name,
                     &mut asset.$asset_path_name,
@@ -36,6 +108,39 @@ pub fn update_assets(state: &mut EditorState, engine: &mut crile::Engine) {
 
     update_asset_type!(SpriteComponent, texture, texture_path);
     update_asset_type!(ScriptComponent, script, script_path);
+    update_prefabs(state, engine);
+}
+
+/// Loads the prefabs of instances that aren't loaded yet and syncs the instances with them
+/// Instances whose prefab failed to load keep the error until it gets reloaded from the inspector
+fn update_prefabs(state: &mut EditorState, engine: &mut crile::Engine) {
+    let scene = &mut state.active_scene;
+    let unloaded = scene
+        .world
+        .query::<(&crile::PrefabInstance,)>()
+        .filter(|(_, (instance,))| instance.prefab.is_none() && instance.load_error.is_none())
+        .filter_map(|(entity, (instance,))| Some((entity, instance.prefab_path.clone()?)))
+        .collect::<Vec<_>>();
+
+    for (root, path) in unloaded {
+        let absolute_path = state.project.make_absolute(&path);
+        let prefab = engine
+            .asset_manager
+            .load_prefab(&absolute_path, &scene.registry);
+
+        let instance = scene.world.get_mut::<crile::PrefabInstance>(root).unwrap();
+        match prefab {
+            Ok(prefab) => {
+                instance.prefab = Some(prefab);
+                scene.sync_prefab(root);
+            }
+            // The path is kept so the link isn't lost when the scene is saved
+            Err(err) => {
+                log::error!("{err}");
+                instance.load_error = Some(err);
+            }
+        }
+    }
 }
 
 fn update_asset<Asset: crile::Asset>(
@@ -64,145 +169,85 @@ fn update_asset<Asset: crile::Asset>(
     }
 }
 
-fn inspect_entity(ui: &mut egui::Ui, entity: &mut crile::EntityMut) {
-    macro_rules! inspect_components {
-        ( [$($component: ty),*]) => {{
-            $( inspect_component::<$component>(ui, entity); )*
-        }};
+/// Returns true if any component was changed, added or removed
+fn inspect_entity(ui: &mut egui::Ui, scene: &mut crile::Scene, entity: crile::Entity) -> bool {
+    // Any entity in the scene can be picked for entity link fields
+    let link_targets = scene
+        .hierarchy_iter(crile::Scene::ROOT)
+        .map(|entity| {
+            (
+                scene.link(entity),
+                scene.get_node(entity).unwrap().name.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut changed = false;
+    for registration in scene.registry.iter() {
+        changed |= inspect_component(ui, registration, &mut scene.world, entity, &link_targets);
     }
 
-    crile::with_components!(inspect_components);
-
     ui.reset_style();
     ui.separator();
 
     ui.vertical_centered(|ui| {
         ui.menu_button("Add component", |ui| {
-            macro_rules! add_component_buttons {
-                ( [$($component: ty),*]) => {{
-                    $( add_component_button::<$component>(ui, entity); )*
-                }};
+            for registration in scene.registry.iter() {
+                changed |= add_component_button(ui, registration, &mut scene.world, entity);
             }
-
-            crile::with_components!(add_component_buttons);
         });
     });
+
+    changed
 }
 
-fn inspect_component<T: Inspectable + crile::Component>(
+fn inspect_component(
     ui: &mut egui::Ui,
-    entity: &mut crile::EntityMut,
-) {
-    if let Some(component) = entity.get::<T>() {
-        let pretty_name = crile::get_pretty_name::<T>();
+    registration: &crile::ComponentRegistration,
+    world: &mut crile::World,
+    entity: crile::Entity,
+    link_targets: &[(crile::EntityLink, String)],
+) -> bool {
+    let mut changed = false;
+    if let Some(component) = (registration.get_mut)(world, entity) {
+        let pretty_name = registration.pretty_name;
         ui.visuals_mut().collapsing_header_frame = true;
         ui.visuals_mut().widgets.noninteractive.bg_stroke.width = 0.;
 
         let response = egui::CollapsingHeader::new(pretty_name)
             .default_open(true)
             .show(ui, |ui| {
-                egui::Grid::new(pretty_name)
-                    .num_columns(2)
-                    .spacing([30.0, 4.0])
-                    .show(ui, |ui| component.inspect(ui));
+                changed = crile_egui::inspect_reflect(ui, pretty_name, component, link_targets);
             });
 
-        response.header_response.context_menu(move |ui| {
+        response.header_response.context_menu(|ui| {
             if ui.button("Remove component").clicked() {
-                entity.remove::<T>();
+                world
+                    .entity_mut(entity)
+                    .unwrap()
+                    .remove_by_id(registration.id());
+                changed = true;
                 ui.close_menu();
             }
         });
     }
+
+    changed
 }
 
-fn add_component_button<T: Inspectable + crile::Component>(
+fn add_component_button(
     ui: &mut egui::Ui,
-    entity: &mut crile::EntityMut,
-) {
-    if !entity.has::<T>() && ui.button(crile::get_pretty_name::<T>()).clicked() {
-        entity.add(T::default());
+    registration: &crile::ComponentRegistration,
+    world: &mut crile::World,
+    entity: crile::Entity,
+) -> bool {
+    let has_component = world
+        .entity(entity)
+        .is_some_and(|entity| entity.has_id(registration.id()));
+    if !has_component && ui.button(registration.pretty_name).clicked() {
+        (registration.insert_default)(world, entity);
         ui.close_menu();
+        return true;
     }
-}
-
-pub trait Inspectable {
-    fn inspect(&mut self, ui: &mut egui::Ui);
-}
-
-impl Inspectable for crile::TransformComponent {
-    fn inspect(&mut self, ui: &mut egui::Ui) {
-        ui.label("Translation");
-        crile_egui::inspect_vec3(ui, &mut self.translation);
-        ui.end_row();
-
-        ui.label("Rotation");
-        crile_egui::inspect_vec3(ui, &mut self.rotation);
-        ui.end_row();
-
-        ui.label("Scale");
-        crile_egui::inspect_vec3(ui, &mut self.scale);
-        ui.end_row();
-    }
-}
-
-impl Inspectable for crile::SpriteComponent {
-    fn inspect(&mut self, ui: &mut egui::Ui) {
-        ui.label("Color");
-        crile_egui::inspect_color(ui, &mut self.color);
-        ui.end_row();
-
-        ui.label("Texture");
-        crile_egui::inspect_asset_path(ui, &mut self.texture_path);
-    }
-}
-
-impl Inspectable for crile::ScriptComponent {
-    fn inspect(&mut self, ui: &mut egui::Ui) {
-        ui.label("Script");
-        crile_egui::inspect_asset_path(ui, &mut self.script_path);
-    }
-}
-
-impl Inspectable for crile::CameraComponent {
-    fn inspect(&mut self, ui: &mut egui::Ui) {
-        ui.label("Near");
-        crile_egui::inspect_f32(ui, &mut self.near);
-        ui.end_row();
-
-        ui.label("Far");
-        crile_egui::inspect_f32(ui, &mut self.far);
-        ui.end_row();
-
-        match self.projection_kind {
-            crile::ProjectionKind::Orthographic => {
-                ui.label("Zoom");
-                crile_egui::inspect_f32(ui, &mut self.orthographic_zoom);
-            }
-            crile::ProjectionKind::Perspective => {
-                ui.label("Fov");
-                crile_egui::inspect_f32(ui, &mut self.perspective_fov);
-            }
-        }
-        ui.end_row();
-
-        ui.label("Projection");
-        egui::ComboBox::from_id_salt("Projection")
-            .selected_text(format!("{:?}", self.projection_kind))
-            .width(ui.available_width())
-            .show_ui(ui, |ui| {
-                ui.selectable_value(
-                    &mut self.projection_kind,
-                    crile::ProjectionKind::Perspective,
-                    "Perspective",
-                );
-                ui.selectable_value(
-                    &mut self.projection_kind,
-                    crile::ProjectionKind::Orthographic,
-                    "Orthographic",
-                );
-            });
-        ui.end_row();
-        self.dirty = true;
-    }
+    false
 }
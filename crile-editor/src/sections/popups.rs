@@ -1,4 +1,4 @@
-use crate::{EditorState, PopupKind};
+use crate::{EditorState, PopupKind, Selection};
 
 pub fn show(
     ctx: &egui::Context,
@@ -32,6 +32,12 @@ pub fn show(
         PopupKind::Stats => {
             popup.show(ctx, |ui| {
                 ui.label(format!("FPS: {}", engine.time.frame_rate()));
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(ctx.screen_rect().height() / 2.)
+                    .show(ui, |ui| {
+                        world_stats(ui, &state.active_scene.world, &state.selection)
+                    });
             });
         }
         PopupKind::None => unreachable!(),
@@ -41,3 +47,117 @@ pub fn show(
         state.popup_open = PopupKind::None;
     }
 }
+
+/// Shows how the entities and components of the world are laid out to diagnose archetype explosion
+fn world_stats(ui: &mut egui::Ui, world: &crile::World, selection: &Selection) {
+    let entities = world.entity_stats();
+    let archetypes = world.archetype_stats();
+    egui::Grid::new("World stats grid")
+        .num_columns(2)
+        .striped(true)
+        .spacing([30.0, 4.0])
+        .show(ui, |ui| {
+            ui.label("Entities");
+            ui.label(format!(
+                "{} alive / {} slots",
+                entities.alive, entities.slots
+            ));
+            ui.end_row();
+
+            ui.label("Free slots");
+            ui.label(format!(
+                "{} ({:.1}% fragmented)",
+                entities.free,
+                entities.fragmentation() * 100.
+            ));
+            ui.end_row();
+
+            ui.label("Archetypes");
+            ui.label(format!(
+                "{} ({} empty)",
+                archetypes.archetypes, archetypes.empty_archetypes
+            ));
+            ui.end_row();
+
+            ui.label("Archetype edges");
+            ui.label(format!(
+                "{} add / {} remove",
+                archetypes.add_edges, archetypes.remove_edges
+            ));
+            ui.end_row();
+        });
+
+    egui::CollapsingHeader::new("Components").show(ui, |ui| {
+        egui::Grid::new("Component stats grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Name");
+                ui.strong("Entities");
+                ui.strong("Archetypes");
+                ui.strong("Memory");
+                ui.end_row();
+
+                for stats in world.component_stats() {
+                    ui.label(short_name(stats.typename))
+                        .on_hover_text(format!("{}\nStorage: {:?}", stats.typename, stats.storage));
+                    ui.label(stats.entities.to_string());
+                    ui.label(stats.archetypes.to_string());
+                    ui.label(format_bytes(stats.bytes));
+                    ui.end_row();
+                }
+            });
+    });
+
+    egui::CollapsingHeader::new("Archetypes").show(ui, |ui| {
+        egui::Grid::new("Archetype infos grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Components");
+                ui.strong("Entities");
+                ui.strong("Capacity");
+                ui.strong("Memory");
+                ui.end_row();
+
+                for info in world.archetype_infos() {
+                    let names: Vec<&str> = info
+                        .components
+                        .iter()
+                        .map(|name| short_name(name))
+                        .collect();
+                    ui.label(names.join(", "));
+                    ui.label(info.entities.to_string());
+                    ui.label(info.capacity.to_string());
+                    ui.label(format_bytes(info.bytes));
+                    ui.end_row();
+                }
+            });
+    });
+
+    if let Selection::Entity(entity) = *selection {
+        let Some(entity) = world.entity(entity) else {
+            return;
+        };
+        egui::CollapsingHeader::new(format!("Selected {}", entity.id())).show(ui, |ui| {
+            for info in entity.type_infos() {
+                ui.label(format!("{} ({} bytes)", info.typename, info.layout.size()));
+            }
+        });
+    }
+}
+
+/// Strips the module path from a type name
+fn short_name(typename: &str) -> &str {
+    typename.rsplit("::").next().unwrap_or(typename)
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f32 / 1024.)
+    } else {
+        format!("{:.1} MiB", bytes as f32 / (1024. * 1024.))
+    }
+}
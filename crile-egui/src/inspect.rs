@@ -43,3 +43,126 @@ pub fn inspect_asset_path(ui: &mut egui::Ui, asset_path: &mut crile::AssetPath)
         asset_path.open_picker = true;
     }
 }
+
+/// Shows a dropdown to pick which of the entities the link points to
+pub fn inspect_entity_link(
+    ui: &mut egui::Ui,
+    id: &str,
+    link: &mut crile::EntityLink,
+    link_targets: &[(crile::EntityLink, String)],
+) -> bool {
+    let old = *link;
+    let selected_text = match link.0 {
+        Some(_) => link_targets
+            .iter()
+            .find(|(target, _)| target == link)
+            .map_or("Missing", |(_, name)| name.as_str()),
+        None => "None",
+    };
+
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected_text)
+        .width(ui.available_width())
+        .show_ui(ui, |ui| {
+            ui.selectable_value(link, crile::EntityLink(None), "None");
+            for (target, name) in link_targets {
+                ui.selectable_value(link, *target, name);
+            }
+        });
+    old != *link
+}
+
+/// Shows a row with a widget for every reflected field of the value inside a grid
+/// Entity links can be set to one of the link targets
+/// Returns whether any of the fields were changed
+pub fn inspect_reflect(
+    ui: &mut egui::Ui,
+    id: &str,
+    value: &mut dyn crile::Reflect,
+    link_targets: &[(crile::EntityLink, String)],
+) -> bool {
+    let mut changed = false;
+    egui::Grid::new(id)
+        .num_columns(2)
+        .spacing([30.0, 4.0])
+        .show(ui, |ui| {
+            for name in value.field_names() {
+                let Some(field) = value.field_mut(name) else {
+                    continue;
+                };
+                ui.label(pretty_field_name(name));
+                changed |= inspect_field(ui, &format!("{id}.{name}"), field, link_targets);
+                ui.end_row();
+            }
+        });
+    changed
+}
+
+/// Picks the widget from the type of the field, nested structs get their own grid
+fn inspect_field(
+    ui: &mut egui::Ui,
+    id: &str,
+    field: &mut dyn crile::Reflect,
+    link_targets: &[(crile::EntityLink, String)],
+) -> bool {
+    if let Some(value) = field.downcast_mut::<glam::Vec3>() {
+        let old = *value;
+        inspect_vec3(ui, value);
+        return old != *value;
+    }
+    if let Some(value) = field.downcast_mut::<crile::Color>() {
+        return ui
+            .color_edit_button_rgba_premultiplied(&mut value.0)
+            .changed();
+    }
+    if let Some(value) = field.downcast_mut::<crile::AssetPath>() {
+        inspect_asset_path(ui, value);
+        return value.open_picker;
+    }
+    if let Some(link) = field.downcast_mut::<crile::EntityLink>() {
+        return inspect_entity_link(ui, id, link, link_targets);
+    }
+
+    let Some(value) = field.value() else {
+        return ui
+            .vertical(|ui| inspect_reflect(ui, id, field, link_targets))
+            .inner;
+    };
+
+    let mut new_value = value.clone();
+    match &mut new_value {
+        crile::FieldValue::Bool(value) => {
+            ui.checkbox(value, "");
+        }
+        crile::FieldValue::Int(value) => {
+            ui.vertical_centered_justified(|ui| ui.add(egui::DragValue::new(value)));
+        }
+        crile::FieldValue::Float(value) => {
+            ui.vertical_centered_justified(|ui| ui.add(egui::DragValue::new(value).speed(0.01)));
+        }
+        crile::FieldValue::String(value) if !field.variants().is_empty() => {
+            egui::ComboBox::from_id_salt(id)
+                .selected_text(value.as_str())
+                .width(ui.available_width())
+                .show_ui(ui, |ui| {
+                    for variant in field.variants() {
+                        ui.selectable_value(value, variant.to_string(), *variant);
+                    }
+                });
+        }
+        crile::FieldValue::String(value) => {
+            ui.text_edit_singleline(value);
+        }
+    }
+
+    new_value != value && field.set_value(new_value)
+}
+
+/// Turns a field name like "orthographic_zoom" into "Orthographic zoom"
+pub fn pretty_field_name(name: &str) -> String {
+    let mut pretty = name.replace('_', " ");
+    if let Some(first) = pretty.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    pretty
+}
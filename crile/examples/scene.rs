@@ -4,7 +4,7 @@ pub struct SceneApp {
     scene: crile::Scene,
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, crile::Component)]
 struct TestComponent {
     a: u32,
 }
@@ -29,6 +29,10 @@ impl crile::Application for SceneApp {
 
     fn update(&mut self, engine: &mut crile::Engine, event_loop: &crile::ActiveEventLoop) {}
 
+    fn world(&mut self) -> Option<&mut crile::World> {
+        Some(&mut self.scene.world)
+    }
+
     fn render(&mut self, engine: &mut crile::Engine) {
         let mut render_pass =
             crile::RenderPass::new(&mut engine.gfx, Some(crile::Color::BLACK), None, None);
@@ -1,6 +1,6 @@
 use std::path::{Path, PathBuf};
 
-use crate::{RefId, Script, Texture, WgpuContext};
+use crate::{ComponentRegistry, Prefab, RefId, Script, Texture, WgpuContext};
 
 type AssetMap<A> = hashbrown::HashMap<PathBuf, RefId<A>>;
 
@@ -55,10 +55,27 @@ impl Asset for Script {
     }
 }
 
+impl Asset for Prefab {
+    const PRETTY_NAME: &'static str = "Prefab";
+    const FILE_EXTENSIONS: &'static [&'static str] = &["prefab"];
+
+    /// Only keeps the components crile has, use [AssetManager::load_prefab] to keep game ones
+    fn load(_: &WgpuContext, path: &Path) -> Option<Self> {
+        Prefab::load(path, ComponentRegistry::default())
+            .inspect_err(|err| log::error!("{err}"))
+            .ok()
+    }
+
+    fn get_map(manager: &mut AssetManager) -> &mut AssetMap<Self> {
+        &mut manager.prefabs
+    }
+}
+
 #[derive(Default)]
 pub struct AssetManager {
     textures: AssetMap<Texture>,
     scripts: AssetMap<Script>,
+    prefabs: AssetMap<Prefab>,
 }
 
 impl AssetManager {
@@ -72,4 +89,24 @@ impl AssetManager {
         map.insert(path.to_path_buf(), asset.clone());
         Some(asset)
     }
+
+    /// Loads the prefab with the components of the registry of the scene it is used in
+    pub fn load_prefab(
+        &mut self,
+        path: &Path,
+        registry: &ComponentRegistry,
+    ) -> Result<RefId<Prefab>, String> {
+        if let Some(prefab) = self.prefabs.get(path) {
+            return Ok(prefab.clone());
+        }
+
+        let prefab = RefId::new(Prefab::load(path, registry.clone())?);
+        self.prefabs.insert(path.to_path_buf(), prefab.clone());
+        Ok(prefab)
+    }
+
+    /// Replaces the cached asset at the path such as after saving a new version of it
+    pub fn insert<A: Asset>(&mut self, path: &Path, asset: RefId<A>) {
+        A::get_map(self).insert(path.to_path_buf(), asset);
+    }
 }
@@ -1,16 +1,65 @@
-use std::{any::TypeId, ptr::NonNull};
+use std::ptr::NonNull;
 
-use crate::{Component, NoHashHashMap};
+use crate::{Entity, NoHashHashMap};
 
-use super::TypeInfo;
+use super::{BorrowFlag, ComponentId, StorageType, TypeInfo};
 
 #[derive(Clone)]
 pub struct Archetype {
     pub(crate) component_arrays: Box<[ComponentArray]>,
     /// Maps a component type id to its index inside [Self::component_arrays]
-    index_map: NoHashHashMap<TypeId, usize>,
+    index_map: NoHashHashMap<ComponentId, usize>,
     type_infos: Box<[TypeInfo]>,
-    pub(crate) entity_indexs: Vec<usize>,
+    pub(crate) entities: Vec<Entity>,
+    /// Archetypes that entities move to when a component is added or removed
+    pub(crate) edges: ArchetypeEdges,
+}
+
+/// Cached transitions from an archetype to the archetype with one component added or removed
+#[derive(Clone, Default)]
+pub(crate) struct ArchetypeEdges {
+    pub add: NoHashHashMap<ComponentId, usize>,
+    pub remove: NoHashHashMap<ComponentId, usize>,
+}
+
+/// Counts of a world's archetypes and the transitions between them for diagnostics
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ArchetypeStats {
+    pub archetypes: usize,
+    /// Archetypes without any entities in them
+    pub empty_archetypes: usize,
+    /// Cached transitions to the archetype with a component added
+    pub add_edges: usize,
+    /// Cached transitions to the archetype with a component removed
+    pub remove_edges: usize,
+}
+
+/// Description of an archetype for debugging tools, see [crate::World::archetype_infos]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchetypeInfo {
+    /// Index of the archetype inside the world
+    pub index: usize,
+    /// Names of the components stored in the archetype, see [TypeInfo::typename]
+    pub components: Vec<&'static str>,
+    pub entities: usize,
+    /// Number of entities that fit before the arrays have to grow
+    pub capacity: usize,
+    /// Memory allocated for the entities, their components and change ticks
+    pub bytes: usize,
+}
+
+/// Totals of a component type across the world, see [crate::World::component_stats]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentStats {
+    pub id: ComponentId,
+    pub typename: &'static str,
+    pub storage: StorageType,
+    /// Number of entities with the component
+    pub entities: usize,
+    /// Number of archetypes containing the component, always 0 for sparse set components
+    pub archetypes: usize,
+    /// Memory allocated for the components and their change ticks
+    pub bytes: usize,
 }
 
 impl Archetype {
@@ -41,8 +90,9 @@ impl Archetype {
         Self {
             type_infos,
             index_map,
-            entity_indexs: Vec::with_capacity(Self::START_CAP),
+            entities: Vec::with_capacity(Self::START_CAP),
             component_arrays,
+            edges: ArchetypeEdges::default(),
         }
     }
 
@@ -52,8 +102,8 @@ impl Archetype {
     /// # Safety
     /// - Component real type must match the type id.
     /// - The component must not be dropped and must not be used elsewhere after calling this function. Use [Self::push_component_cloned] otherwise.
-    pub unsafe fn push_component(&mut self, component_ptr: *const u8, type_id: TypeId) {
-        let array = self.get_array_mut(&type_id).expect("id does not exist");
+    pub unsafe fn push_component(&mut self, component_ptr: *const u8, id: ComponentId) {
+        let array = self.get_array_mut(id).expect("id does not exist");
         let array_ptr = array.alloc_push_space();
         std::ptr::copy_nonoverlapping(component_ptr, array_ptr, array.type_info.layout.size());
     }
@@ -63,34 +113,37 @@ impl Archetype {
     ///
     /// # Safety
     /// - Component real type must match the type id.
-    pub unsafe fn push_component_cloned(&mut self, component_ptr: *const u8, type_id: TypeId) {
-        let array = self.get_array_mut(&type_id).expect("id does not exist");
+    pub unsafe fn push_component_cloned(&mut self, component_ptr: *const u8, id: ComponentId) {
+        let array = self.get_array_mut(id).expect("id does not exist");
         let array_ptr = array.alloc_push_space();
         (array.type_info.clone_to)(component_ptr, array_ptr);
     }
 
-    /// # Safety
-    /// - Component reference must follow Rust borrow rules
-    pub(crate) unsafe fn borrow_component<T: Component>(
-        &self,
-        component_index: usize,
-    ) -> Option<&mut T> {
-        let array = self.get_array(&TypeId::of::<T>())?;
-        Some(&mut *array.get_component_ptr(component_index).cast::<T>())
+    /// Gets the index of the component's array inside [Self::component_arrays]
+    pub(crate) fn array_index(&self, id: ComponentId) -> Option<usize> {
+        self.index_map.get(&id).copied()
+    }
+
+    pub(crate) fn has_id(&self, id: ComponentId) -> bool {
+        self.index_map.contains_key(&id)
     }
 
-    pub(crate) fn has_component<T: 'static>(&self) -> bool {
-        self.index_map.contains_key(&TypeId::of::<T>())
+    /// Reserves space for at least the number of additional entities
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+        for array in self.component_arrays.iter_mut() {
+            array.reserve(additional);
+        }
     }
 
     /// Returns the entity index inside this archetype
-    pub(crate) fn new_entity(&mut self, entity_index: usize) -> usize {
-        self.entity_indexs.push(entity_index);
-        self.entity_indexs.len() - 1
+    pub(crate) fn new_entity(&mut self, entity: Entity) -> usize {
+        self.entities.push(entity);
+        self.entities.len() - 1
     }
 
-    /// Returns the entity index of the component that was swapped
-    pub(crate) fn remove_entity(&mut self, component_index: usize, should_drop: bool) -> usize {
+    /// Returns the entity of the component that was swapped
+    pub(crate) fn remove_entity(&mut self, component_index: usize, should_drop: bool) -> Entity {
         assert!(component_index < self.count());
 
         // Moves the last item to index and decrement length by 1
@@ -98,20 +151,27 @@ impl Archetype {
             array.swap_remove(component_index, should_drop);
         }
 
-        let moved_index = *self.entity_indexs.last().unwrap();
-        self.entity_indexs.swap_remove(component_index);
-        moved_index
+        let moved = *self.entities.last().unwrap();
+        self.entities.swap_remove(component_index);
+        moved
+    }
+
+    /// Sets the ticks of every component of the entity at the index
+    pub(crate) fn set_ticks(&mut self, component_index: usize, ticks: ComponentTicks) {
+        for array in self.component_arrays.iter_mut() {
+            array.ticks[component_index] = ticks;
+        }
     }
 
-    pub(crate) fn get_array(&self, id: &TypeId) -> Option<&ComponentArray> {
-        let index = self.index_map.get(id)?;
+    pub(crate) fn get_array(&self, id: ComponentId) -> Option<&ComponentArray> {
+        let index = self.index_map.get(&id)?;
         self.component_arrays.get(*index)
     }
 
-    pub(crate) fn get_array_mut(&mut self, id: &TypeId) -> Option<&mut ComponentArray> {
-        let index = self.index_map.get(id)?;
+    pub(crate) fn get_array_mut(&mut self, id: ComponentId) -> Option<&mut ComponentArray> {
+        let index = self.index_map.get(&id)?;
         let array = self.component_arrays.get_mut(*index)?;
-        assert_eq!(array.count + 1, self.entity_indexs.len()); // Check new_entity was called
+        assert_eq!(array.count + 1, self.entities.len()); // Check new_entity was called
         Some(array)
     }
 
@@ -120,7 +180,37 @@ impl Archetype {
     }
 
     pub fn count(&self) -> usize {
-        self.entity_indexs.len()
+        self.entities.len()
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.entities.capacity()
+    }
+
+    /// Memory allocated for the entities, their components and change ticks
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        let arrays: usize = self
+            .component_arrays
+            .iter()
+            .map(ComponentArray::allocated_bytes)
+            .sum();
+        arrays + self.entities.capacity() * std::mem::size_of::<Entity>()
+    }
+}
+
+/// The world ticks of when a component was added and last mutably accessed
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u32) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
     }
 }
 
@@ -132,6 +222,10 @@ pub(crate) struct ComponentArray {
     type_info: TypeInfo,
     capacity: usize,
     count: usize,
+    /// Change detection ticks of each component, kept in the same order as the components
+    pub(crate) ticks: Vec<ComponentTicks>,
+    /// Runtime borrow checking for [crate::WorldCell]
+    pub(crate) borrow: BorrowFlag,
 }
 
 impl ComponentArray {
@@ -141,10 +235,12 @@ impl ComponentArray {
             capacity: 0,
             type_info,
             count: 0,
+            ticks: Vec::new(),
+            borrow: BorrowFlag::default(),
         }
     }
 
-    fn with_capacity(type_info: TypeInfo, capacity: usize) -> Self {
+    pub(crate) fn with_capacity(type_info: TypeInfo, capacity: usize) -> Self {
         let mut array = Self::new(type_info);
         array.grow(capacity);
         array
@@ -180,13 +276,23 @@ impl ComponentArray {
         }
     }
 
-    fn swap_remove(&mut self, index: usize, drop: bool) {
+    fn reserve(&mut self, additional: usize) {
+        let required = self.count + additional;
+        if required > self.capacity {
+            self.grow(required.max(self.capacity * 2));
+        }
+        self.ticks.reserve(additional);
+    }
+
+    pub(crate) fn swap_remove(&mut self, index: usize, drop: bool) {
         if drop {
             unsafe {
                 self.drop_component(index);
             }
         }
 
+        self.ticks.swap_remove(index);
+
         let last_index = self.count - 1;
         self.count -= 1;
         if index == last_index {
@@ -195,8 +301,9 @@ impl ComponentArray {
 
         let size = self.type_info.layout.size();
         unsafe {
-            let ptr_src = self.get_ptr().add(index * size);
-            let ptr_dst = self.get_ptr().add(last_index * size);
+            // Move the last component into the removed slot
+            let ptr_src = self.get_ptr().add(last_index * size);
+            let ptr_dst = self.get_ptr().add(index * size);
             std::ptr::copy_nonoverlapping(ptr_src, ptr_dst, size);
         }
     }
@@ -215,10 +322,25 @@ impl ComponentArray {
         std::alloc::Layout::from_size_align(capacity * size, align).unwrap()
     }
 
-    pub fn get_type_id(&self) -> TypeId {
+    pub fn get_id(&self) -> ComponentId {
         self.type_info.id
     }
 
+    /// Memory allocated for the components and their change ticks
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        self.capacity * self.type_info.layout.size()
+            + self.ticks.capacity() * std::mem::size_of::<ComponentTicks>()
+    }
+
+    pub(crate) fn type_info(&self) -> &TypeInfo {
+        &self.type_info
+    }
+
+    /// Gets a raw ptr to the ticks array which can be written to when the world is mutably borrowed
+    pub(crate) fn get_ticks_ptr(&self) -> *mut ComponentTicks {
+        self.ticks.as_ptr().cast_mut()
+    }
+
     // Gets a raw ptr to a component inside this array
     pub fn get_component_ptr(&self, index: usize) -> *mut u8 {
         assert!(index <= self.count);
@@ -226,7 +348,9 @@ impl ComponentArray {
         unsafe { self.get_ptr().add(offset) }
     }
 
-    fn alloc_push_space(&mut self) -> *mut u8 {
+    pub(crate) fn alloc_push_space(&mut self) -> *mut u8 {
+        // The ticks are set afterwards by the world
+        self.ticks.push(ComponentTicks::default());
         self.count += 1;
         if self.count > self.capacity {
             self.grow(self.capacity * 2);
@@ -240,6 +364,7 @@ impl Clone for ComponentArray {
     fn clone(&self) -> Self {
         let mut component_array = Self::with_capacity(self.type_info.clone(), self.capacity);
         component_array.count = self.count;
+        component_array.ticks.clone_from(&self.ticks);
 
         for i in 0..self.count {
             // Call clone on each component
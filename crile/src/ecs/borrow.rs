@@ -0,0 +1,162 @@
+use std::{
+    fmt::Display,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
+use super::{Component, ComponentId, Entity, World};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BorrowError {
+    /// The entity doesn't exist or doesn't have the component
+    NotFound,
+    /// Tried to borrow a component while it is mutably borrowed
+    AlreadyBorrowedMut,
+    /// Tried to mutably borrow a component while it is borrowed
+    AlreadyBorrowed,
+}
+
+impl Display for BorrowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BorrowError::NotFound => write!(f, "entity or component does not exist"),
+            BorrowError::AlreadyBorrowedMut => write!(f, "component is already mutably borrowed"),
+            BorrowError::AlreadyBorrowed => write!(f, "component is already borrowed"),
+        }
+    }
+}
+
+impl std::error::Error for BorrowError {}
+
+/// Tracks the borrows of a component array
+/// Positive is the number of shared borrows and -1 is a mutable borrow
+#[derive(Default)]
+pub(crate) struct BorrowFlag(AtomicIsize);
+
+impl BorrowFlag {
+    fn borrow(&self) -> Result<(), BorrowError> {
+        self.0
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                (count >= 0).then_some(count + 1)
+            })
+            .map(|_| ())
+            .map_err(|_| BorrowError::AlreadyBorrowedMut)
+    }
+
+    fn borrow_mut(&self) -> Result<(), BorrowError> {
+        self.0
+            .compare_exchange(0, -1, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|count| {
+                if count < 0 {
+                    BorrowError::AlreadyBorrowedMut
+                } else {
+                    BorrowError::AlreadyBorrowed
+                }
+            })
+    }
+
+    fn release(&self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+
+    fn release_mut(&self) {
+        self.0.store(0, Ordering::Release);
+    }
+}
+
+/// A shared borrow of a component obtained from [WorldCell::get]
+pub struct Ref<'a, T> {
+    value: &'a T,
+    flag: &'a BorrowFlag,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> Drop for Ref<'_, T> {
+    fn drop(&mut self) {
+        self.flag.release();
+    }
+}
+
+/// A mutable borrow of a component obtained from [WorldCell::get_mut]
+pub struct RefMut<'a, T> {
+    value: &'a mut T,
+    flag: &'a BorrowFlag,
+}
+
+impl<T> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Drop for RefMut<'_, T> {
+    fn drop(&mut self) {
+        self.flag.release_mut();
+    }
+}
+
+/// Allows components of different types to be borrowed mutably at the same time
+///
+/// Every component array has a borrow flag which is checked at runtime, like a RefCell.
+/// Created with [World::cell] which mutably borrows the world so nothing can get around the checks.
+pub struct WorldCell<'w> {
+    world: &'w World,
+}
+
+impl<'w> WorldCell<'w> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        Self { world }
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, BorrowError> {
+        let (array, index) = self
+            .world
+            .component_array(entity, ComponentId::of::<T>())
+            .ok_or(BorrowError::NotFound)?;
+
+        array.borrow.borrow()?;
+        Ok(Ref {
+            // SAFETY: The borrow flag makes sure there is no mutable reference
+            value: unsafe { &*array.get_component_ptr(index).cast::<T>() },
+            flag: &array.borrow,
+        })
+    }
+
+    /// Mutably borrows the component and marks it as changed
+    pub fn get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, BorrowError> {
+        let (array, index) = self
+            .world
+            .component_array(entity, ComponentId::of::<T>())
+            .ok_or(BorrowError::NotFound)?;
+
+        array.borrow.borrow_mut()?;
+        // SAFETY: The borrow flag makes sure there are no other references to the array
+        unsafe {
+            (*array.get_ticks_ptr().add(index)).changed = self.world.change_tick();
+            Ok(RefMut {
+                value: &mut *array.get_component_ptr(index).cast::<T>(),
+                flag: &array.borrow,
+            })
+        }
+    }
+
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.world.exists(entity)
+    }
+}
@@ -0,0 +1,76 @@
+use super::{Component, ComponentTuple, Entity, World};
+
+type Command<T> = Box<dyn FnOnce(&mut T)>;
+
+/// Queue of structural changes to apply to T later on
+///
+/// Useful for when the world is being iterated through so entities cannot be spawned, despawned or
+/// have components added or removed until the iteration has finished.
+/// T is [World] by default but can be anything else that owns a world like [crate::Scene].
+pub struct Commands<T = World> {
+    queue: Vec<Command<T>>,
+}
+
+impl<T> Default for Commands<T> {
+    fn default() -> Self {
+        Self { queue: Vec::new() }
+    }
+}
+
+impl<T> Commands<T> {
+    /// Adds a custom command to the queue
+    pub fn push(&mut self, command: impl FnOnce(&mut T) + 'static) {
+        self.queue.push(Box::new(command));
+    }
+
+    /// Runs every command in the order they were added then clears the queue
+    pub fn apply(&mut self, target: &mut T) {
+        for command in self.queue.drain(..) {
+            command(target);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+impl Commands<World> {
+    pub fn spawn<C: ComponentTuple + 'static>(&mut self, components: C) {
+        self.push(move |world| {
+            world.spawn(components);
+        });
+    }
+
+    /// Despawns the entity if it still exists when the commands are applied
+    pub fn despawn(&mut self, entity: Entity) {
+        self.push(move |world| {
+            if world.exists(entity) {
+                world.despawn(entity);
+            }
+        });
+    }
+
+    /// Adds the component to the entity or replaces it if it already has one
+    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) {
+        self.push(move |world| {
+            if let Some(mut entity) = world.entity_mut(entity) {
+                entity.insert(component);
+            }
+        });
+    }
+
+    pub fn remove<C: Component>(&mut self, entity: Entity) {
+        self.push(move |world| {
+            if let Some(mut entity) = world.entity_mut(entity) {
+                if entity.has::<C>() {
+                    entity.remove::<C>();
+                }
+            }
+        });
+    }
+}
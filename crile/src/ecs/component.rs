@@ -1,114 +1,120 @@
+use std::{
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+};
+
 use crate::TypeInfo;
-use std::any::TypeId;
 
-use super::Archetype;
+use super::{Archetype, ComponentId};
+
+pub use crile_derive::Component;
 
 /// Represents a usable component type
-/// Shorthand for 'static + Clone + Default
+///
+/// It has to be implemented for each type with `#[derive(Component)]` or by hand with
+/// `impl Component for MyType {}` so tuples never count as components, which lets them be nested
+/// inside each other as [ComponentTuple]s.
 pub trait Component: 'static + Clone + Default {}
-impl<T: 'static + Clone + Default> Component for T {}
 
-/// Represents a tuple of components of any type
-/// It is automatically implemented for every tuple type (maximum 8 elements in a tuple)
+/// Represents a group of components that can be added to an entity at once
+///
+/// It is implemented for every component and for tuples of up to 16 component tuples, so tuples
+/// can be nested to compose reusable bundles, e.g. `(transform_bundle(), (Sprite, Script))`.
+/// The total number of components can't be greater than [MAX_TUPLE_COMPONENTS].
 pub trait ComponentTuple {
-    /// Fixed sized array of type T with the length being the number of components inside this tuple
-    type FixedArray<T>;
-
-    /// Gets the array of sorted type infos for this tuple
-    // TODO: remove heap allocation
-    fn type_infos() -> Box<[TypeInfo]>;
+    /// Pushes the type info of every component in the tuple, including nested ones
+    fn push_type_infos(infos: &mut TypeInfos);
 
     /// Moves every component from the tuple into the archetype
     fn move_all(self, archetype: &mut Archetype);
 
-    /// Gets an array of component arrays from the archetype that matches this component tuple
-    /// We use a static sized tuple to prevent unnecessary heap allocation
-    fn get_array_ptrs(archetype: &Archetype) -> Option<Self::FixedArray<*mut u8>>;
-
-    /// The tuple but every component as a ref
-    type RefTuple<'a>;
-    /// The tuple but every component as a mut ref
-    type MutTuple<'a>;
-
-    /// Gets the component tuple as a reference to each component from the component array pointer
-    /// obtained from [Self::get_array_ptrs]
-    ///
-    /// # Safety
-    /// - Index must not be greater than the component array size
-    /// - Since it returns mutable references to each component, it assumes borrow rules have been it met
-    unsafe fn array_ptr_array_get<'a>(
-        array_ptrs: &Self::FixedArray<*mut u8>,
-        index: usize,
-    ) -> Self::MutTuple<'a>;
-
-    // Converts a tuple of mutable component reference to non mutable ones
-    fn mut_to_ref(mut_tuple: Self::MutTuple<'_>) -> Self::RefTuple<'_>;
+    /// Gets the sorted type infos for this tuple
+    fn type_infos() -> TypeInfos {
+        let mut infos = TypeInfos::default();
+        Self::push_type_infos(&mut infos);
+        infos.sort_unstable();
+        infos
+    }
 }
 
-/// Counts the number of identifiers as input
-/// Useful for counting macro repetition
-macro_rules! count_idents {
-    ($($idents:ident),*) => {
-        {
-            #[allow(dead_code, non_camel_case_types)]
-            enum Idents { $($idents,)* __CountIdentsLast }
-            Idents::__CountIdentsLast as usize
+impl<T: Component> ComponentTuple for T {
+    fn push_type_infos(infos: &mut TypeInfos) {
+        infos.push(TypeInfo::of::<T>());
+    }
+
+    fn move_all(self, archetype: &mut Archetype) {
+        unsafe {
+            archetype.push_component(&self as *const T as *const u8, ComponentId::of::<T>());
+            std::mem::forget(self);
         }
-    };
+    }
+}
+
+/// Maximum number of components in a [ComponentTuple] including nested ones
+pub const MAX_TUPLE_COMPONENTS: usize = 32;
+
+/// The type infos of a [ComponentTuple] stored inline so spawning doesn't allocate
+pub struct TypeInfos {
+    infos: [MaybeUninit<TypeInfo>; MAX_TUPLE_COMPONENTS],
+    len: usize,
+}
+
+impl Default for TypeInfos {
+    fn default() -> Self {
+        Self {
+            infos: [const { MaybeUninit::uninit() }; MAX_TUPLE_COMPONENTS],
+            len: 0,
+        }
+    }
+}
+
+impl TypeInfos {
+    /// # Panics
+    /// - There are already [MAX_TUPLE_COMPONENTS] type infos
+    pub fn push(&mut self, info: TypeInfo) {
+        assert!(
+            self.len < MAX_TUPLE_COMPONENTS,
+            "Component tuples can't have more than {MAX_TUPLE_COMPONENTS} components"
+        );
+        self.infos[self.len].write(info);
+        self.len += 1;
+    }
+}
+
+impl Deref for TypeInfos {
+    type Target = [TypeInfo];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The first len infos are initialized
+        unsafe { std::slice::from_raw_parts(self.infos.as_ptr().cast(), self.len) }
+    }
+}
+
+impl DerefMut for TypeInfos {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { std::slice::from_raw_parts_mut(self.infos.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl Drop for TypeInfos {
+    fn drop(&mut self) {
+        unsafe { std::ptr::drop_in_place(&mut **self) };
+    }
 }
 
 /// Macro to automatically impl ComponentTuple for the specified tuple type
 macro_rules! tuple_impl {
     ($($type: ident),*) => {
-        impl<$($type: Component),*> ComponentTuple for ($($type,)*) {
-            type FixedArray<T> = [T; count_idents!($($type),*)];
-
-            fn type_infos() -> Box<[TypeInfo]> {
-                let mut infos = [$(TypeInfo::of::<$type>()),*];
-                infos.sort_unstable();
-                Box::new(infos)
+        impl<$($type: ComponentTuple),*> ComponentTuple for ($($type,)*) {
+            #[allow(unused)]
+            fn push_type_infos(infos: &mut TypeInfos) {
+                $($type::push_type_infos(infos);)*
             }
 
             #[allow(non_snake_case, unused)]
             fn move_all(self, archetype: &mut Archetype) {
                 let ($($type,)*) = self;
-                $(
-                    unsafe {
-                        archetype.push_component(&$type as *const $type as *const u8, TypeId::of::<$type>());
-                        std::mem::forget($type);
-                    }
-                )*
-            }
-
-            #[allow(unused)]
-            fn get_array_ptrs(archetype: &Archetype) -> Option<Self::FixedArray<*mut u8>> {
-                Some([
-                    $(
-                        unsafe { archetype.get_array(&TypeId::of::<$type>())?.get_ptr() }
-                    ),*
-                ])
-            }
-
-            type RefTuple<'a> = ($(&'a $type,)*);
-            type MutTuple<'a> = ($(&'a mut $type,)*);
-
-            #[allow(non_snake_case, unused, clippy::unused_unit)]
-            unsafe fn array_ptr_array_get<'a>(
-                ptr_array: &Self::FixedArray<*mut u8>,
-                index: usize,
-            ) -> Self::MutTuple<'a> {
-                let [$($type,)*] = ptr_array;
-                (
-                    $(
-                        &mut *$type.cast::<$type>().add(index),
-                    )*
-                )
-            }
-
-            #[allow(non_snake_case, clippy::unused_unit)]
-            fn mut_to_ref(mut_tuple: Self::MutTuple<'_>) -> Self::RefTuple<'_> {
-                let ($($type,)*) = mut_tuple;
-                ( $($type,)* )
+                $($type.move_all(archetype);)*
             }
         }
     };
@@ -126,4 +132,4 @@ macro_rules! recursive_impl {
 }
 
 // Expands to tuple_impl!(T1), tuple_impl!(T1, T2), tuple_impl!(T1, T2, T3), etc.
-recursive_impl!(T1, T2, T3, T4, T5);
+recursive_impl!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16);
@@ -0,0 +1,214 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Component, ComponentId, TypeInfo};
+
+/// The type of a field in a runtime defined component
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Bool,
+    Int,
+    Float,
+    String,
+}
+
+impl FieldKind {
+    pub fn default_value(self) -> FieldValue {
+        match self {
+            FieldKind::Bool => FieldValue::Bool(false),
+            FieldKind::Int => FieldValue::Int(0),
+            FieldKind::Float => FieldValue::Float(0.),
+            FieldKind::String => FieldValue::String(String::new()),
+        }
+    }
+}
+
+/// The value of a field in a runtime defined component
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl FieldValue {
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            FieldValue::Bool(_) => FieldKind::Bool,
+            FieldValue::Int(_) => FieldKind::Int,
+            FieldValue::Float(_) => FieldKind::Float,
+            FieldValue::String(_) => FieldKind::String,
+        }
+    }
+
+    /// Converts the value into the kind if it can be done without losing information
+    pub fn convert(self, kind: FieldKind) -> Option<FieldValue> {
+        match (self, kind) {
+            (value, kind) if value.kind() == kind => Some(value),
+            (FieldValue::Int(value), FieldKind::Float) => Some(FieldValue::Float(value as f64)),
+            (FieldValue::Float(value), FieldKind::Int) if value.fract() == 0. => {
+                Some(FieldValue::Int(value as i64))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+    pub kind: FieldKind,
+}
+
+/// Describes a component type defined at runtime
+/// Registered with [crate::World::register_component]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ComponentSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>,
+}
+
+impl ComponentSchema {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn field(mut self, name: impl Into<String>, kind: FieldKind) -> Self {
+        self.fields.push(FieldSchema {
+            name: name.into(),
+            kind,
+        });
+        self
+    }
+
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+
+    /// Creates the component with every field set to its default value
+    pub fn default_component(&self) -> DynamicComponent {
+        DynamicComponent {
+            values: self
+                .fields
+                .iter()
+                .map(|field| field.kind.default_value())
+                .collect(),
+        }
+    }
+
+    /// Checks if the component has a value of the right kind for each field
+    pub fn matches(&self, component: &DynamicComponent) -> bool {
+        self.fields.len() == component.values.len()
+            && self
+                .fields
+                .iter()
+                .zip(&component.values)
+                .all(|(field, value)| field.kind == value.kind())
+    }
+}
+
+/// The data of a runtime defined component
+/// Stores a value for each field in the order of its [ComponentSchema]
+#[derive(Clone, Default, PartialEq, Debug, Component)]
+pub struct DynamicComponent {
+    values: Vec<FieldValue>,
+}
+
+impl DynamicComponent {
+    pub fn values(&self) -> &[FieldValue] {
+        &self.values
+    }
+
+    pub fn get(&self, index: usize) -> Option<&FieldValue> {
+        self.values.get(index)
+    }
+
+    /// Sets the field at the index, converting the value to the kind of the field
+    /// Returns false if the index doesn't exist or the value couldn't be converted
+    pub fn set(&mut self, index: usize, value: FieldValue) -> bool {
+        let Some(field) = self.values.get_mut(index) else {
+            return false;
+        };
+
+        match value.convert(field.kind()) {
+            Some(value) => {
+                *field = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The components registered at runtime in a world
+#[derive(Clone, Default)]
+pub(crate) struct DynamicComponents {
+    schemas: Vec<ComponentSchema>,
+    type_infos: Vec<TypeInfo>,
+}
+
+impl DynamicComponents {
+    pub fn register(&mut self, schema: ComponentSchema) -> ComponentId {
+        if let Some(id) = self.id(&schema.name) {
+            assert_eq!(
+                self.schema(id),
+                Some(&schema),
+                "component '{}' was already registered with different fields",
+                schema.name
+            );
+            return id;
+        }
+
+        let index = self.schemas.len() as u32;
+        self.type_infos
+            .push(TypeInfo::dynamic(index, intern_name(&schema.name)));
+        self.schemas.push(schema);
+        ComponentId::Dynamic(index)
+    }
+
+    pub fn id(&self, name: &str) -> Option<ComponentId> {
+        let index = self.schemas.iter().position(|schema| schema.name == name)?;
+        Some(ComponentId::Dynamic(index as u32))
+    }
+
+    pub fn schema(&self, id: ComponentId) -> Option<&ComponentSchema> {
+        match id {
+            ComponentId::Dynamic(index) => self.schemas.get(index as usize),
+            ComponentId::Type(_) => None,
+        }
+    }
+
+    pub fn type_info(&self, id: ComponentId) -> Option<&TypeInfo> {
+        match id {
+            ComponentId::Dynamic(index) => self.type_infos.get(index as usize),
+            ComponentId::Type(_) => None,
+        }
+    }
+
+    pub fn schemas(&self) -> impl Iterator<Item = (ComponentId, &ComponentSchema)> {
+        self.schemas
+            .iter()
+            .enumerate()
+            .map(|(index, schema)| (ComponentId::Dynamic(index as u32), schema))
+    }
+}
+
+/// Type infos need a static name so each component name is only allocated once for all worlds
+fn intern_name(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let mut names = NAMES.lock().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned;
+    }
+    let interned = Box::leak(name.to_owned().into_boxed_str());
+    names.insert(interned);
+    interned
+}
@@ -0,0 +1,73 @@
+/// Handle to an entity inside a [crate::World]
+///
+/// The index is reused once the entity is despawned so the generation is incremented every time
+/// that happens. This means a stale handle will never point to a different entity that took its slot.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Entity {
+    pub(crate) index: u32,
+    pub(crate) generation: u32,
+}
+
+impl Entity {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self {
+            index: index.try_into().expect("Too many entities"),
+            generation,
+        }
+    }
+
+    /// The slot of the entity inside the world
+    /// Only unique among alive entities, use the whole handle to identify entities over time
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Packs the entity into a single u64 with the generation in the upper bits
+    pub fn to_bits(self) -> u64 {
+        ((self.generation as u64) << 32) | self.index as u64
+    }
+
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            index: bits as u32,
+            generation: (bits >> 32) as u32,
+        }
+    }
+}
+
+/// Usage of the entity slots of a world, see [crate::World::entity_stats]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct EntityStats {
+    pub alive: usize,
+    /// Slots allocated for entities whether they are alive or not
+    pub slots: usize,
+    /// Slots of despawned entities waiting to be reused
+    pub free: usize,
+}
+
+impl EntityStats {
+    /// Fraction of the slots that are free, from 0 to 1
+    pub fn fragmentation(&self) -> f32 {
+        if self.slots == 0 {
+            return 0.;
+        }
+        self.free as f32 / self.slots as f32
+    }
+}
+
+// Hash as a single u64 so it can be used inside a NoHashHashMap
+impl std::hash::Hash for Entity {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.to_bits());
+    }
+}
+
+impl std::fmt::Display for Entity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Entity({}v{})", self.index, self.generation)
+    }
+}
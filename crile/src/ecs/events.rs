@@ -0,0 +1,154 @@
+use std::marker::PhantomData;
+
+#[derive(Clone)]
+struct EventInstance<E> {
+    /// Number of events sent before this one
+    id: usize,
+    event: E,
+}
+
+/// Double buffered queue of events of type E, stored in the world as a resource
+///
+/// Events are kept for the frame they are sent in and the next one, so readers running before
+/// the writer in a frame still see them. [crate::World::update_events] is called by the engine
+/// loop at the end of every frame to drop the oldest buffer.
+#[derive(Clone)]
+pub struct Events<E> {
+    /// Events sent during the previous frame
+    previous: Vec<EventInstance<E>>,
+    /// Events sent during the current frame
+    current: Vec<EventInstance<E>>,
+    /// Total number of events sent, used as the id of the next event
+    event_count: usize,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            event_count: 0,
+        }
+    }
+}
+
+impl<E> Events<E> {
+    pub fn send(&mut self, event: E) {
+        self.current.push(EventInstance {
+            id: self.event_count,
+            event,
+        });
+        self.event_count += 1;
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Swaps the buffers, dropping the events sent before the previous frame
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// Creates a reader that will see every event that is still buffered
+    pub fn reader(&self) -> EventReader<E> {
+        EventReader::default()
+    }
+
+    /// Creates a reader that will only see the events sent after this call
+    pub fn reader_current(&self) -> EventReader<E> {
+        EventReader {
+            last_read: self.event_count,
+            marker: PhantomData,
+        }
+    }
+
+    /// Number of buffered events
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every buffered event without waiting for the next updates
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    fn iter_from(&self, id: usize) -> impl Iterator<Item = &EventInstance<E>> {
+        self.previous
+            .iter()
+            .chain(self.current.iter())
+            .skip_while(move |instance| instance.id < id)
+    }
+}
+
+/// Cursor into an [Events] queue so each reader sees each event only once
+/// It can be kept between frames, e.g. captured by a system like a [crate::QueryState]
+pub struct EventReader<E> {
+    /// Id of the next event to read
+    last_read: usize,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        Self {
+            last_read: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> Clone for EventReader<E> {
+    fn clone(&self) -> Self {
+        Self {
+            last_read: self.last_read,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> EventReader<E> {
+    /// Iterates over the events that this reader hasn't seen yet
+    /// Events that were dropped before being read are skipped
+    pub fn read<'a>(&mut self, events: &'a Events<E>) -> impl Iterator<Item = &'a E> {
+        let start = self.last_read;
+        self.last_read = events.event_count;
+        events.iter_from(start).map(|instance| &instance.event)
+    }
+
+    /// Number of events this reader hasn't seen yet
+    pub fn len(&self, events: &Events<E>) -> usize {
+        events.iter_from(self.last_read).count()
+    }
+
+    pub fn is_empty(&self, events: &Events<E>) -> bool {
+        self.len(events) == 0
+    }
+}
+
+/// Sends events to an [Events] queue
+pub struct EventWriter<'a, E> {
+    events: &'a mut Events<E>,
+}
+
+impl<'a, E> EventWriter<'a, E> {
+    pub fn new(events: &'a mut Events<E>) -> Self {
+        Self { events }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = E>) {
+        self.events.send_batch(events);
+    }
+}
@@ -0,0 +1,76 @@
+use std::rc::Rc;
+
+use super::{ComponentId, Entity, World};
+use crate::NoHashHashMap;
+
+/// Function called with the world and the entity that triggered it
+pub type Hook = Rc<dyn Fn(&mut World, Entity)>;
+
+/// Functions that run when a component type is added to or removed from an entity
+///
+/// Hooks get the whole world mutably so they can make structural changes, including to the entity
+/// that triggered them.
+#[derive(Clone, Default)]
+pub struct ComponentHooks {
+    pub(crate) on_add: Option<Hook>,
+    pub(crate) on_insert: Option<Hook>,
+    pub(crate) on_remove: Option<Hook>,
+}
+
+impl ComponentHooks {
+    /// Called after the component is added to an entity that didn't have one
+    pub fn on_add(&mut self, hook: impl Fn(&mut World, Entity) + 'static) -> &mut Self {
+        self.on_add = Some(Rc::new(hook));
+        self
+    }
+
+    /// Called after the component is added or replaced, runs after [Self::on_add]
+    pub fn on_insert(&mut self, hook: impl Fn(&mut World, Entity) + 'static) -> &mut Self {
+        self.on_insert = Some(Rc::new(hook));
+        self
+    }
+
+    /// Called before the component is removed or its entity is despawned
+    /// The component can still be accessed from the entity
+    pub fn on_remove(&mut self, hook: impl Fn(&mut World, Entity) + 'static) -> &mut Self {
+        self.on_remove = Some(Rc::new(hook));
+        self
+    }
+}
+
+/// The hooks of each component type and the observers of despawned entities in a world
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    components: NoHashHashMap<ComponentId, ComponentHooks>,
+    despawn_observers: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty() && self.despawn_observers.is_empty()
+    }
+
+    pub fn component_mut(&mut self, id: ComponentId) -> &mut ComponentHooks {
+        self.components.entry(id).or_default()
+    }
+
+    pub fn on_add(&self, id: ComponentId) -> Option<Hook> {
+        self.components.get(&id)?.on_add.clone()
+    }
+
+    pub fn on_insert(&self, id: ComponentId) -> Option<Hook> {
+        self.components.get(&id)?.on_insert.clone()
+    }
+
+    pub fn on_remove(&self, id: ComponentId) -> Option<Hook> {
+        self.components.get(&id)?.on_remove.clone()
+    }
+
+    pub fn add_despawn_observer(&mut self, observer: Hook) {
+        self.despawn_observers.push(observer);
+    }
+
+    pub fn despawn_observers(&self) -> Vec<Hook> {
+        self.despawn_observers.clone()
+    }
+}
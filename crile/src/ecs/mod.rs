@@ -1,12 +1,36 @@
 mod archetype;
+mod borrow;
+mod commands;
 mod component;
+mod dynamic;
+mod entity;
+mod events;
+mod hooks;
 mod query;
+mod query_state;
+mod resource;
+mod schedule;
+mod snapshot;
+mod sparse_set;
+mod system;
 mod type_info;
 mod world;
 
 pub use archetype::*;
+pub use borrow::*;
+pub use commands::*;
 pub use component::*;
+pub use dynamic::*;
+pub use entity::*;
+pub use events::*;
+pub use hooks::*;
 pub use query::*;
+pub use query_state::*;
+pub use resource::*;
+pub use schedule::*;
+pub use snapshot::*;
+pub use sparse_set::*;
+pub use system::*;
 pub use type_info::*;
 pub use world::*;
 
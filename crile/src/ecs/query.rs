@@ -1,85 +1,769 @@
-use super::{Archetype, ComponentTuple, World};
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-pub struct QueryIter<'a, T: ComponentTuple> {
+use super::{
+    Access, Archetype, Component, ComponentId, ComponentTicks, Entity, MatchedArchetype, SparseSet,
+    World,
+};
+
+/// Represents something that can be fetched from each entity inside an archetype
+///
+/// This is implemented for:
+/// - `&T` and `&mut T` to get a component
+/// - `Option<&T>` and `Option<&mut T>` to get a component if the entity has it
+/// - [Entity] to get the entity itself
+/// - [With] and [Without] to filter entities based on whether they have a component
+/// - [Added] and [Changed] to filter entities based on when a component was added or changed
+/// - [Or] to match entities that match either of two filters
+/// - Tuples of up to 16 of any of the above (including nested tuples)
+pub trait Query {
+    /// Data about a matching archetype that doesn't change, like the indices of its component
+    /// arrays, so it can be cached by a [crate::QueryState]
+    type Fetch: Copy + Send + Sync;
+    /// Data obtained from a matching archetype used to get the item of each entity
+    type State: Copy;
+    type Item<'a>;
+
+    /// Gets the fetch from the archetype or None if the archetype doesn't match this query
+    /// The world is used to find components stored outside of the archetype in sparse sets
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch>;
+
+    /// Gets the state from an archetype matched by [Self::fetch]
+    /// Returns None if none of the archetype's entities can match the query
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        ticks: QueryTicks,
+    ) -> Option<Self::State>;
+
+    /// Adds the components this query reads and writes so systems can be scheduled around it
+    fn access(access: &mut Access);
+
+    /// Checks if the entity at the index should be part of the query
+    /// Used for filters that depend on each entity rather than the archetype
+    ///
+    /// # Safety
+    /// - Index must not be greater than the archetype count
+    unsafe fn matches(_state: Self::State, _index: usize) -> bool {
+        true
+    }
+
+    /// Gets the item of the entity at the index from the state obtained from [Self::get_state]
+    /// Getting a component mutably will mark it as changed
+    ///
+    /// # Safety
+    /// - Index must not be greater than the archetype count
+    /// - Since it can return mutable references to components, it assumes borrow rules have been met
+    unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a>;
+}
+
+/// The world ticks used for change detection inside queries
+#[derive(Clone, Copy, Debug)]
+pub struct QueryTicks {
+    /// Components changed after this tick count as changed
+    pub last_change_tick: u32,
+    /// Tick to mark mutably accessed components with
+    pub change_tick: u32,
+}
+
+/// Queries that never mutably borrow components so they can be used with a shared World
+pub trait ReadOnlyQuery: Query {}
+
+/// Only matches entities that have the component T without fetching it
+pub struct With<T>(PhantomData<T>);
+
+/// Only matches entities that do not have the component T
+pub struct Without<T>(PhantomData<T>);
+
+/// Only matches entities where the component T was added since the last [World::advance_tick]
+pub struct Added<T>(PhantomData<T>);
+
+/// Only matches entities where the component T was added or mutably accessed since the last
+/// [World::advance_tick]
+pub struct Changed<T>(PhantomData<T>);
+
+/// Only matches entities that match at least one of the filters A and B
+pub struct Or<A, B>(PhantomData<(A, B)>);
+
+/// Where to find a component for the entities of an archetype
+#[derive(Clone, Copy)]
+pub struct ComponentFetch {
+    kind: FetchKind,
+}
+
+#[derive(Clone, Copy)]
+enum FetchKind {
+    /// Index of the component array in the archetype
+    Table(usize),
+    Sparse(ComponentId),
+}
+
+impl ComponentFetch {
+    /// Returns None if the archetype can't have the component
+    fn new(world: &World, archetype: &Archetype, id: ComponentId) -> Option<Self> {
+        let kind = match world.is_sparse(id) {
+            true => FetchKind::Sparse(id),
+            false => FetchKind::Table(archetype.array_index(id)?),
+        };
+        Some(Self { kind })
+    }
+
+    fn is_sparse(&self) -> bool {
+        matches!(self.kind, FetchKind::Sparse(_))
+    }
+}
+
+/// Where a component is stored for the entities of an archetype
+#[derive(Clone, Copy)]
+pub struct ComponentState {
+    storage: Storage,
+}
+
+#[derive(Clone, Copy)]
+enum Storage {
+    /// Every entity of the archetype has the component in its array
+    Table {
+        ptr: *mut u8,
+        ticks_ptr: *mut ComponentTicks,
+    },
+    /// The component has to be looked up for each entity of the archetype
+    Sparse {
+        entities: *const Entity,
+        set: *const SparseSet,
+    },
+}
+
+impl ComponentState {
+    /// Returns None if no entity in the archetype has the component
+    fn new(world: &World, archetype: &Archetype, fetch: ComponentFetch) -> Option<Self> {
+        let storage = match fetch.kind {
+            FetchKind::Table(index) => {
+                let array = &archetype.component_arrays[index];
+                Storage::Table {
+                    ptr: array.get_ptr(),
+                    ticks_ptr: array.get_ticks_ptr(),
+                }
+            }
+            FetchKind::Sparse(id) => match world.sparse_set(id) {
+                Some(set) if !set.entities().is_empty() => Storage::Sparse {
+                    entities: archetype.entities.as_ptr(),
+                    set,
+                },
+                _ => return None,
+            },
+        };
+        Some(Self { storage })
+    }
+
+    /// Checks if the entity at the index has the component
+    ///
+    /// # Safety
+    /// - Index must not be greater than the archetype count
+    unsafe fn contains(self, index: usize) -> bool {
+        match self.storage {
+            Storage::Table { .. } => true,
+            Storage::Sparse { entities, set } => (*set).contains(*entities.add(index)),
+        }
+    }
+
+    /// Gets pointers to the component and its ticks of the entity at the index if it has it
+    ///
+    /// # Safety
+    /// - Index must not be greater than the archetype count
+    /// - T must be the type of the component
+    unsafe fn get<T>(self, index: usize) -> Option<(*mut T, *mut ComponentTicks)> {
+        match self.storage {
+            Storage::Table { ptr, ticks_ptr } => {
+                Some((ptr.cast::<T>().add(index), ticks_ptr.add(index)))
+            }
+            Storage::Sparse { entities, set } => {
+                let dense = &(*set).dense;
+                let index = (*set).dense_index(*entities.add(index))?;
+                Some((
+                    dense.get_ptr().cast::<T>().add(index),
+                    dense.get_ticks_ptr().add(index),
+                ))
+            }
+        }
+    }
+}
+
+/// Pointers to a component array and its ticks to allow mutable access
+#[derive(Clone, Copy)]
+pub struct MutState {
+    component: ComponentState,
+    change_tick: u32,
+}
+
+impl MutState {
+    fn new(
+        world: &World,
+        archetype: &Archetype,
+        fetch: ComponentFetch,
+        ticks: QueryTicks,
+    ) -> Option<Self> {
+        Some(Self {
+            component: ComponentState::new(world, archetype, fetch)?,
+            change_tick: ticks.change_tick,
+        })
+    }
+
+    unsafe fn get<'a, T: Component>(self, index: usize) -> Option<&'a mut T> {
+        let (ptr, ticks) = self.component.get::<T>(index)?;
+        (*ticks).changed = self.change_tick;
+        Some(&mut *ptr)
+    }
+}
+
+/// Pointer to the ticks of a component array for change detection filters
+#[derive(Clone, Copy)]
+pub struct TicksState {
+    component: ComponentState,
+    last_change_tick: u32,
+}
+
+impl TicksState {
+    fn new(
+        world: &World,
+        archetype: &Archetype,
+        fetch: ComponentFetch,
+        ticks: QueryTicks,
+    ) -> Option<Self> {
+        Some(Self {
+            component: ComponentState::new(world, archetype, fetch)?,
+            last_change_tick: ticks.last_change_tick,
+        })
+    }
+
+    /// Checks the ticks of the component if the entity at the index has it
+    unsafe fn check<T>(self, index: usize, func: impl Fn(ComponentTicks) -> bool) -> bool {
+        self.component
+            .get::<T>(index)
+            .is_some_and(|(_, ticks)| func(*ticks))
+    }
+}
+
+impl<T: Component> Query for &T {
+    type Fetch = ComponentFetch;
+    type State = ComponentState;
+    type Item<'a> = &'a T;
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        ComponentFetch::new(world, archetype, ComponentId::of::<T>())
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        _: QueryTicks,
+    ) -> Option<Self::State> {
+        ComponentState::new(world, archetype, fetch)
+    }
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.contains(index)
+    }
+
+    unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a> {
+        &*state.get::<T>(index).unwrap_unchecked().0
+    }
+}
+
+impl<T: Component> ReadOnlyQuery for &T {}
+
+impl<T: Component> Query for &mut T {
+    type Fetch = ComponentFetch;
+    type State = MutState;
+    type Item<'a> = &'a mut T;
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        ComponentFetch::new(world, archetype, ComponentId::of::<T>())
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        ticks: QueryTicks,
+    ) -> Option<Self::State> {
+        MutState::new(world, archetype, fetch, ticks)
+    }
+
+    fn access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.component.contains(index)
+    }
+
+    unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a> {
+        state.get(index).unwrap_unchecked()
+    }
+}
+
+impl<T: Component> Query for Option<&T> {
+    type Fetch = Option<ComponentFetch>;
+    type State = Option<ComponentState>;
+    type Item<'a> = Option<&'a T>;
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        Some(ComponentFetch::new(
+            world,
+            archetype,
+            ComponentId::of::<T>(),
+        ))
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        _: QueryTicks,
+    ) -> Option<Self::State> {
+        Some(fetch.and_then(|fetch| ComponentState::new(world, archetype, fetch)))
+    }
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a> {
+        state
+            .and_then(|state| state.get::<T>(index))
+            .map(|(ptr, _)| &*ptr)
+    }
+}
+
+impl<T: Component> ReadOnlyQuery for Option<&T> {}
+
+impl<T: Component> Query for Option<&mut T> {
+    type Fetch = Option<ComponentFetch>;
+    type State = Option<MutState>;
+    type Item<'a> = Option<&'a mut T>;
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        Some(ComponentFetch::new(
+            world,
+            archetype,
+            ComponentId::of::<T>(),
+        ))
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        ticks: QueryTicks,
+    ) -> Option<Self::State> {
+        Some(fetch.and_then(|fetch| MutState::new(world, archetype, fetch, ticks)))
+    }
+
+    fn access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a> {
+        state.and_then(|state| state.get(index))
+    }
+}
+
+impl Query for Entity {
+    type Fetch = ();
+    type State = *const Entity;
+    type Item<'a> = Entity;
+
+    fn fetch(_: &World, _: &Archetype) -> Option<Self::Fetch> {
+        Some(())
+    }
+
+    fn get_state(_: &World, archetype: &Archetype, _: (), _: QueryTicks) -> Option<Self::State> {
+        Some(archetype.entities.as_ptr())
+    }
+
+    fn access(_: &mut Access) {}
+
+    unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a> {
+        *state.add(index)
+    }
+}
+
+impl ReadOnlyQuery for Entity {}
+
+impl<T: Component> Query for With<T> {
+    type Fetch = ComponentFetch;
+    type State = ComponentState;
+    type Item<'a> = ();
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        ComponentFetch::new(world, archetype, ComponentId::of::<T>())
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        _: QueryTicks,
+    ) -> Option<Self::State> {
+        ComponentState::new(world, archetype, fetch)
+    }
+
+    fn access(_: &mut Access) {}
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.contains(index)
+    }
+
+    unsafe fn get<'a>(_: Self::State, _: usize) -> Self::Item<'a> {}
+}
+
+impl<T: Component> ReadOnlyQuery for With<T> {}
+
+impl<T: Component> Query for Without<T> {
+    /// Only sparse components need to be checked for each entity
+    type Fetch = Option<ComponentFetch>;
+    type State = Option<ComponentState>;
+    type Item<'a> = ();
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        match ComponentFetch::new(world, archetype, ComponentId::of::<T>()) {
+            Some(fetch) if fetch.is_sparse() => Some(Some(fetch)),
+            Some(_) => None,
+            None => Some(None),
+        }
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        _: QueryTicks,
+    ) -> Option<Self::State> {
+        Some(fetch.and_then(|fetch| ComponentState::new(world, archetype, fetch)))
+    }
+
+    fn access(_: &mut Access) {}
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.is_none_or(|state| !state.contains(index))
+    }
+
+    unsafe fn get<'a>(_: Self::State, _: usize) -> Self::Item<'a> {}
+}
+
+impl<T: Component> ReadOnlyQuery for Without<T> {}
+
+impl<T: Component> Query for Added<T> {
+    type Fetch = ComponentFetch;
+    type State = TicksState;
+    type Item<'a> = ();
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        ComponentFetch::new(world, archetype, ComponentId::of::<T>())
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        ticks: QueryTicks,
+    ) -> Option<Self::State> {
+        TicksState::new(world, archetype, fetch, ticks)
+    }
+
+    // The ticks can be written to by queries that mutably access the component
+    fn access(access: &mut Access) {
+        access.add_ticks_read::<T>();
+    }
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.check::<T>(index, |ticks| ticks.added > state.last_change_tick)
+    }
+
+    unsafe fn get<'a>(_: Self::State, _: usize) -> Self::Item<'a> {}
+}
+
+impl<T: Component> ReadOnlyQuery for Added<T> {}
+
+impl<T: Component> Query for Changed<T> {
+    type Fetch = ComponentFetch;
+    type State = TicksState;
+    type Item<'a> = ();
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        ComponentFetch::new(world, archetype, ComponentId::of::<T>())
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        ticks: QueryTicks,
+    ) -> Option<Self::State> {
+        TicksState::new(world, archetype, fetch, ticks)
+    }
+
+    // The ticks can be written to by queries that mutably access the component
+    fn access(access: &mut Access) {
+        access.add_ticks_read::<T>();
+    }
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.check::<T>(index, |ticks| ticks.changed > state.last_change_tick)
+    }
+
+    unsafe fn get<'a>(_: Self::State, _: usize) -> Self::Item<'a> {}
+}
+
+impl<T: Component> ReadOnlyQuery for Changed<T> {}
+
+impl<A: ReadOnlyQuery, B: ReadOnlyQuery> Query for Or<A, B> {
+    type Fetch = (Option<A::Fetch>, Option<B::Fetch>);
+    type State = (Option<A::State>, Option<B::State>);
+    type Item<'a> = ();
+
+    fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+        match (A::fetch(world, archetype), B::fetch(world, archetype)) {
+            (None, None) => None,
+            fetch => Some(fetch),
+        }
+    }
+
+    fn get_state(
+        world: &World,
+        archetype: &Archetype,
+        fetch: Self::Fetch,
+        ticks: QueryTicks,
+    ) -> Option<Self::State> {
+        let a = fetch
+            .0
+            .and_then(|fetch| A::get_state(world, archetype, fetch, ticks));
+        let b = fetch
+            .1
+            .and_then(|fetch| B::get_state(world, archetype, fetch, ticks));
+        match (a, b) {
+            (None, None) => None,
+            state => Some(state),
+        }
+    }
+
+    fn access(access: &mut Access) {
+        A::access(access);
+        B::access(access);
+    }
+
+    unsafe fn matches(state: Self::State, index: usize) -> bool {
+        state.0.is_some_and(|state| A::matches(state, index))
+            || state.1.is_some_and(|state| B::matches(state, index))
+    }
+
+    unsafe fn get<'a>(_: Self::State, _: usize) -> Self::Item<'a> {}
+}
+
+impl<A: ReadOnlyQuery, B: ReadOnlyQuery> ReadOnlyQuery for Or<A, B> {}
+
+/// Macro to automatically impl Query for the specified tuple type
+macro_rules! query_tuple_impl {
+    ($($type: ident),*) => {
+        impl<$($type: Query),*> Query for ($($type,)*) {
+            type Fetch = ($($type::Fetch,)*);
+            type State = ($($type::State,)*);
+            type Item<'a> = ($($type::Item<'a>,)*);
+
+            #[allow(unused, clippy::unused_unit)]
+            fn fetch(world: &World, archetype: &Archetype) -> Option<Self::Fetch> {
+                Some(($($type::fetch(world, archetype)?,)*))
+            }
+
+            #[allow(non_snake_case, unused, clippy::unused_unit)]
+            fn get_state(
+                world: &World,
+                archetype: &Archetype,
+                fetch: Self::Fetch,
+                ticks: QueryTicks,
+            ) -> Option<Self::State> {
+                let ($($type,)*) = fetch;
+                Some(($($type::get_state(world, archetype, $type, ticks)?,)*))
+            }
+
+            #[allow(unused)]
+            fn access(access: &mut Access) {
+                $($type::access(access);)*
+            }
+
+            #[allow(non_snake_case, unused)]
+            unsafe fn matches(state: Self::State, index: usize) -> bool {
+                let ($($type,)*) = state;
+                true $(&& $type::matches($type, index))*
+            }
+
+            #[allow(non_snake_case, unused, clippy::unused_unit)]
+            unsafe fn get<'a>(state: Self::State, index: usize) -> Self::Item<'a> {
+                let ($($type,)*) = state;
+                ($($type::get($type, index),)*)
+            }
+        }
+
+        impl<$($type: ReadOnlyQuery),*> ReadOnlyQuery for ($($type,)*) {}
+    };
+}
+
+macro_rules! query_recursive_impl {
+    ($head: tt) => {
+        query_tuple_impl!();
+        query_tuple_impl!($head);
+    };
+    ($head: tt, $($tail: tt),*) => {
+        query_tuple_impl!($head, $($tail),*);
+        query_recursive_impl!($($tail),*);
+    };
+}
+
+query_recursive_impl!(Q1, Q2, Q3, Q4, Q5, Q6, Q7, Q8, Q9, Q10, Q11, Q12, Q13, Q14, Q15, Q16);
+
+pub struct QueryIter<'a, Q: Query> {
     world: &'a World,
+    /// The archetypes matched by a [QueryState] or None to check every archetype of the world
+    matched: Option<&'a [MatchedArchetype<Q::Fetch>]>,
     next_archetype_index: usize,
-    current_archetype_iter: ArchetypeIter<T>,
+    current_archetype_iter: ArchetypeIter<Q>,
 }
 
-impl<'a, T: ComponentTuple> QueryIter<'a, T> {
+impl<'a, Q: Query> QueryIter<'a, Q> {
     pub(crate) fn new(world: &'a World) -> Self {
         Self {
+            matched: None,
             next_archetype_index: 0,
             current_archetype_iter: ArchetypeIter::empty(),
             world,
         }
     }
 
+    /// Only goes through the archetypes that were matched by a [QueryState] of the world
+    pub(crate) fn cached(world: &'a World, matched: &'a [MatchedArchetype<Q::Fetch>]) -> Self {
+        Self {
+            matched: Some(matched),
+            ..Self::new(world)
+        }
+    }
+
+    /// Gets the archetype at the index and its fetch if it matches the query
+    fn archetype(&self, index: usize) -> Option<(&'a Archetype, Option<Q::Fetch>)> {
+        let world = self.world;
+        match self.matched {
+            Some(matched) => {
+                let matched = matched.get(index)?;
+                Some((&world.archetypes[matched.index], Some(matched.fetch)))
+            }
+            None => {
+                let archetype = world.archetypes.get(index)?;
+                Some((archetype, Q::fetch(world, archetype)))
+            }
+        }
+    }
+
     fn next_archetype(&mut self) -> Option<()> {
-        let archetype = self.world.archetypes.get(self.next_archetype_index)?;
-        self.current_archetype_iter = ArchetypeIter::new(archetype);
+        let (archetype, fetch) = self.archetype(self.next_archetype_index)?;
+        self.current_archetype_iter = match fetch {
+            Some(fetch) => {
+                ArchetypeIter::new(self.world, archetype, fetch, self.world.query_ticks())
+            }
+            None => ArchetypeIter::empty(),
+        };
         self.next_archetype_index += 1;
         Some(())
     }
 
     // Gets the next entity as part of this query
-    unsafe fn next_mut(&mut self) -> Option<(usize, T::MutTuple<'a>)> {
-        match self.current_archetype_iter.next() {
-            Some(tuple) => Some(tuple),
-            None => {
+    unsafe fn next_unchecked(&mut self) -> Option<(Entity, Q::Item<'a>)> {
+        loop {
+            match self.current_archetype_iter.next() {
+                Some(item) => return Some(item),
                 // We went through all the entities in the archetype so get the next one
-                self.next_archetype()?;
-                self.next_mut()
+                None => self.next_archetype()?,
             }
         }
     }
 }
 
-impl<'a, T: ComponentTuple> Iterator for QueryIter<'a, T> {
-    type Item = (usize, T::RefTuple<'a>);
+impl<'a, Q: ReadOnlyQuery> Iterator for QueryIter<'a, Q> {
+    type Item = (Entity, Q::Item<'a>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe { self.next_mut() }.map(|(id, c)| (id, T::mut_to_ref(c)))
+        unsafe { self.next_unchecked() }
     }
 }
 
 /// This is the same as QueryIter (uses it internally) but force mutably borrowing World to
 /// allow a mutable borrow to the components
-pub struct QueryIterMut<'a, T: ComponentTuple> {
-    query: QueryIter<'a, T>,
+pub struct QueryIterMut<'a, Q: Query> {
+    query: QueryIter<'a, Q>,
 }
 
-impl<'a, T: ComponentTuple> QueryIterMut<'a, T> {
+impl<'a, Q: Query> QueryIterMut<'a, Q> {
     pub(crate) fn new(world: &'a mut World) -> Self {
+        unsafe { Self::new_unchecked(world) }
+    }
+
+    pub(crate) fn cached(world: &'a mut World, matched: &'a [MatchedArchetype<Q::Fetch>]) -> Self {
+        unsafe { Self::cached_unchecked(world, matched) }
+    }
+
+    /// # Safety
+    /// - Same as [Self::new_unchecked]
+    pub(crate) unsafe fn cached_unchecked(
+        world: &'a World,
+        matched: &'a [MatchedArchetype<Q::Fetch>],
+    ) -> Self {
+        Self {
+            query: QueryIter::cached(world, matched),
+        }
+    }
+
+    /// # Safety
+    /// - Nothing else can access the components Q mutably accesses while this is alive
+    ///
+    /// # Panics
+    /// - The query would alias itself, see [Access::of_query]
+    pub(crate) unsafe fn new_unchecked(world: &'a World) -> Self {
+        Access::of_query::<Q>();
         Self {
             query: QueryIter::new(world),
         }
     }
 }
 
-impl<'a, T: ComponentTuple> Iterator for QueryIterMut<'a, T> {
-    type Item = (usize, T::MutTuple<'a>);
+impl<'a, Q: Query> Iterator for QueryIterMut<'a, Q> {
+    type Item = (Entity, Q::Item<'a>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe { self.query.next_mut() }
+        unsafe { self.query.next_unchecked() }
     }
 }
 
-struct ArchetypeIter<T: ComponentTuple> {
+struct ArchetypeIter<Q: Query> {
     component_index: usize,
     count: usize,
-    entity_indexs: *const usize,
-    array_ptr_array: Option<T::FixedArray<*mut u8>>,
+    entities: *const Entity,
+    state: Option<Q::State>,
 }
 
-impl<T: ComponentTuple> ArchetypeIter<T> {
-    fn new(archetype: &Archetype) -> Self {
-        match T::get_array_ptrs(archetype) {
-            Some(array_ptr_array) => Self {
+impl<Q: Query> ArchetypeIter<Q> {
+    fn new(world: &World, archetype: &Archetype, fetch: Q::Fetch, ticks: QueryTicks) -> Self {
+        match Q::get_state(world, archetype, fetch, ticks) {
+            Some(state) => Self {
                 component_index: 0,
                 count: archetype.count(),
-                entity_indexs: archetype.entity_indexs.as_ptr(),
-                array_ptr_array: Some(array_ptr_array),
+                entities: archetype.entities.as_ptr(),
+                state: Some(state),
             },
             None => Self::empty(),
         }
@@ -89,22 +773,178 @@ impl<T: ComponentTuple> ArchetypeIter<T> {
         Self {
             component_index: 0,
             count: 0,
-            entity_indexs: std::ptr::null(),
-            array_ptr_array: None,
+            entities: std::ptr::null(),
+            state: None,
         }
     }
 
-    unsafe fn next<'a>(&mut self) -> Option<(usize, T::MutTuple<'a>)> {
-        if self.component_index < self.count {
-            let component_tuple = T::array_ptr_array_get(
-                self.array_ptr_array.as_ref().unwrap_unchecked(),
-                self.component_index,
-            );
-            let index = *self.entity_indexs.add(self.component_index);
+    unsafe fn next<'a>(&mut self) -> Option<(Entity, Q::Item<'a>)> {
+        while self.component_index < self.count {
+            let state = self.state.unwrap_unchecked();
+            let index = self.component_index;
             self.component_index += 1;
-            Some((index, component_tuple))
-        } else {
-            None
+
+            if Q::matches(state, index) {
+                return Some((*self.entities.add(index), Q::get(state, index)));
+            }
+        }
+
+        None
+    }
+}
+
+/// Queries whose items can be fetched from multiple threads at the same time
+///
+/// Components read by the query must be Sync and components written must be Send.
+pub trait ParallelQuery: Query {}
+
+impl<T: Component + Sync> ParallelQuery for &T {}
+impl<T: Component + Send> ParallelQuery for &mut T {}
+impl<T: Component + Sync> ParallelQuery for Option<&T> {}
+impl<T: Component + Send> ParallelQuery for Option<&mut T> {}
+impl ParallelQuery for Entity {}
+impl<T: Component> ParallelQuery for With<T> {}
+impl<T: Component> ParallelQuery for Without<T> {}
+impl<T: Component> ParallelQuery for Added<T> {}
+impl<T: Component> ParallelQuery for Changed<T> {}
+impl<A: ReadOnlyQuery + ParallelQuery, B: ReadOnlyQuery + ParallelQuery> ParallelQuery
+    for Or<A, B>
+{
+}
+
+macro_rules! parallel_query_tuple_impl {
+    ($($type: ident),*) => {
+        impl<$($type: ParallelQuery),*> ParallelQuery for ($($type,)*) {}
+    };
+}
+
+macro_rules! parallel_query_recursive_impl {
+    ($head: tt) => {
+        parallel_query_tuple_impl!();
+        parallel_query_tuple_impl!($head);
+    };
+    ($head: tt, $($tail: tt),*) => {
+        parallel_query_tuple_impl!($head, $($tail),*);
+        parallel_query_recursive_impl!($($tail),*);
+    };
+}
+
+parallel_query_recursive_impl!(
+    Q1, Q2, Q3, Q4, Q5, Q6, Q7, Q8, Q9, Q10, Q11, Q12, Q13, Q14, Q15, Q16
+);
+
+/// A range of entities inside an archetype that is processed by one thread
+struct Chunk<Q: Query> {
+    state: Q::State,
+    entities: *const Entity,
+    start: usize,
+    end: usize,
+}
+
+// SAFETY: ParallelQuery makes sure the components can be sent or shared between threads and chunks
+// never overlap so each component is only mutably accessed by one thread
+unsafe impl<Q: ParallelQuery> Send for Chunk<Q> {}
+unsafe impl<Q: ParallelQuery> Sync for Chunk<Q> {}
+
+impl<Q: Query> Chunk<Q> {
+    unsafe fn for_each<'a>(&self, func: &impl Fn(Entity, Q::Item<'a>)) {
+        for index in self.start..self.end {
+            if Q::matches(self.state, index) {
+                func(*self.entities.add(index), Q::get(self.state, index));
+            }
+        }
+    }
+}
+
+impl<'a, Q: Query> QueryIter<'a, Q> {
+    /// Number of entities in each chunk given to a thread
+    const CHUNK_SIZE: usize = 1024;
+
+    /// Splits the remaining entities into chunks of at most [Self::CHUNK_SIZE]
+    fn chunks(&self) -> Vec<Chunk<Q>> {
+        let mut chunks = Vec::new();
+        let mut push_chunks = |state: Q::State, entities: *const Entity, start, end| {
+            for chunk_start in (start..end).step_by(Self::CHUNK_SIZE) {
+                chunks.push(Chunk {
+                    state,
+                    entities,
+                    start: chunk_start,
+                    end: (chunk_start + Self::CHUNK_SIZE).min(end),
+                });
+            }
+        };
+
+        let current = &self.current_archetype_iter;
+        if let Some(state) = current.state {
+            push_chunks(
+                state,
+                current.entities,
+                current.component_index,
+                current.count,
+            );
+        }
+
+        let ticks = self.world.query_ticks();
+        let mut index = self.next_archetype_index;
+        while let Some((archetype, fetch)) = self.archetype(index) {
+            let state = fetch.and_then(|fetch| Q::get_state(self.world, archetype, fetch, ticks));
+            if let Some(state) = state {
+                push_chunks(state, archetype.entities.as_ptr(), 0, archetype.count());
+            }
+            index += 1;
+        }
+
+        chunks
+    }
+
+    /// # Safety
+    /// - Same as [Self::next_unchecked]
+    unsafe fn par_for_each_unchecked(self, func: impl Fn(Entity, Q::Item<'a>) + Send + Sync)
+    where
+        Q: ParallelQuery,
+    {
+        let chunks = self.chunks();
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        if chunks.len() <= 1 || threads == 1 {
+            for chunk in &chunks {
+                chunk.for_each(&func);
+            }
+            return;
         }
+
+        // Threads take the next chunk that hasn't been processed until they run out
+        let next_chunk = AtomicUsize::new(0);
+        let work = || loop {
+            let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+            let Some(chunk) = chunks.get(index) else {
+                break;
+            };
+            unsafe { chunk.for_each(&func) };
+        };
+
+        std::thread::scope(|scope| {
+            for _ in 1..threads.min(chunks.len()) {
+                scope.spawn(work);
+            }
+            work();
+        });
+    }
+}
+
+impl<'a, Q: ReadOnlyQuery + ParallelQuery> QueryIter<'a, Q> {
+    /// Calls the function on every remaining entity in the query using multiple threads
+    ///
+    /// The archetypes are split into chunks which are processed in no particular order.
+    pub fn par_for_each(self, func: impl Fn(Entity, Q::Item<'a>) + Send + Sync) {
+        unsafe { self.par_for_each_unchecked(func) }
+    }
+}
+
+impl<'a, Q: ParallelQuery> QueryIterMut<'a, Q> {
+    /// Calls the function on every remaining entity in the query using multiple threads
+    ///
+    /// The archetypes are split into chunks which are processed in no particular order.
+    pub fn par_for_each(self, func: impl Fn(Entity, Q::Item<'a>) + Send + Sync) {
+        unsafe { self.query.par_for_each_unchecked(func) }
     }
 }
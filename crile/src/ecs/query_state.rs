@@ -0,0 +1,78 @@
+use super::{Access, Query, QueryIter, QueryIterMut, ReadOnlyQuery, World};
+
+/// An archetype that matched a query along with what is needed to fetch from it
+#[derive(Clone, Copy)]
+pub(crate) struct MatchedArchetype<F> {
+    pub index: usize,
+    pub fetch: F,
+}
+
+/// Caches the archetypes of a world that match the query Q so they aren't checked on every
+/// iteration like [World::query] does
+///
+/// Only the archetypes created since it was last used get checked, so it is worth keeping around
+/// between frames, e.g. captured by a system. Using it with another world rebuilds the cache.
+pub struct QueryState<Q: Query> {
+    world_id: Option<u64>,
+    /// Number of the world's archetypes that were already checked
+    archetype_count: usize,
+    matched: Vec<MatchedArchetype<Q::Fetch>>,
+}
+
+impl<Q: Query> Default for QueryState<Q> {
+    fn default() -> Self {
+        // Every state is made here so the query only has to be checked for aliasing once
+        Access::of_query::<Q>();
+        Self {
+            world_id: None,
+            archetype_count: 0,
+            matched: Vec::new(),
+        }
+    }
+}
+
+impl<Q: Query> QueryState<Q> {
+    pub fn new(world: &World) -> Self {
+        let mut state = Self::default();
+        state.update(world);
+        state
+    }
+
+    /// Checks the archetypes that were created since the last update
+    pub fn update(&mut self, world: &World) {
+        let world_id = world.id();
+        if self.world_id != Some(world_id) {
+            self.world_id = Some(world_id);
+            self.archetype_count = 0;
+            self.matched.clear();
+        }
+
+        let new_archetypes = world.archetypes.iter().enumerate();
+        for (index, archetype) in new_archetypes.skip(self.archetype_count) {
+            if let Some(fetch) = Q::fetch(world, archetype) {
+                self.matched.push(MatchedArchetype { index, fetch });
+            }
+        }
+        self.archetype_count = world.archetypes.len();
+    }
+
+    pub fn iter<'a>(&'a mut self, world: &'a World) -> QueryIter<'a, Q>
+    where
+        Q: ReadOnlyQuery,
+    {
+        self.update(world);
+        QueryIter::cached(world, &self.matched)
+    }
+
+    pub fn iter_mut<'a>(&'a mut self, world: &'a mut World) -> QueryIterMut<'a, Q> {
+        self.update(world);
+        QueryIterMut::cached(world, &self.matched)
+    }
+
+    /// # Safety
+    /// - Nothing else can access the components Q mutably accesses while the iterator is alive
+    pub(crate) unsafe fn iter_unchecked<'a>(&'a mut self, world: &'a World) -> QueryIterMut<'a, Q> {
+        self.update(world);
+        QueryIterMut::cached_unchecked(world, &self.matched)
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    any::{Any, TypeId},
+    cell::UnsafeCell,
+};
+
+use crate::NoHashHashMap;
+
+/// Represents a usable resource type
+/// Shorthand for 'static + Clone
+pub trait Resource: 'static + Clone {}
+impl<T: 'static + Clone> Resource for T {}
+
+/// Object safe version of [Resource] so resources of different types can be stored together
+trait AnyResource {
+    fn clone_box(&self) -> Box<UnsafeCell<dyn AnyResource>>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<R: Resource> AnyResource for R {
+    fn clone_box(&self) -> Box<UnsafeCell<dyn AnyResource>> {
+        Box::new(UnsafeCell::new(self.clone()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Stores a single value of each resource type
+/// The values are inside an [UnsafeCell] so systems running in parallel can mutate different
+/// resources at the same time
+#[derive(Default)]
+pub(crate) struct Resources {
+    map: NoHashHashMap<TypeId, Box<UnsafeCell<dyn AnyResource>>>,
+}
+
+impl Resources {
+    pub fn insert<R: Resource>(&mut self, resource: R) -> Option<R> {
+        let old = self
+            .map
+            .insert(TypeId::of::<R>(), Box::new(UnsafeCell::new(resource)))?;
+        Some(unsafe { Self::into_inner(old) })
+    }
+
+    pub fn get<R: Resource>(&self) -> Option<&R> {
+        let resource = self.map.get(&TypeId::of::<R>())?;
+        unsafe { (*resource.get()).as_any().downcast_ref() }
+    }
+
+    pub fn get_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        let resource = self.map.get_mut(&TypeId::of::<R>())?;
+        resource.get_mut().as_any_mut().downcast_mut()
+    }
+
+    /// # Safety
+    /// - Nothing else can access the resource while the reference is alive
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_unchecked_mut<R: Resource>(&self) -> Option<&mut R> {
+        let resource = self.map.get(&TypeId::of::<R>())?;
+        (*resource.get()).as_any_mut().downcast_mut()
+    }
+
+    pub fn remove<R: Resource>(&mut self) -> Option<R> {
+        let resource = self.map.remove(&TypeId::of::<R>())?;
+        Some(unsafe { Self::into_inner(resource) })
+    }
+
+    pub fn contains<R: Resource>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<R>())
+    }
+
+    /// # Safety
+    /// - The real type of the resource must be R
+    unsafe fn into_inner<R: Resource>(resource: Box<UnsafeCell<dyn AnyResource>>) -> R {
+        let resource = Box::from_raw(Box::into_raw(resource).cast::<UnsafeCell<R>>());
+        resource.into_inner()
+    }
+}
+
+impl Clone for Resources {
+    fn clone(&self) -> Self {
+        Self {
+            map: self
+                .map
+                .iter()
+                .map(|(id, resource)| (*id, unsafe { (*resource.get()).clone_box() }))
+                .collect(),
+        }
+    }
+}
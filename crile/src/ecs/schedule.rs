@@ -0,0 +1,291 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use super::{System, World};
+
+/// The stages of a frame in the order they usually run
+/// Every system in a stage finishes before the next stage starts
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Stage {
+    /// Runs once when the scene starts
+    Startup,
+    PreUpdate,
+    /// Runs at a fixed rate which may be more or less than once a frame
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    /// Runs before the scene renders to get data ready for rendering
+    RenderPrepare,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Startup,
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::RenderPrepare,
+    ];
+}
+
+/// Systems grouped into stages
+///
+/// Systems in a stage run in the order they were added unless ordered with [System::before] or
+/// [System::after]. Systems whose access doesn't conflict are run in parallel on worker threads.
+#[derive(Default)]
+pub struct Schedule {
+    stages: [StageSystems; Stage::ALL.len()],
+    /// Started the first time a batch has more than one system
+    workers: Option<WorkerPool>,
+}
+
+#[derive(Default)]
+struct StageSystems {
+    systems: Vec<System>,
+    /// Indices of the systems that can run at the same time in the order they run
+    /// Gets rebuilt when a system is added
+    batches: Option<Vec<Vec<usize>>>,
+}
+
+impl Schedule {
+    pub fn add_system(&mut self, stage: Stage, system: System) -> &mut Self {
+        let stage_systems = &mut self.stages[stage as usize];
+        assert!(
+            stage_systems
+                .systems
+                .iter()
+                .all(|other| other.name() != system.name()),
+            "system '{}' already exists in {stage:?}",
+            system.name()
+        );
+
+        stage_systems.systems.push(system);
+        stage_systems.batches = None;
+        self
+    }
+
+    pub fn systems(&self, stage: Stage) -> &[System] {
+        &self.stages[stage as usize].systems
+    }
+
+    /// Runs every system in the stage then applies the commands they queued
+    pub fn run(&mut self, stage: Stage, world: &mut World) {
+        let stage_systems = &mut self.stages[stage as usize];
+        let batches = stage_systems
+            .batches
+            .get_or_insert_with(|| build_batches(stage, &stage_systems.systems));
+
+        for batch in batches.iter() {
+            run_batch(&mut stage_systems.systems, batch, world, &mut self.workers);
+        }
+    }
+}
+
+/// Groups the systems into batches which respect the ordering constraints and only contain
+/// systems that don't conflict with each other
+fn build_batches(stage: Stage, systems: &[System]) -> Vec<Vec<usize>> {
+    let find = |name: &str| {
+        systems
+            .iter()
+            .position(|system| system.name() == name)
+            .unwrap_or_else(|| panic!("system '{name}' does not exist in {stage:?}"))
+    };
+
+    // The systems that have to finish before each system
+    let mut dependencies = vec![Vec::new(); systems.len()];
+    for (index, system) in systems.iter().enumerate() {
+        for name in &system.after {
+            dependencies[index].push(find(name));
+        }
+        for name in &system.before {
+            dependencies[find(name)].push(index);
+        }
+    }
+
+    // Whether the system has to wait for the other one through the ordering constraints
+    let waits_for = |index: usize, other: usize| {
+        let mut seen = vec![false; systems.len()];
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            for &dependency in &dependencies[index] {
+                if dependency == other {
+                    return true;
+                }
+                if !std::mem::replace(&mut seen[dependency], true) {
+                    stack.push(dependency);
+                }
+            }
+        }
+        false
+    };
+
+    let mut done = vec![false; systems.len()];
+    let mut pending: Vec<usize> = (0..systems.len()).collect();
+    let mut batches = Vec::new();
+
+    while !pending.is_empty() {
+        let mut batch: Vec<usize> = Vec::new();
+        // Systems that were skipped over because of a conflict or because they are still waiting
+        // for others so anything after that conflicts with them keeps the order they were added in
+        let mut skipped: Vec<usize> = Vec::new();
+
+        for &index in &pending {
+            let is_ready = dependencies[index].iter().all(|&other| done[other]);
+            if !is_ready {
+                skipped.push(index);
+                continue;
+            }
+
+            // Skipped systems that wait for this one don't hold it back
+            let access = systems[index].access();
+            let conflicts = batch
+                .iter()
+                .chain(skipped.iter().filter(|&&other| !waits_for(other, index)))
+                .any(|&other| access.conflicts(systems[other].access()));
+            if conflicts {
+                skipped.push(index);
+            } else {
+                batch.push(index);
+            }
+        }
+
+        assert!(
+            !batch.is_empty(),
+            "systems in {stage:?} have a cycle in their ordering"
+        );
+
+        for &index in &batch {
+            done[index] = true;
+        }
+        pending.retain(|index| !done[*index]);
+        batches.push(batch);
+    }
+
+    batches
+}
+
+fn run_batch(
+    systems: &mut [System],
+    batch: &[usize],
+    world: &mut World,
+    workers: &mut Option<WorkerPool>,
+) {
+    if let [index] = batch {
+        systems[*index].run(world);
+        return;
+    }
+
+    let mut batch_systems: Vec<&mut System> = systems
+        .iter_mut()
+        .enumerate()
+        .filter(|(index, _)| batch.contains(index))
+        .map(|(_, system)| system)
+        .collect();
+
+    let world_ref = WorldRef(world);
+    let tasks = batch_systems
+        .iter_mut()
+        .map(|system| -> BorrowedTask<'_> {
+            // SAFETY: Systems in a batch never have conflicting access
+            Box::new(move || unsafe { system.run_unchecked(world_ref.get()) })
+        })
+        .collect();
+    workers.get_or_insert_with(WorkerPool::new).run(tasks);
+
+    for system in batch_systems {
+        system.apply_commands(world);
+    }
+}
+
+/// Lets the world be shared between the worker threads
+#[derive(Clone, Copy)]
+struct WorldRef<'w>(&'w World);
+
+// SAFETY: Systems can only access components and resources they declared which are required to
+// be Send and Sync, and batches never contain systems with conflicting access
+unsafe impl Send for WorldRef<'_> {}
+unsafe impl Sync for WorldRef<'_> {}
+
+impl<'w> WorldRef<'w> {
+    fn get(self) -> &'w World {
+        self.0
+    }
+}
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+type BorrowedTask<'a> = Box<dyn FnOnce() + Send + 'a>;
+
+/// Threads that are kept alive between runs so running a batch doesn't spawn new threads
+struct WorkerPool {
+    sender: Option<mpsc::Sender<Task>>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    fn new() -> Self {
+        let count = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        // The calling thread runs a task too so one less worker is needed
+        let threads = (1..count.max(2))
+            .map(|_| {
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    let task = receiver.lock().unwrap().recv();
+                    match task {
+                        Ok(task) => task(),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            threads,
+        }
+    }
+
+    /// Runs the tasks on the workers and the first one on the calling thread
+    /// Waits for every task to finish and panics afterwards if any of them panicked
+    fn run<'a>(&self, tasks: Vec<BorrowedTask<'a>>) {
+        let (done_sender, done_receiver) = mpsc::channel();
+        let mut tasks = tasks.into_iter();
+        let first = tasks.next();
+
+        let mut sent = 0;
+        for task in tasks {
+            let done = done_sender.clone();
+            let task: BorrowedTask<'a> = Box::new(move || {
+                let _ = done.send(panic::catch_unwind(AssertUnwindSafe(task)));
+            });
+            // SAFETY: Every task is waited for below so nothing it borrows can end before it runs
+            let task = unsafe { std::mem::transmute::<BorrowedTask<'a>, Task>(task) };
+            self.sender.as_ref().unwrap().send(task).unwrap();
+            sent += 1;
+        }
+
+        let mut result = first.map_or(Ok(()), |task| panic::catch_unwind(AssertUnwindSafe(task)));
+        for _ in 0..sent {
+            let other = done_receiver.recv().unwrap();
+            result = result.and(other);
+        }
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Closing the channel makes the workers stop waiting for tasks
+        self.sender = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
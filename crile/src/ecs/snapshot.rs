@@ -0,0 +1,295 @@
+use std::{cmp::Ordering, ptr::NonNull};
+
+use super::{Component, ComponentId, ComponentTicks, Entity, TypeInfo, World};
+
+/// A copy of a single component of any type
+pub struct ComponentSnapshot {
+    type_info: TypeInfo,
+    ticks: ComponentTicks,
+    data: NonNull<u8>,
+}
+
+impl ComponentSnapshot {
+    /// # Safety
+    /// - The component must be a valid value of the type info's type
+    pub(crate) unsafe fn clone_from_ptr(
+        type_info: &TypeInfo,
+        ticks: ComponentTicks,
+        component: *const u8,
+    ) -> Self {
+        let data = Self::alloc(type_info);
+        (type_info.clone_to)(component, data.as_ptr());
+        Self {
+            type_info: type_info.clone(),
+            ticks,
+            data,
+        }
+    }
+
+    fn alloc(type_info: &TypeInfo) -> NonNull<u8> {
+        // Components can't be zero sized so the layout is always valid to allocate
+        let ptr = unsafe { std::alloc::alloc(type_info.layout) };
+        NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(type_info.layout))
+    }
+
+    pub fn id(&self) -> ComponentId {
+        self.type_info.id
+    }
+
+    pub fn type_info(&self) -> &TypeInfo {
+        &self.type_info
+    }
+
+    /// The ticks the component had when the snapshot was taken
+    pub fn ticks(&self) -> ComponentTicks {
+        self.ticks
+    }
+
+    /// Gets the component if it is of type T
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        (self.id() == ComponentId::of::<T>()).then(|| unsafe { &*self.data.as_ptr().cast::<T>() })
+    }
+
+    /// Changes the type info of a runtime defined component to the one it has in another world
+    pub(crate) fn with_type_info(mut self, type_info: &TypeInfo) -> Self {
+        debug_assert_eq!(self.type_info.layout, type_info.layout);
+        self.type_info = type_info.clone();
+        self
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    /// Frees the memory without dropping the component for when it was moved somewhere else
+    pub(crate) fn forget(self) {
+        let this = std::mem::ManuallyDrop::new(self);
+        unsafe { std::alloc::dealloc(this.data.as_ptr(), this.type_info.layout) };
+    }
+}
+
+impl Clone for ComponentSnapshot {
+    fn clone(&self) -> Self {
+        unsafe { Self::clone_from_ptr(&self.type_info, self.ticks, self.as_ptr()) }
+    }
+}
+
+impl Drop for ComponentSnapshot {
+    fn drop(&mut self) {
+        unsafe {
+            (self.type_info.drop)(self.data.as_ptr());
+            std::alloc::dealloc(self.data.as_ptr(), self.type_info.layout);
+        }
+    }
+}
+
+impl std::fmt::Debug for ComponentSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentSnapshot")
+            .field("type", &self.type_info.typename)
+            .field("ticks", &self.ticks)
+            .finish()
+    }
+}
+
+/// A copy of an entity and its components sorted by id
+#[derive(Clone, Debug)]
+pub struct EntitySnapshot {
+    pub entity: Entity,
+    pub components: Vec<ComponentSnapshot>,
+}
+
+impl EntitySnapshot {
+    pub fn get(&self, id: ComponentId) -> Option<&ComponentSnapshot> {
+        self.components
+            .binary_search_by(|component| component.id().cmp(&id))
+            .ok()
+            .map(|index| &self.components[index])
+    }
+}
+
+/// A copy of the entities of a world at some point in time
+/// Created with [World::snapshot] and [World::snapshot_of]
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    /// Sorted by entity
+    entities: Vec<EntitySnapshot>,
+    /// The components that were copied or None if every component was
+    components: Option<Vec<ComponentId>>,
+    /// Change tick of the world when the snapshot was taken
+    tick: u32,
+}
+
+impl Snapshot {
+    pub(crate) fn new(
+        mut entities: Vec<EntitySnapshot>,
+        components: Option<Vec<ComponentId>>,
+        tick: u32,
+    ) -> Self {
+        entities.sort_unstable_by_key(|entity| entity.entity);
+        Self {
+            entities,
+            components,
+            tick,
+        }
+    }
+
+    /// Checks if the component type was copied into the snapshot
+    pub fn includes(&self, id: ComponentId) -> bool {
+        self.components
+            .as_ref()
+            .is_none_or(|components| components.contains(&id))
+    }
+
+    pub fn entities(&self) -> &[EntitySnapshot] {
+        &self.entities
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&EntitySnapshot> {
+        self.entities
+            .binary_search_by_key(&entity, |snapshot| snapshot.entity)
+            .ok()
+            .map(|index| &self.entities[index])
+    }
+
+    /// Computes the changes that turn this snapshot into the newer one
+    ///
+    /// Both snapshots should be of the same world since changed components are found by comparing
+    /// their change ticks rather than their values. A component changed in the same tick that one
+    /// of the snapshots was taken in keeps the same ticks, so every component with a change tick
+    /// at or after the earlier snapshot's tick is treated as changed. This can report components
+    /// that weren't actually changed, which is harmless since applying them writes the same value.
+    pub fn diff(&self, newer: &Snapshot) -> WorldDiff {
+        let mut diff = WorldDiff::default();
+        let tick = self.tick.min(newer.tick);
+
+        merge(&self.entities, &newer.entities, |a, b| {
+            a.entity.cmp(&b.entity)
+        })
+        .for_each(|pair| match pair {
+            (Some(old), None) => diff.despawned.push(old.clone()),
+            (None, Some(new)) => diff.spawned.push(new.clone()),
+            (Some(old), Some(new)) => {
+                let changes = merge(&old.components, &new.components, |a, b| a.id().cmp(&b.id()))
+                    .filter(|pair| match pair {
+                        (Some(a), Some(b)) => a.ticks != b.ticks || a.ticks.changed >= tick,
+                        _ => true,
+                    })
+                    .map(|(a, b)| ComponentChange {
+                        entity: old.entity,
+                        old: a.cloned(),
+                        new: b.cloned(),
+                    });
+                diff.changed.extend(changes);
+            }
+            (None, None) => unreachable!(),
+        });
+
+        diff
+    }
+
+    /// Changes the world back to how it was when this snapshot was taken
+    /// Only the components that were included in the snapshot are restored
+    pub fn restore(&self, world: &mut World) {
+        let current = match &self.components {
+            Some(components) => world.snapshot_of(components),
+            None => world.snapshot(),
+        };
+        current.diff(self).apply(world);
+    }
+}
+
+/// A component that was added, removed or changed on an entity that exists in both snapshots
+#[derive(Clone, Debug)]
+pub struct ComponentChange {
+    pub entity: Entity,
+    /// None if the component was added
+    pub old: Option<ComponentSnapshot>,
+    /// None if the component was removed
+    pub new: Option<ComponentSnapshot>,
+}
+
+/// The structural and component changes between two [Snapshot]s
+///
+/// Can be applied to go from the old state to the new state or reverted to go back, which is
+/// useful for undo and redo. Entities are respawned with the same handles they had before.
+#[derive(Clone, Debug, Default)]
+pub struct WorldDiff {
+    pub spawned: Vec<EntitySnapshot>,
+    pub despawned: Vec<EntitySnapshot>,
+    pub changed: Vec<ComponentChange>,
+}
+
+impl WorldDiff {
+    pub fn is_empty(&self) -> bool {
+        self.spawned.is_empty() && self.despawned.is_empty() && self.changed.is_empty()
+    }
+
+    /// Makes the changes to a world in the old state to bring it to the new state
+    pub fn apply(&self, world: &mut World) {
+        self.apply_changes(world, &self.despawned, &self.spawned, |change| {
+            change.new.as_ref()
+        });
+    }
+
+    /// Undoes the changes to a world in the new state to bring it back to the old state
+    pub fn revert(&self, world: &mut World) {
+        self.apply_changes(world, &self.spawned, &self.despawned, |change| {
+            change.old.as_ref()
+        });
+    }
+
+    fn apply_changes(
+        &self,
+        world: &mut World,
+        to_despawn: &[EntitySnapshot],
+        to_spawn: &[EntitySnapshot],
+        target: impl Fn(&ComponentChange) -> Option<&ComponentSnapshot>,
+    ) {
+        // Despawn first so the slots of the entities being spawned are free
+        for snapshot in to_despawn {
+            if world.exists(snapshot.entity) {
+                world.despawn(snapshot.entity);
+            }
+        }
+
+        for snapshot in to_spawn {
+            world.spawn_snapshot(snapshot);
+        }
+
+        for change in &self.changed {
+            let Some(mut entity) = world.entity_mut(change.entity) else {
+                continue;
+            };
+
+            match target(change) {
+                Some(component) => entity.insert_snapshot(component),
+                None => {
+                    let id = change.old.as_ref().or(change.new.as_ref()).unwrap().id();
+                    if entity.has_id(id) {
+                        entity.remove_by_id(id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Walks through two sorted slices together pairing up equal items
+fn merge<'a, T>(
+    a: &'a [T],
+    b: &'a [T],
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> impl Iterator<Item = (Option<&'a T>, Option<&'a T>)> {
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    std::iter::from_fn(move || match (a.peek(), b.peek()) {
+        (Some(x), Some(y)) => match cmp(x, y) {
+            Ordering::Less => Some((a.next(), None)),
+            Ordering::Greater => Some((None, b.next())),
+            Ordering::Equal => Some((a.next(), b.next())),
+        },
+        (Some(_), None) => Some((a.next(), None)),
+        (None, Some(_)) => Some((None, b.next())),
+        (None, None) => None,
+    })
+}
@@ -0,0 +1,100 @@
+use super::{ComponentArray, ComponentId, ComponentTicks, Entity, TypeInfo};
+
+/// Where the components of a type are stored
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum StorageType {
+    /// Stored in the archetype of the entity which is fastest to iterate through
+    #[default]
+    Table,
+    /// Stored in a sparse set keyed by entity index so adding and removing the component doesn't
+    /// move the entity's other components into a different archetype
+    SparseSet,
+}
+
+/// Components of a single type stored densely with a lookup from the entity index
+#[derive(Clone)]
+pub(crate) struct SparseSet {
+    /// Index into the dense array for each entity index or [Self::EMPTY]
+    sparse: Vec<u32>,
+    /// The entity of each component in the dense array
+    entities: Vec<Entity>,
+    pub dense: ComponentArray,
+}
+
+impl SparseSet {
+    const EMPTY: u32 = u32::MAX;
+    const START_CAP: usize = 32;
+
+    pub fn new(type_info: TypeInfo) -> Self {
+        Self {
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            dense: ComponentArray::with_capacity(type_info, Self::START_CAP),
+        }
+    }
+
+    pub fn id(&self) -> ComponentId {
+        self.dense.get_id()
+    }
+
+    /// Memory allocated for the lookup, the entities, the components and their change ticks
+    pub fn allocated_bytes(&self) -> usize {
+        self.sparse.capacity() * std::mem::size_of::<u32>()
+            + self.entities.capacity() * std::mem::size_of::<Entity>()
+            + self.dense.allocated_bytes()
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Borrows the components mutably along with their entities
+    pub fn split_mut(&mut self) -> (&mut ComponentArray, &[Entity]) {
+        (&mut self.dense, &self.entities)
+    }
+
+    /// Gets the index of the entity's component in the dense array
+    pub fn dense_index(&self, entity: Entity) -> Option<usize> {
+        let index = *self.sparse.get(entity.index())?;
+        // The generation needs to be checked since the slot could be from a despawned entity
+        (index != Self::EMPTY && self.entities[index as usize] == entity).then_some(index as usize)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    /// # Safety
+    /// - The component must match the type info and must not be used after calling this
+    /// - The entity must not already be in the set
+    pub unsafe fn insert(&mut self, entity: Entity, component: *const u8, ticks: ComponentTicks) {
+        debug_assert!(!self.contains(entity));
+        let index = entity.index();
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, Self::EMPTY);
+        }
+
+        self.sparse[index] = self.entities.len() as u32;
+        self.entities.push(entity);
+
+        let ptr = self.dense.alloc_push_space();
+        std::ptr::copy_nonoverlapping(component, ptr, self.dense.type_info().layout.size());
+        *self.dense.ticks.last_mut().unwrap() = ticks;
+    }
+
+    /// Removes the entity's component, dropping it if should_drop is true
+    /// Returns false if the entity wasn't in the set
+    pub fn remove(&mut self, entity: Entity, should_drop: bool) -> bool {
+        let Some(index) = self.dense_index(entity) else {
+            return false;
+        };
+
+        self.dense.swap_remove(index, should_drop);
+        self.entities.swap_remove(index);
+        self.sparse[entity.index()] = Self::EMPTY;
+        if let Some(moved) = self.entities.get(index) {
+            self.sparse[moved.index()] = index as u32;
+        }
+        true
+    }
+}
@@ -0,0 +1,379 @@
+use std::any::{type_name, TypeId};
+
+use super::{
+    Commands, Component, ComponentTuple, Entity, EventReader, EventWriter, Events, Query,
+    QueryIter, QueryIterMut, QueryState, ReadOnlyQuery, Resource, World,
+};
+
+/// The components and resources a system reads and writes
+/// Systems with access that doesn't conflict can run at the same time
+#[derive(Default, Clone, Debug)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    ticks_reads: Vec<TypeId>,
+    resource_reads: Vec<TypeId>,
+    resource_writes: Vec<TypeId>,
+    /// Needs the whole world so it can't run with anything else
+    exclusive: bool,
+    /// Components that were added as a write and another read or write, which a single query
+    /// can't do since it would hand out aliasing references
+    aliased: Vec<&'static str>,
+}
+
+impl Access {
+    /// Gets the access of the query
+    ///
+    /// # Panics
+    /// - The query accesses a component mutably along with any other access to it like
+    ///   `(&mut T, &T)` which would give out a mutable reference that aliases
+    pub fn of_query<Q: Query>() -> Self {
+        let mut access = Access::default();
+        Q::access(&mut access);
+        assert!(
+            access.aliased.is_empty(),
+            "query {} accesses {} mutably more than once",
+            type_name::<Q>(),
+            access.aliased.join(", ")
+        );
+        access
+    }
+
+    pub fn add_read<T: 'static>(&mut self) {
+        if self.writes.contains(&TypeId::of::<T>()) {
+            self.aliased.push(type_name::<T>());
+        }
+        push_unique(&mut self.reads, TypeId::of::<T>());
+    }
+
+    pub fn add_write<T: 'static>(&mut self) {
+        let id = TypeId::of::<T>();
+        if self.reads.contains(&id) || self.writes.contains(&id) {
+            self.aliased.push(type_name::<T>());
+        }
+        push_unique(&mut self.writes, id);
+    }
+
+    /// Reads only the change ticks of the component which doesn't hand out references to it so
+    /// it can be used alongside a write in the same query
+    pub fn add_ticks_read<T: 'static>(&mut self) {
+        push_unique(&mut self.ticks_reads, TypeId::of::<T>());
+    }
+
+    pub fn add_resource_read<T: 'static>(&mut self) {
+        push_unique(&mut self.resource_reads, TypeId::of::<T>());
+    }
+
+    pub fn add_resource_write<T: 'static>(&mut self) {
+        push_unique(&mut self.resource_writes, TypeId::of::<T>());
+    }
+
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    /// Checks if two systems with these accesses can't run at the same time
+    pub fn conflicts(&self, other: &Access) -> bool {
+        fn overlaps(writes: &[TypeId], reads: &[TypeId], other_writes: &[TypeId]) -> bool {
+            writes
+                .iter()
+                .any(|id| reads.contains(id) || other_writes.contains(id))
+        }
+
+        self.exclusive
+            || other.exclusive
+            || overlaps(&self.writes, &other.reads, &other.writes)
+            || overlaps(&other.writes, &self.reads, &self.writes)
+            || overlaps(&self.writes, &other.ticks_reads, &[])
+            || overlaps(&other.writes, &self.ticks_reads, &[])
+            || overlaps(
+                &self.resource_writes,
+                &other.resource_reads,
+                &other.resource_writes,
+            )
+            || overlaps(
+                &other.resource_writes,
+                &self.resource_reads,
+                &self.resource_writes,
+            )
+    }
+
+    /// Checks if everything in the other access is allowed by this one
+    fn allows(&self, other: &Access) -> bool {
+        let can_read =
+            |id, writes: &[TypeId], reads: &[TypeId]| reads.contains(id) || writes.contains(id);
+
+        other
+            .reads
+            .iter()
+            .all(|id| can_read(id, &self.writes, &self.reads))
+            && other
+                .ticks_reads
+                .iter()
+                .all(|id| can_read(id, &self.writes, &self.reads))
+            && other.writes.iter().all(|id| self.writes.contains(id))
+            && other
+                .resource_reads
+                .iter()
+                .all(|id| can_read(id, &self.resource_writes, &self.resource_reads))
+            && other
+                .resource_writes
+                .iter()
+                .all(|id| self.resource_writes.contains(id))
+    }
+}
+
+fn push_unique(ids: &mut Vec<TypeId>, id: TypeId) {
+    if !ids.contains(&id) {
+        ids.push(id);
+    }
+}
+
+type SystemFn = Box<dyn FnMut(&mut SystemContext) + Send>;
+type ExclusiveSystemFn = Box<dyn FnMut(&mut World) + Send>;
+
+enum SystemKind {
+    Parallel(SystemFn),
+    Exclusive(ExclusiveSystemFn),
+}
+
+/// A function that runs on the world as part of a [crate::Schedule]
+///
+/// The components and resources it uses must be declared up front with the builder functions so
+/// the schedule knows which systems can run in parallel on worker threads.
+pub struct System {
+    name: &'static str,
+    kind: SystemKind,
+    access: Access,
+    pub(crate) before: Vec<&'static str>,
+    pub(crate) after: Vec<&'static str>,
+    commands: SystemCommands,
+}
+
+impl System {
+    pub fn new(name: &'static str, func: impl FnMut(&mut SystemContext) + Send + 'static) -> Self {
+        Self::with_kind(
+            name,
+            SystemKind::Parallel(Box::new(func)),
+            Access::default(),
+        )
+    }
+
+    /// Creates a system that gets the whole world mutably
+    /// It always runs on its own and never in parallel with other systems
+    pub fn exclusive(name: &'static str, func: impl FnMut(&mut World) + Send + 'static) -> Self {
+        let access = Access {
+            exclusive: true,
+            ..Default::default()
+        };
+        Self::with_kind(name, SystemKind::Exclusive(Box::new(func)), access)
+    }
+
+    fn with_kind(name: &'static str, kind: SystemKind, access: Access) -> Self {
+        Self {
+            name,
+            kind,
+            access,
+            before: Vec::new(),
+            after: Vec::new(),
+            commands: SystemCommands(Commands::default()),
+        }
+    }
+
+    pub fn reads<T: Component + Sync>(mut self) -> Self {
+        self.access.add_read::<T>();
+        self
+    }
+
+    pub fn writes<T: Component + Send + Sync>(mut self) -> Self {
+        self.access.add_write::<T>();
+        self
+    }
+
+    pub fn reads_resource<R: Resource + Sync>(mut self) -> Self {
+        self.access.add_resource_read::<R>();
+        self
+    }
+
+    pub fn writes_resource<R: Resource + Send + Sync>(mut self) -> Self {
+        self.access.add_resource_write::<R>();
+        self
+    }
+
+    /// Run this system before the system with the name in the same stage
+    pub fn before(mut self, name: &'static str) -> Self {
+        self.before.push(name);
+        self
+    }
+
+    /// Run this system after the system with the name in the same stage
+    pub fn after(mut self, name: &'static str) -> Self {
+        self.after.push(name);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    /// Runs the system on its own
+    pub(crate) fn run(&mut self, world: &mut World) {
+        match &mut self.kind {
+            SystemKind::Parallel(_) => unsafe { self.run_unchecked(world) },
+            SystemKind::Exclusive(func) => func(world),
+        }
+        self.apply_commands(world);
+    }
+
+    /// Runs the system without applying its commands
+    ///
+    /// # Safety
+    /// - Systems running at the same time must not have conflicting access
+    /// - The world must not be structurally changed while running
+    pub(crate) unsafe fn run_unchecked(&mut self, world: &World) {
+        let SystemKind::Parallel(func) = &mut self.kind else {
+            panic!("exclusive system '{}' can't run in parallel", self.name);
+        };
+
+        let mut context = SystemContext {
+            world,
+            name: self.name,
+            access: &self.access,
+            commands: &mut self.commands,
+        };
+        func(&mut context);
+    }
+
+    pub(crate) fn apply_commands(&mut self, world: &mut World) {
+        self.commands.0.apply(world);
+    }
+}
+
+/// Commands queued by a system which could be running on another thread
+struct SystemCommands(Commands);
+
+// SAFETY: SystemContext only allows queuing commands with Send components
+unsafe impl Send for SystemCommands {}
+
+/// The view of the world a [System] gets while running
+///
+/// Only the components and resources declared by the system can be accessed.
+/// Structural changes are queued and applied once the systems running alongside it finish.
+pub struct SystemContext<'w> {
+    world: &'w World,
+    name: &'static str,
+    access: &'w Access,
+    commands: &'w mut SystemCommands,
+}
+
+impl SystemContext<'_> {
+    pub fn query<Q: ReadOnlyQuery>(&self) -> QueryIter<'_, Q> {
+        self.check_query::<Q>();
+        self.world.query()
+    }
+
+    pub fn query_mut<Q: Query>(&mut self) -> QueryIterMut<'_, Q> {
+        self.check_query::<Q>();
+        // SAFETY: Access was checked and self is mutably borrowed so there is only one at a time
+        unsafe { QueryIterMut::new_unchecked(self.world) }
+    }
+
+    /// Same as [Self::query] but only goes through the archetypes cached by the state
+    /// The state can be kept by the system between runs, e.g. by capturing it in the closure
+    pub fn query_cached<'a, Q: ReadOnlyQuery>(
+        &'a self,
+        state: &'a mut QueryState<Q>,
+    ) -> QueryIter<'a, Q> {
+        self.check_query::<Q>();
+        state.iter(self.world)
+    }
+
+    /// Same as [Self::query_mut] but only goes through the archetypes cached by the state
+    pub fn query_cached_mut<'a, Q: Query>(
+        &'a mut self,
+        state: &'a mut QueryState<Q>,
+    ) -> QueryIterMut<'a, Q> {
+        self.check_query::<Q>();
+        // SAFETY: Access was checked and self is mutably borrowed so there is only one at a time
+        unsafe { state.iter_unchecked(self.world) }
+    }
+
+    pub fn resource<R: Resource>(&self) -> Option<&R> {
+        let mut access = Access::default();
+        access.add_resource_read::<R>();
+        self.check_access(&access, type_name::<R>());
+        self.world.resource()
+    }
+
+    pub fn resource_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        let mut access = Access::default();
+        access.add_resource_write::<R>();
+        self.check_access(&access, type_name::<R>());
+        // SAFETY: Access was checked and self is mutably borrowed so there is only one at a time
+        unsafe { self.world.resource_unchecked_mut() }
+    }
+
+    /// Requires the system to write the [Events] resource of E
+    ///
+    /// # Panics
+    /// - The events weren't added to the world with [World::add_event]
+    pub fn event_writer<E: Resource>(&mut self) -> EventWriter<'_, E> {
+        let events = self
+            .resource_mut::<Events<E>>()
+            .unwrap_or_else(|| panic!("Events {} were not added", type_name::<E>()));
+        EventWriter::new(events)
+    }
+
+    /// Reads the events the reader hasn't seen yet
+    /// Requires the system to read the [Events] resource of E
+    pub fn read_events<'a, E: Resource>(
+        &'a self,
+        reader: &mut EventReader<E>,
+    ) -> impl Iterator<Item = &'a E> {
+        let events = self.resource::<Events<E>>();
+        events
+            .map(|events| reader.read(events))
+            .into_iter()
+            .flatten()
+    }
+
+    pub fn spawn<C: ComponentTuple + Send + 'static>(&mut self, components: C) {
+        self.commands.0.spawn(components);
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.commands.0.despawn(entity);
+    }
+
+    pub fn insert<C: Component + Send>(&mut self, entity: Entity, component: C) {
+        self.commands.0.insert(entity, component);
+    }
+
+    pub fn remove<C: Component>(&mut self, entity: Entity) {
+        self.commands.0.remove::<C>(entity);
+    }
+
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.world.exists(entity)
+    }
+
+    pub fn change_tick(&self) -> u32 {
+        self.world.change_tick()
+    }
+
+    fn check_query<Q: Query>(&self) {
+        self.check_access(&Access::of_query::<Q>(), type_name::<Q>());
+    }
+
+    fn check_access(&self, access: &Access, name: &str) {
+        assert!(
+            self.access.allows(access),
+            "system '{}' accessed {name} without declaring it",
+            self.name
+        );
+    }
+}
@@ -1,26 +1,26 @@
-use std::{any::TypeId, rc::Rc};
+use std::rc::Rc;
 
 use super::*;
 
-#[derive(Default, Clone, Copy, Debug, PartialEq)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Component)]
 struct Position {
     x: f32,
     y: f32,
 }
 
-#[derive(Default, Clone, Copy, Debug, PartialEq)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Component)]
 struct Velocity {
     x: f32,
     y: f32,
 }
 
-#[derive(Default, Clone, Debug, PartialEq)]
+#[derive(Default, Clone, Debug, PartialEq, Component)]
 struct Metadata {
     rc: Rc<String>,
     stuff: Vec<String>,
 }
 
-#[derive(Default, Clone, Debug, PartialEq)]
+#[derive(Default, Clone, Debug, PartialEq, Component)]
 struct Empty;
 
 #[test]
@@ -28,7 +28,7 @@ struct Empty;
 fn empty_component() {
     let mut world = World::default();
     let id = world.spawn((Empty,));
-    assert_eq!(id, 0);
+    assert_eq!(id.index(), 0);
     assert_eq!(*world.get::<Empty>(id).unwrap(), Empty);
 }
 
@@ -36,9 +36,9 @@ fn empty_component() {
 fn normal_spawn_1_component() {
     let mut world = World::default();
     let id = world.spawn((Position { x: 1., y: 2. },));
-    assert_eq!(id, 0);
+    assert_eq!(id.index(), 0);
 
-    let position = world.get::<Position>(0).unwrap();
+    let position = world.get::<Position>(id).unwrap();
     assert_eq!(position.x, 1.);
     assert_eq!(position.y, 2.);
 }
@@ -47,14 +47,14 @@ fn normal_spawn_1_component() {
 fn spawn_raw_1_component() {
     let mut world = World::default();
     let position = Position { x: 1., y: 2. };
-    world.spawn_raw(&[TypeInfo::of::<Position>()], |archetype| unsafe {
+    let id = world.spawn_raw(&[TypeInfo::of::<Position>()], |archetype| unsafe {
         archetype.push_component_cloned(
             &position as *const Position as *const u8,
-            TypeId::of::<Position>(),
+            ComponentId::of::<Position>(),
         );
     });
 
-    let position = world.get::<Position>(0).unwrap();
+    let position = world.get::<Position>(id).unwrap();
     assert_eq!(position.x, 1.);
     assert_eq!(position.y, 2.);
 }
@@ -108,18 +108,47 @@ fn query_world() {
     let mut world = World::default();
     let mut position = Position { x: 1., y: 2. };
     let mut velocity = Velocity { x: 1., y: 2. };
-    world.spawn((position, velocity));
-    world.spawn((velocity, position));
+    let id = world.spawn((position, velocity));
+    let id2 = world.spawn((velocity, position));
     assert_eq!(world.archetypes.len(), 1);
 
-    let mut query = world.query_mut::<(Position, Velocity)>();
-    assert_eq!(query.next().unwrap(), (0, (&mut position, &mut velocity)));
-    assert_eq!(query.next().unwrap(), (1, (&mut position, &mut velocity)));
+    let mut query = world.query_mut::<(&mut Position, &mut Velocity)>();
+    assert_eq!(query.next().unwrap(), (id, (&mut position, &mut velocity)));
+    assert_eq!(query.next().unwrap(), (id2, (&mut position, &mut velocity)));
+}
+
+#[test]
+fn stale_entity() {
+    let mut world = World::default();
+    let id = world.spawn((Position { x: 1., y: 2. },));
+    world.despawn(id);
+
+    // The new entity reuses the slot but the old handle should not point to it
+    let id2 = world.spawn((Position { x: 3., y: 4. },));
+    assert_eq!(id.index(), id2.index());
+    assert_ne!(id, id2);
+    assert!(!world.exists(id));
+    assert!(world.exists(id2));
+    assert_eq!(world.get::<Position>(id), None);
+    assert!(world.entity(id).is_none());
+    assert_eq!(world.get::<Position>(id2).unwrap().x, 3.);
+}
+
+#[test]
+fn despawn_moves_last() {
+    let mut world = World::default();
+    let id = world.spawn((Position { x: 1., y: 2. },));
+    let id2 = world.spawn((Position { x: 3., y: 4. },));
+    world.despawn(id);
+
+    assert_eq!(
+        *world.get::<Position>(id2).unwrap(),
+        Position { x: 3., y: 4. }
+    );
+    assert_eq!(world.query::<(&Position,)>().count(), 1);
 }
 
 #[test]
-#[should_panic]
-// #[ignore = "TODO: fix this test"]
 fn multiple_borrow() {
     let mut world = World::default();
     let id = world.spawn((Metadata {
@@ -127,13 +156,85 @@ fn multiple_borrow() {
         ..Default::default()
     },));
 
-    let a = world.get::<Metadata>(id).unwrap();
+    let cell = world.cell();
+    let a = cell.get::<Metadata>(id).unwrap();
     let string = &a.stuff[0];
 
-    let a2 = world.get::<Metadata>(id).unwrap();
+    assert_eq!(
+        cell.get_mut::<Metadata>(id).err(),
+        Some(BorrowError::AlreadyBorrowed)
+    );
+    assert_eq!(string, "asdf");
+    drop(a);
+
+    let mut a2 = cell.get_mut::<Metadata>(id).unwrap();
     a2.stuff.resize(10, String::new());
+    assert_eq!(
+        cell.get::<Metadata>(id).err(),
+        Some(BorrowError::AlreadyBorrowedMut)
+    );
+    drop(a2);
+
+    assert_eq!(world.get::<Metadata>(id).unwrap().stuff.len(), 10);
+}
+
+#[test]
+fn world_cell() {
+    let mut world = World::default();
+    let id = world.spawn((Position::default(), Velocity { x: 1., y: 2. }));
+    let id2 = world.spawn((Position::default(),));
+    world.advance_tick();
+
+    let cell = world.cell();
+    {
+        let mut position = cell.get_mut::<Position>(id).unwrap();
+        let velocity = cell.get::<Velocity>(id).unwrap();
+        let velocity2 = cell.get::<Velocity>(id).unwrap();
+        position.x += velocity.x + velocity2.x;
+
+        // Entities in the same archetype share a borrow flag
+        assert_eq!(
+            cell.get::<Position>(id).err(),
+            Some(BorrowError::AlreadyBorrowedMut)
+        );
+        assert!(cell.get::<Position>(id2).is_ok());
+        assert_eq!(
+            cell.get_mut::<Velocity>(id).err(),
+            Some(BorrowError::AlreadyBorrowed)
+        );
+        assert_eq!(cell.get::<Velocity>(id2).err(), Some(BorrowError::NotFound));
+    }
+
+    assert!(cell.get_mut::<Velocity>(id).is_ok());
+    assert_eq!(world.get::<Position>(id).unwrap().x, 2.);
+    assert_eq!(world.query::<(Entity, Changed<Position>)>().count(), 1);
+    assert_eq!(world.query::<(Entity, Changed<Velocity>)>().count(), 1);
+}
+
+#[test]
+fn entity_mut_after_move() {
+    let mut world = World::default();
+    let id = world.spawn((Position { x: 1., y: 1. },));
+    world.spawn((Position::default(), Velocity::default()));
+
+    let mut entity = world.entity_mut(id).unwrap();
+    entity.get_mut::<Position>().unwrap().x = 2.;
+
+    // Adding and removing moves the entity between archetypes
+    entity.add(Velocity { x: 3., y: 3. });
+    entity.get_mut::<Velocity>().unwrap().y = 4.;
+    entity.remove::<Position>();
+    assert!(entity.get::<Position>().is_none());
+    assert_eq!(
+        *entity.get::<Velocity>().unwrap(),
+        Velocity { x: 3., y: 4. }
+    );
+    entity.insert(Position { x: 5., y: 5. });
+    assert_eq!(entity.get::<Position>().unwrap().x, 5.);
 
-    dbg!(string);
+    world.advance_tick();
+    *world.get_mut::<Position>(id).unwrap() = Position::default();
+    assert_eq!(world.query::<(Entity, Changed<Position>)>().count(), 1);
 }
 
 #[test]
@@ -142,3 +243,1099 @@ fn spawn_duplicate_components() {
     let mut world = World::default();
     world.spawn((Position { x: 1., y: 2. }, Position { x: 2., y: 2. }));
 }
+
+#[test]
+fn query_filters() {
+    let mut world = World::default();
+    let moving = world.spawn((Position { x: 1., y: 2. }, Velocity { x: 1., y: 1. }));
+    let still = world.spawn((Position { x: 3., y: 4. },));
+    world.spawn((Velocity::default(),));
+
+    let still_entities = world
+        .query::<(Entity, &Position, Without<Velocity>)>()
+        .map(|(_, (entity, _, _))| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(still_entities, vec![still]);
+
+    let moving_entities = world
+        .query::<(&Position, With<Velocity>)>()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(moving_entities, vec![moving]);
+
+    for (_, (position, velocity)) in world.query_mut::<(&mut Position, Option<&Velocity>)>() {
+        if let Some(velocity) = velocity {
+            position.x += velocity.x;
+        }
+    }
+    assert_eq!(world.get::<Position>(moving).unwrap().x, 2.);
+    assert_eq!(world.get::<Position>(still).unwrap().x, 3.);
+    assert_eq!(
+        world.query::<(Option<&Velocity>, (&Position,))>().count(),
+        2
+    );
+}
+
+#[test]
+fn change_detection() {
+    let mut world = World::default();
+    let id = world.spawn((Position::default(), Velocity::default()));
+    let id2 = world.spawn((Position::default(), Velocity::default()));
+    assert_eq!(world.query::<(Entity, Added<Position>)>().count(), 2);
+
+    world.advance_tick();
+    assert_eq!(world.query::<(Entity, Added<Position>)>().count(), 0);
+    assert_eq!(world.query::<(Entity, Changed<Position>)>().count(), 0);
+
+    // Only mutably queried components should be marked as changed
+    for (_, (position,)) in world.query_mut::<(&mut Position,)>() {
+        position.x += 1.;
+    }
+    world.set_changed::<Velocity>(id2);
+    assert_eq!(world.query::<(Entity, Changed<Position>)>().count(), 2);
+    let changed = world
+        .query::<(Entity, Changed<Velocity>)>()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(changed, vec![id2]);
+
+    // Moving archetypes keeps the ticks of existing components
+    world.advance_tick();
+    world.entity_mut(id).unwrap().add(Metadata::default());
+    assert_eq!(world.query::<(Added<Metadata>,)>().count(), 1);
+    assert_eq!(world.query::<(Entity, Changed<Position>)>().count(), 0);
+    let ticks = world.component_ticks::<Position>(id).unwrap();
+    assert!(ticks.added < world.change_tick());
+
+    // Either filter can match even in archetypes that don't have the other component
+    type AddedOrChanged = Or<Added<Metadata>, Changed<Velocity>>;
+    assert_eq!(world.query::<(Entity, AddedOrChanged)>().count(), 1);
+    world.set_changed::<Velocity>(id2);
+    let matched = world
+        .query::<(Entity, AddedOrChanged)>()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+    assert_eq!(matched.len(), 2);
+    assert!(matched.contains(&id) && matched.contains(&id2));
+}
+
+#[test]
+fn commands() {
+    let mut world = World::default();
+    let id = world.spawn((Position::default(),));
+    let id2 = world.spawn((Position::default(), Velocity::default()));
+
+    let mut commands: Commands = Commands::default();
+    for (entity, (_, velocity)) in world.query::<(&Position, Option<&Velocity>)>() {
+        if velocity.is_some() {
+            commands.despawn(entity);
+        } else {
+            commands.insert(entity, Velocity { x: 1., y: 1. });
+            commands.spawn((Position { x: 5., y: 5. },));
+        }
+    }
+
+    assert_eq!(commands.len(), 3);
+    commands.apply(&mut world);
+    assert!(commands.is_empty());
+
+    assert!(!world.exists(id2));
+    assert_eq!(
+        *world.get::<Velocity>(id).unwrap(),
+        Velocity { x: 1., y: 1. }
+    );
+    assert_eq!(world.query::<(&Position,)>().count(), 2);
+
+    commands.insert(id, Velocity { x: 2., y: 2. });
+    commands.remove::<Position>(id);
+    commands.despawn(id2);
+    commands.apply(&mut world);
+    assert_eq!(world.get::<Velocity>(id).unwrap().x, 2.);
+    assert!(!world.entity(id).unwrap().has::<Position>());
+}
+
+#[test]
+fn resources() {
+    let mut world = World::default();
+    assert!(world.resource::<Position>().is_none());
+
+    assert_eq!(world.insert_resource(Position { x: 1., y: 2. }), None);
+    world.insert_resource(Metadata::default());
+    assert!(world.has_resource::<Position>());
+    assert_eq!(world.resource::<Position>().unwrap().y, 2.);
+
+    world.resource_mut::<Position>().unwrap().x = 5.;
+    let old = world.insert_resource(Position { x: 3., y: 3. });
+    assert_eq!(old, Some(Position { x: 5., y: 2. }));
+
+    let cloned = world.clone();
+    world
+        .resource_mut::<Metadata>()
+        .unwrap()
+        .stuff
+        .push("a".into());
+    assert_eq!(
+        *cloned.resource::<Position>().unwrap(),
+        Position { x: 3., y: 3. }
+    );
+    assert!(cloned.resource::<Metadata>().unwrap().stuff.is_empty());
+
+    assert_eq!(
+        world.remove_resource::<Position>(),
+        Some(Position { x: 3., y: 3. })
+    );
+    assert!(!world.has_resource::<Position>());
+    assert!(world.remove_resource::<Velocity>().is_none());
+}
+
+#[test]
+fn schedule() {
+    let mut world = World::default();
+    world.spawn((Position::default(), Velocity { x: 1., y: 2. }));
+    world.spawn((Position::default(),));
+    world.insert_resource(Vec::<&'static str>::new());
+
+    let mut schedule = Schedule::default();
+    schedule
+        .add_system(
+            Stage::Update,
+            System::new("log_b", |context| {
+                context.resource_mut::<Vec<&str>>().unwrap().push("b");
+            })
+            .writes_resource::<Vec<&str>>()
+            .after("log_a"),
+        )
+        .add_system(
+            Stage::Update,
+            System::new("log_a", |context| {
+                context.resource_mut::<Vec<&str>>().unwrap().push("a");
+            })
+            .writes_resource::<Vec<&str>>(),
+        )
+        .add_system(
+            Stage::Update,
+            System::new("movement", |context| {
+                for (_, (position, velocity)) in context.query_mut::<(&mut Position, &Velocity)>() {
+                    position.x += velocity.x;
+                    position.y += velocity.y;
+                }
+            })
+            .writes::<Position>()
+            .reads::<Velocity>(),
+        )
+        .add_system(
+            Stage::Update,
+            System::new("spawn", |context| {
+                let count = context.query::<(&Velocity,)>().count();
+                if count < 2 {
+                    context.spawn((Velocity::default(),));
+                }
+            })
+            .reads::<Velocity>(),
+        )
+        .add_system(
+            Stage::PostUpdate,
+            System::exclusive("clear", |world| {
+                world.resource_mut::<Vec<&str>>().unwrap().push("clear");
+            }),
+        );
+
+    schedule.run(Stage::Update, &mut world);
+    assert_eq!(*world.resource::<Vec<&str>>().unwrap(), ["a", "b"]);
+    assert_eq!(world.query::<(&Velocity,)>().count(), 2);
+    let moved = world
+        .query::<(&Position,)>()
+        .filter(|(_, (position,))| **position == Position { x: 1., y: 2. })
+        .count();
+    assert_eq!(moved, 1);
+
+    schedule.run(Stage::Update, &mut world);
+    schedule.run(Stage::PostUpdate, &mut world);
+    assert_eq!(
+        *world.resource::<Vec<&str>>().unwrap(),
+        ["a", "b", "a", "b", "clear"]
+    );
+    assert_eq!(world.query::<(&Velocity,)>().count(), 2);
+}
+
+#[test]
+#[should_panic(expected = "without declaring it")]
+fn schedule_undeclared_access() {
+    let mut world = World::default();
+    world.spawn((Position::default(),));
+
+    let mut schedule = Schedule::default();
+    schedule.add_system(
+        Stage::Update,
+        System::new("movement", |context| {
+            context.query_mut::<(&mut Position,)>().count();
+        })
+        .reads::<Position>(),
+    );
+    schedule.run(Stage::Update, &mut world);
+}
+
+#[test]
+fn schedule_waiting_order() {
+    let mut world = World::default();
+    world.insert_resource(Vec::<&'static str>::new());
+
+    // "c" doesn't conflict with "a" but still has to wait for "b" which was added before it
+    let mut schedule = Schedule::default();
+    schedule
+        .add_system(
+            Stage::Update,
+            System::new("a", |context| {
+                context.query_mut::<(&mut Position,)>().count();
+            })
+            .writes::<Position>(),
+        )
+        .add_system(
+            Stage::Update,
+            System::new("b", |context| {
+                context.resource_mut::<Vec<&str>>().unwrap().push("b");
+            })
+            .writes_resource::<Vec<&str>>()
+            .after("a"),
+        )
+        .add_system(
+            Stage::Update,
+            System::new("c", |context| {
+                context.resource_mut::<Vec<&str>>().unwrap().push("c");
+            })
+            .writes_resource::<Vec<&str>>(),
+        );
+    schedule.run(Stage::Update, &mut world);
+    assert_eq!(*world.resource::<Vec<&str>>().unwrap(), ["b", "c"]);
+}
+
+#[test]
+fn schedule_reuses_threads() {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    let mut world = World::default();
+    let threads = Arc::new(Mutex::new(HashSet::new()));
+    let mut schedule = Schedule::default();
+    for name in ["a", "b", "c"] {
+        let threads = threads.clone();
+        schedule.add_system(
+            Stage::Update,
+            System::new(name, move |_| {
+                threads.lock().unwrap().insert(std::thread::current().id());
+            }),
+        );
+    }
+
+    // Batches run on the same workers every time instead of new threads
+    let count = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    for _ in 0..=count {
+        schedule.run(Stage::Update, &mut world);
+    }
+    assert!(threads.lock().unwrap().len() <= count.max(2));
+}
+
+#[test]
+#[should_panic(expected = "cycle")]
+fn schedule_cycle() {
+    let mut schedule = Schedule::default();
+    schedule
+        .add_system(Stage::Update, System::new("a", |_| {}).after("b"))
+        .add_system(Stage::Update, System::new("b", |_| {}).after("a"));
+    schedule.run(Stage::Update, &mut World::default());
+}
+
+#[test]
+fn dynamic_components() {
+    let mut world = World::default();
+    let schema = ComponentSchema::new("Health")
+        .field("current", FieldKind::Float)
+        .field("alive", FieldKind::Bool);
+    let health = world.register_component(schema.clone());
+    assert_eq!(world.register_component(schema), health);
+    assert_eq!(world.component_id("Health"), Some(health));
+
+    let id = world.spawn((Position::default(),));
+    let id2 = world.spawn((Position::default(), Velocity::default()));
+    for entity in [id, id2] {
+        let component = world.component_schema(health).unwrap().default_component();
+        world
+            .entity_mut(entity)
+            .unwrap()
+            .add_dynamic(health, component);
+    }
+
+    // Values are converted to the kind of the field
+    let component = world.get_dynamic_mut(id, health).unwrap();
+    assert!(component.set(0, FieldValue::Int(10)));
+    assert!(!component.set(1, FieldValue::Int(1)));
+    assert_eq!(
+        world.get_dynamic(id, health).unwrap().values(),
+        &[FieldValue::Float(10.), FieldValue::Bool(false)]
+    );
+
+    assert_eq!(world.query_dynamic(health).count(), 2);
+    world.advance_tick();
+    for (entity, component) in world.query_dynamic_mut(health) {
+        if entity == id2 {
+            component.set(1, FieldValue::Bool(true));
+        }
+    }
+    assert_eq!(
+        world.get_dynamic(id2, health).unwrap().get(1),
+        Some(&FieldValue::Bool(true))
+    );
+
+    // Cloned worlds keep their runtime defined components
+    let cloned = world.clone();
+    assert_eq!(
+        cloned.get_dynamic(id, health),
+        world.get_dynamic(id, health)
+    );
+
+    world.entity_mut(id).unwrap().remove_by_id(health);
+    assert!(!world.entity(id).unwrap().has_id(health));
+    assert!(world.entity(id).unwrap().has::<Position>());
+    assert_eq!(world.query_dynamic(health).count(), 1);
+}
+
+#[test]
+fn dynamic_components_across_worlds() {
+    let mut world = World::default();
+    let armor =
+        world.register_component(ComponentSchema::new("Armor").field("value", FieldKind::Int));
+    let health =
+        world.register_component(ComponentSchema::new("Health").field("value", FieldKind::Float));
+    let id = world.spawn((Position::default(),));
+    let mut component = world.component_schema(health).unwrap().default_component();
+    component.set(0, FieldValue::Float(5.));
+    world.entity_mut(id).unwrap().add_dynamic(health, component);
+
+    // Components are matched by name since the other world registered them in another order
+    let mut other = World::default();
+    let other_health = other.register_component(world.component_schema(health).unwrap().clone());
+    let copy = other.spawn_from_world(id, &world);
+    assert_ne!(other_health, health);
+    assert_eq!(other.component_id("Armor"), None);
+    assert_eq!(
+        other.get_dynamic(copy, other_health).unwrap().get(0),
+        Some(&FieldValue::Float(5.))
+    );
+    let copy = world.spawn_from_world(copy, &other);
+    assert!(world.get_dynamic(copy, health).is_some());
+    assert!(world.get_dynamic(copy, armor).is_none());
+}
+
+#[test]
+#[should_panic(expected = "does not match the fields")]
+fn dynamic_component_replace_mismatch() {
+    let mut world = World::default();
+    let schema = ComponentSchema::new("Health").field("current", FieldKind::Float);
+    let health = world.register_component(schema.clone());
+    let id = world.spawn((Position::default(),));
+    let mut entity = world.entity_mut(id).unwrap();
+    entity.add_dynamic(health, schema.default_component());
+    entity.insert_dynamic(health, DynamicComponent::default());
+}
+
+#[test]
+#[should_panic]
+fn dynamic_component_schema_conflict() {
+    let mut world = World::default();
+    world.register_component(ComponentSchema::new("Health").field("current", FieldKind::Float));
+    world.register_component(ComponentSchema::new("Health").field("current", FieldKind::Int));
+}
+
+#[test]
+fn component_hooks() {
+    use std::cell::RefCell;
+
+    let mut world = World::default();
+    let events = Rc::new(RefCell::new(Vec::new()));
+
+    let log = |events: &Rc<RefCell<Vec<_>>>, name: &'static str| {
+        let events = events.clone();
+        move |_: &mut World, entity: Entity| events.borrow_mut().push((name, entity))
+    };
+    world
+        .component_hooks::<Velocity>()
+        .on_add(log(&events, "add"))
+        .on_insert(log(&events, "insert"))
+        .on_remove(log(&events, "remove"));
+    world.on_despawn(log(&events, "despawn"));
+
+    let id = world.spawn((Position::default(), Velocity::default()));
+    let id2 = world.spawn((Position::default(),));
+    world.entity_mut(id2).unwrap().add(Velocity::default());
+    world
+        .entity_mut(id2)
+        .unwrap()
+        .insert(Velocity { x: 1., y: 1. });
+    world.entity_mut(id2).unwrap().remove::<Velocity>();
+    world.despawn(id);
+    assert_eq!(
+        *events.borrow(),
+        vec![
+            ("add", id),
+            ("insert", id),
+            ("add", id2),
+            ("insert", id2),
+            ("insert", id2),
+            ("remove", id2),
+            ("despawn", id),
+            ("remove", id),
+        ]
+    );
+
+    // Hooks can make structural changes to the entity that triggered them
+    world
+        .component_hooks::<Metadata>()
+        .on_add(|world, entity| world.entity_mut(entity).unwrap().remove::<Position>());
+    let mut entity = world.entity_mut(id2).unwrap();
+    entity.add(Metadata::default());
+    assert!(!entity.has::<Position>());
+    assert!(entity.has::<Metadata>());
+}
+
+#[test]
+fn snapshot_diff() {
+    let mut world = World::default();
+    let id = world.spawn((Position::default(), Velocity::default()));
+    let id2 = world.spawn((Position::default(),));
+    let id3 = world.spawn((Position { x: 3., y: 3. }, Metadata::default()));
+    world.advance_tick();
+    let old = world.snapshot();
+
+    world.advance_tick();
+    world.get_mut::<Position>(id).unwrap().x = 1.;
+    world.entity_mut(id).unwrap().remove::<Velocity>();
+    world
+        .entity_mut(id2)
+        .unwrap()
+        .add(Velocity { x: 2., y: 2. });
+    world.despawn(id3);
+    let id4 = world.spawn((Position { x: 4., y: 4. },));
+    let new = world.snapshot();
+
+    let diff = old.diff(&new);
+    assert_eq!(diff.spawned.len(), 1);
+    assert_eq!(diff.despawned.len(), 1);
+    assert_eq!(diff.despawned[0].entity, id3);
+    assert_eq!(diff.changed.len(), 3);
+    assert!(old.diff(&old).is_empty());
+
+    // Reverting brings back despawned entities with the same handles
+    diff.revert(&mut world);
+    assert!(!world.exists(id4));
+    assert_eq!(world.get::<Position>(id3).unwrap().x, 3.);
+    assert!(world.entity(id3).unwrap().has::<Metadata>());
+    assert_eq!(*world.get::<Position>(id).unwrap(), Position::default());
+    assert!(world.entity(id).unwrap().has::<Velocity>());
+    assert!(!world.entity(id2).unwrap().has::<Velocity>());
+
+    diff.apply(&mut world);
+    assert!(world.exists(id4));
+    assert!(!world.exists(id3));
+    assert_eq!(world.get::<Position>(id).unwrap().x, 1.);
+    assert_eq!(world.get::<Velocity>(id2).unwrap().x, 2.);
+
+    // Restoring a snapshot of some components leaves the others alone
+    let positions = world.snapshot_of(&[ComponentId::of::<Position>()]);
+    assert!(positions
+        .get(id2)
+        .unwrap()
+        .get(ComponentId::of::<Velocity>())
+        .is_none());
+    world.advance_tick();
+    world.get_mut::<Position>(id2).unwrap().x = 10.;
+    world.get_mut::<Velocity>(id2).unwrap().x = 10.;
+    positions.restore(&mut world);
+    assert_eq!(world.get::<Position>(id2).unwrap().x, 0.);
+    assert_eq!(world.get::<Velocity>(id2).unwrap().x, 10.);
+
+    // Changes made in the same tick as the snapshot keep the same ticks but are still found
+    world.advance_tick();
+    let snapshot = world.snapshot();
+    world.get_mut::<Position>(id2).unwrap().x = 5.;
+    assert_eq!(snapshot.diff(&world.snapshot()).changed.len(), 1);
+    snapshot.restore(&mut world);
+    assert_eq!(world.get::<Position>(id2).unwrap().x, 0.);
+}
+
+#[test]
+fn snapshot_generations() {
+    let mut world = World::default();
+    let e = world.spawn((Position::default(),));
+    let snapshot = world.snapshot();
+    world.despawn(e);
+    let f = world.spawn((Position::default(),));
+    assert_eq!(f.index(), e.index());
+
+    // Restoring gives back the old handle but the slot never reuses a handed out generation
+    snapshot.restore(&mut world);
+    assert!(world.exists(e));
+    assert!(!world.exists(f));
+    world.despawn(e);
+    let g = world.spawn((Position::default(),));
+    assert_ne!(g, f);
+    assert!(!world.exists(f));
+    assert!(!world.exists(e));
+}
+
+#[test]
+fn spawn_batch() {
+    let mut world = World::default();
+    let id = world.spawn((Position::default(),));
+    world.despawn(id);
+
+    let entities = world.spawn_batch((0..100).map(|i| {
+        let i = i as f32;
+        (Position { x: i, y: i }, Velocity::default())
+    }));
+    assert_eq!(entities.len(), 100);
+    assert_eq!(entities[0].index(), id.index());
+    assert_eq!(world.get::<Position>(entities[42]).unwrap().x, 42.);
+    assert_eq!(world.query::<(&Position, &Velocity)>().count(), 100);
+
+    // Some entities already have the component so it only gets replaced
+    world
+        .entity_mut(entities[0])
+        .unwrap()
+        .add(Metadata::default());
+    let id2 = world.spawn((Velocity::default(),));
+    let batch = entities[..10].iter().chain([&id2]).map(|entity| {
+        let metadata = Metadata {
+            stuff: vec![entity.to_string()],
+            ..Default::default()
+        };
+        (*entity, metadata)
+    });
+    world.insert_batch(batch);
+    assert_eq!(world.query::<(&Metadata,)>().count(), 11);
+    assert_eq!(
+        world.get::<Metadata>(id2).unwrap().stuff,
+        vec![id2.to_string()]
+    );
+    assert_eq!(
+        world.get::<Metadata>(entities[0]).unwrap().stuff,
+        vec![entities[0].to_string()]
+    );
+    assert_eq!(world.get::<Position>(entities[5]).unwrap().x, 5.);
+}
+
+#[test]
+fn sparse_set_storage() {
+    let mut world = World::default();
+    world.set_storage::<Metadata>(StorageType::SparseSet);
+    let id = world.spawn((Position::default(), Metadata::default()));
+    let id2 = world.spawn((Position { x: 2., y: 2. }, Velocity::default()));
+    assert_eq!(world.archetypes.len(), 2);
+
+    // Adding and removing a sparse component doesn't move the entity
+    world.advance_tick();
+    let metadata = Metadata {
+        stuff: vec!["hello".into()],
+        ..Default::default()
+    };
+    world.entity_mut(id2).unwrap().add(metadata.clone());
+    world.entity_mut(id).unwrap().remove::<Metadata>();
+    assert_eq!(world.archetypes.len(), 2);
+    assert_eq!(world.get::<Metadata>(id2), Some(&metadata));
+    assert!(!world.entity(id).unwrap().has::<Metadata>());
+
+    // Queries join both storages
+    let results: Vec<_> = world.query::<(&Position, &Metadata)>().collect();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, id2);
+    assert_eq!(world.query::<(&Position, Option<&Metadata>)>().count(), 2);
+    assert_eq!(world.query::<(With<Metadata>,)>().count(), 1);
+    assert_eq!(world.query::<(Without<Metadata>,)>().next().unwrap().0, id);
+    assert_eq!(world.query::<(Added<Metadata>,)>().count(), 1);
+    world.advance_tick();
+    assert_eq!(world.query::<(Changed<Metadata>,)>().count(), 0);
+    for (_, (metadata,)) in world.query_mut::<(&mut Metadata,)>() {
+        metadata.stuff.push("world".into());
+    }
+    assert_eq!(world.query::<(Changed<Metadata>,)>().count(), 1);
+
+    // Snapshots and clones include sparse components
+    let snapshot = world.snapshot();
+    let clone = world.spawn_from_world(id2, &world.clone());
+    assert_eq!(world.get::<Metadata>(clone).unwrap().stuff.len(), 2);
+    world.despawn(id2);
+    assert!(world.get::<Metadata>(id2).is_none());
+    assert_eq!(world.query::<(&Metadata,)>().count(), 1);
+    snapshot.restore(&mut world);
+    assert_eq!(world.get::<Metadata>(id2).unwrap().stuff.len(), 2);
+    assert!(!world.exists(clone));
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Component)]
+struct Tag<const N: usize>(usize);
+
+fn movement_bundle() -> (Position, Velocity) {
+    (Position { x: 1., y: 1. }, Velocity { x: 2., y: 2. })
+}
+
+#[test]
+fn nested_component_tuples() {
+    let mut world = World::default();
+    let id = world.spawn((
+        movement_bundle(),
+        (Tag::<0>(0), Tag::<1>(1), Tag::<2>(2), Tag::<3>(3)),
+        Tag::<4>(4),
+        Tag::<5>(5),
+        Tag::<6>(6),
+        Tag::<7>(7),
+        Tag::<8>(8),
+        Tag::<9>(9),
+        Tag::<10>(10),
+        Tag::<11>(11),
+        Tag::<12>(12),
+        (Tag::<13>(13), (Tag::<14>(14), Metadata::default())),
+    ));
+    assert_eq!(world.archetypes[0].type_infos().len(), 18);
+    assert_eq!(world.get::<Velocity>(id).unwrap().x, 2.);
+    assert_eq!(world.get::<Tag<14>>(id).unwrap().0, 14);
+
+    // Single components are tuples too
+    let id2 = world.spawn(Tag::<0>(1));
+    assert_eq!(world.get::<Tag<0>>(id2).unwrap().0, 1);
+
+    type Tags = (
+        &'static Tag<0>,
+        &'static Tag<4>,
+        &'static Tag<5>,
+        &'static Tag<6>,
+        &'static Tag<7>,
+        &'static Tag<8>,
+        &'static Tag<9>,
+        &'static Tag<10>,
+        &'static Tag<11>,
+        &'static Tag<12>,
+        &'static Tag<13>,
+        &'static Tag<14>,
+    );
+    let (entity, (tag, .., last)) = world.query::<Tags>().next().unwrap();
+    assert_eq!(entity, id);
+    assert_eq!((tag.0, last.0), (0, 14));
+}
+
+#[test]
+fn archetype_edges() {
+    let mut world = World::default();
+    let add_components = |world: &mut World| {
+        let entity = world.spawn((Position::default(),));
+        let mut entity = world.entity_mut(entity).unwrap();
+        entity.add(Velocity::default());
+        entity.add(Metadata::default());
+        entity.remove::<Velocity>();
+        entity.id()
+    };
+
+    let id = add_components(&mut world);
+    let stats = world.archetype_stats();
+    assert_eq!(
+        stats,
+        ArchetypeStats {
+            archetypes: 4,
+            empty_archetypes: 3,
+            add_edges: 3,
+            remove_edges: 3,
+        }
+    );
+
+    // The same transitions reuse the cached edges
+    let id2 = add_components(&mut world);
+    assert_eq!(world.archetype_stats(), stats);
+    world.entity_mut(id).unwrap().remove::<Position>();
+    assert_eq!(world.archetype_stats().remove_edges, 4);
+    assert!(world.entity(id).unwrap().has::<Metadata>());
+    assert!(world.entity(id2).unwrap().has::<Position>());
+}
+
+#[test]
+fn introspection() {
+    let mut world = World::default();
+    world.set_storage::<Metadata>(StorageType::SparseSet);
+    let entities: Vec<Entity> = (0..4)
+        .map(|_| world.spawn((Position::default(), Velocity::default())))
+        .collect();
+    world.spawn((Position::default(),));
+    world
+        .entity_mut(entities[0])
+        .unwrap()
+        .add(Metadata::default());
+    world.despawn(entities[1]);
+
+    let infos = world.archetype_infos();
+    let moving = infos
+        .iter()
+        .find(|info| info.components.len() == 2)
+        .unwrap();
+    assert_eq!(moving.entities, 3);
+    assert!(moving.capacity >= 3);
+    assert!(moving.bytes >= moving.capacity * (size_of::<Position>() + size_of::<Velocity>()));
+    assert!(moving
+        .components
+        .iter()
+        .all(|name| name.ends_with("Position") || name.ends_with("Velocity")));
+
+    let stats = world.component_stats();
+    let find = |name: &str| stats.iter().find(|stats| stats.typename.ends_with(name));
+    let position = find("Position").unwrap();
+    assert_eq!((position.entities, position.archetypes), (4, 2));
+    assert_eq!(position.storage, StorageType::Table);
+    let metadata = find("Metadata").unwrap();
+    assert_eq!((metadata.entities, metadata.archetypes), (1, 0));
+    assert_eq!(metadata.storage, StorageType::SparseSet);
+
+    let entity_stats = world.entity_stats();
+    assert_eq!(
+        entity_stats,
+        EntityStats {
+            alive: 4,
+            slots: 5,
+            free: 1,
+        }
+    );
+    assert_eq!(entity_stats.fragmentation(), 0.2);
+
+    // Components can be listed and read without knowing their types statically
+    world.get_mut::<Velocity>(entities[0]).unwrap().x = 3.;
+    let entity = world.entity(entities[0]).unwrap();
+    let infos = entity.type_infos();
+    assert_eq!(infos.len(), 3);
+    assert!(infos
+        .iter()
+        .any(|info| info.id == ComponentId::of::<Metadata>()));
+    let velocity = infos
+        .iter()
+        .find(|info| info.typename.ends_with("Velocity"))
+        .unwrap();
+    let ptr = entity.get_by_id(velocity.id).unwrap();
+    assert_eq!(unsafe { (*ptr.cast::<Velocity>()).x }, 3.);
+}
+
+/// Compares two ways of doing the same thing and prints how long each took
+/// Run with `cargo test --release -- --ignored --nocapture bench`
+fn bench(name: &str, labels: [&str; 2], first: impl FnOnce(), second: impl FnOnce()) {
+    let start = std::time::Instant::now();
+    first();
+    let first_time = start.elapsed();
+
+    let start = std::time::Instant::now();
+    second();
+    let second_time = start.elapsed();
+
+    println!(
+        "{name}: {} {first_time:?}, {} {second_time:?}",
+        labels[0], labels[1]
+    );
+}
+
+const BENCH_COUNT: usize = 100_000;
+
+#[test]
+#[ignore = "benchmark"]
+fn bench_spawn_batch() {
+    let components = |i: usize| (Position { x: i as f32, y: 0. }, Velocity::default());
+    let mut world = World::default();
+    let mut batch_world = World::default();
+
+    bench(
+        "spawn",
+        ["per entity", "batch"],
+        || {
+            for i in 0..BENCH_COUNT {
+                world.spawn(components(i));
+            }
+        },
+        || {
+            batch_world.spawn_batch((0..BENCH_COUNT).map(components));
+        },
+    );
+    assert_eq!(world.query::<(&Position,)>().count(), BENCH_COUNT);
+    assert_eq!(batch_world.query::<(&Position,)>().count(), BENCH_COUNT);
+}
+
+#[test]
+#[ignore = "benchmark"]
+fn bench_insert_batch() {
+    let mut world = World::default();
+    let entities = world.spawn_batch((0..BENCH_COUNT).map(|_| (Position::default(),)));
+    let mut batch_world = world.clone();
+
+    bench(
+        "insert",
+        ["per entity", "batch"],
+        || {
+            for entity in &entities {
+                world
+                    .entity_mut(*entity)
+                    .unwrap()
+                    .insert(Velocity::default());
+            }
+        },
+        || {
+            batch_world.insert_batch(entities.iter().map(|entity| (*entity, Velocity::default())));
+        },
+    );
+    assert_eq!(world.query::<(&Velocity,)>().count(), BENCH_COUNT);
+    assert_eq!(batch_world.query::<(&Velocity,)>().count(), BENCH_COUNT);
+}
+
+/// Spawns entities with every combination of the dynamic components to make many archetypes
+fn spawn_archetypes(world: &mut World, components: usize) {
+    let ids: Vec<ComponentId> = (0..components)
+        .map(|i| {
+            world.register_component(ComponentSchema {
+                name: format!("Tag{i}"),
+                fields: Vec::new(),
+            })
+        })
+        .collect();
+
+    for combination in 0..1 << components {
+        let entity = world.spawn((Position::default(),));
+        let mut entity = world.entity_mut(entity).unwrap();
+        for (bit, id) in ids.iter().enumerate() {
+            if combination & (1 << bit) != 0 {
+                entity.insert_dynamic(*id, DynamicComponent::default());
+            }
+        }
+    }
+}
+
+#[test]
+#[ignore = "benchmark"]
+fn bench_query_state() {
+    let mut world = World::default();
+    spawn_archetypes(&mut world, 10);
+    world.spawn((Position::default(), Velocity::default()));
+    let mut state = QueryState::<(&Position, &Velocity)>::new(&world);
+
+    bench(
+        "query",
+        ["uncached", "cached"],
+        || {
+            for _ in 0..1000 {
+                assert_eq!(world.query::<(&Position, &Velocity)>().count(), 1);
+            }
+        },
+        || {
+            for _ in 0..1000 {
+                assert_eq!(state.iter(&world).count(), 1);
+            }
+        },
+    );
+}
+
+#[test]
+fn query_state() {
+    let mut world = World::default();
+    world.spawn((Position::default(), Velocity { x: 1., y: 1. }));
+    let mut state = QueryState::<(&mut Position, &Velocity)>::default();
+    assert_eq!(state.iter_mut(&mut world).count(), 1);
+
+    // Archetypes created after the state was used are picked up
+    spawn_archetypes(&mut world, 3);
+    let id = world.spawn((
+        Position::default(),
+        Velocity { x: 2., y: 2. },
+        Metadata::default(),
+    ));
+    for (_, (position, velocity)) in state.iter_mut(&mut world) {
+        position.x += velocity.x;
+    }
+    assert_eq!(world.get::<Position>(id).unwrap().x, 2.);
+
+    // The cache is rebuilt for another world and when the storage of a component changes
+    let mut other = world.clone();
+    other.despawn(id);
+    assert_eq!(state.iter_mut(&mut other).count(), 1);
+    let mut world = World::default();
+    let mut state = QueryState::<(&Position, Without<Velocity>)>::new(&world);
+    world.spawn((Position::default(),));
+    world.set_storage::<Velocity>(StorageType::SparseSet);
+    let id = world.spawn((Position::default(), Velocity::default()));
+    assert_eq!(state.iter(&world).count(), 1);
+    world.entity_mut(id).unwrap().remove::<Velocity>();
+    assert_eq!(state.iter(&world).count(), 2);
+
+    // Systems can keep their state between runs
+    let mut schedule = Schedule::default();
+    let mut state = QueryState::<(&mut Position,)>::default();
+    schedule.add_system(
+        Stage::Update,
+        System::new("move", move |context| {
+            for (_, (position,)) in context.query_cached_mut(&mut state) {
+                position.y += 1.;
+            }
+        })
+        .writes::<Position>(),
+    );
+    schedule.run(Stage::Update, &mut world);
+    schedule.run(Stage::Update, &mut world);
+    assert_eq!(world.get::<Position>(id).unwrap().y, 2.);
+}
+
+#[test]
+fn par_for_each() {
+    let mut world = World::default();
+    world.spawn_batch(
+        (0..3000).map(|i| (Position { x: i as f32, y: 0. }, Velocity { x: 1., y: 2. })),
+    );
+    world.spawn_batch((0..3000).map(|i| (Position { x: i as f32, y: 0. },)));
+    world.advance_tick();
+
+    world
+        .query_mut::<(&mut Position, Option<&Velocity>)>()
+        .par_for_each(|_, (position, velocity)| {
+            if let Some(velocity) = velocity {
+                position.x += velocity.x;
+                position.y += velocity.y;
+            }
+        });
+
+    let total = std::sync::atomic::AtomicUsize::new(0);
+    world
+        .query::<(&Position, &Velocity, Changed<Position>)>()
+        .par_for_each(|_, (position, _, _)| {
+            assert_eq!(position.y, 2.);
+            total.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+    assert_eq!(total.into_inner(), 3000);
+    assert_eq!(world.query::<(Changed<Position>,)>().count(), 6000);
+
+    // Only the entities the iterator hasn't reached yet are processed
+    let mut query = world.query_mut::<(&mut Velocity,)>();
+    for _ in 0..1000 {
+        query.next();
+    }
+    query.par_for_each(|_, (velocity,)| velocity.x = 0.);
+    assert_eq!(
+        world
+            .query::<(&Velocity,)>()
+            .filter(|(_, (velocity,))| velocity.x == 0.)
+            .count(),
+        2000
+    );
+}
+
+#[test]
+fn events() {
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    struct Hit(u32);
+
+    let mut world = World::default();
+    world.add_event::<Hit>();
+    let mut early_reader = world.events::<Hit>().unwrap().reader();
+
+    world.send_event(Hit(1));
+    world.event_writer().send_batch([Hit(2), Hit(3)]);
+    let mut late_reader = world.events::<Hit>().unwrap().reader_current();
+    world.send_event(Hit(4));
+
+    let events = world.events::<Hit>().unwrap();
+    assert_eq!(early_reader.len(events), 4);
+    assert!(early_reader.read(events).copied().eq([1, 2, 3, 4].map(Hit)));
+    assert!(early_reader.is_empty(events));
+    assert!(late_reader.read(events).copied().eq([Hit(4)]));
+
+    // Events survive one frame boundary so readers running before the writer still see them
+    world.update_events();
+    world.send_event(Hit(5));
+    let events = world.events::<Hit>().unwrap();
+    assert_eq!(events.len(), 5);
+    assert!(early_reader.read(events).copied().eq([Hit(5)]));
+
+    // Events that weren't read before the second boundary are dropped
+    let mut missed_reader = EventReader::<Hit>::default();
+    world.update_events();
+    world.update_events();
+    assert!(world.events::<Hit>().unwrap().is_empty());
+    assert_eq!(missed_reader.read(world.events().unwrap()).count(), 0);
+
+    // Systems keep their reader between runs
+    world.insert_resource(Vec::<u32>::new());
+    let mut schedule = Schedule::default();
+    schedule
+        .add_system(
+            Stage::Update,
+            System::new("send", |context| {
+                context.event_writer().send(Hit(6));
+            })
+            .writes_resource::<Events<Hit>>(),
+        )
+        .add_system(Stage::PostUpdate, {
+            let mut reader = EventReader::<Hit>::default();
+            System::new("receive", move |context| {
+                let hits: Vec<u32> = context.read_events(&mut reader).map(|hit| hit.0).collect();
+                context.resource_mut::<Vec<u32>>().unwrap().extend(hits);
+            })
+            .reads_resource::<Events<Hit>>()
+            .writes_resource::<Vec<u32>>()
+        });
+
+    for _ in 0..2 {
+        schedule.run(Stage::Update, &mut world);
+        schedule.run(Stage::PostUpdate, &mut world);
+        world.update_events();
+    }
+    assert_eq!(*world.resource::<Vec<u32>>().unwrap(), [6, 6]);
+}
+
+#[test]
+#[should_panic(expected = "mutably more than once")]
+fn query_aliasing_write_write() {
+    let mut world = World::default();
+    world.spawn((Position::default(),));
+    world.query_mut::<(&mut Position, &mut Position)>().count();
+}
+
+// Kept free of threads and hooks so it can run under `cargo miri test query_aliasing`
+#[test]
+fn query_aliasing_checked() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut world = World::default();
+    world.spawn((Position::default(), Velocity { x: 1., y: 2. }));
+
+    let mut aliases = |check: &mut dyn FnMut(&mut World)| {
+        catch_unwind(AssertUnwindSafe(|| check(&mut world))).is_err()
+    };
+    assert!(aliases(&mut |world| {
+        world.query_mut::<(&mut Position, &Position)>().count();
+    }));
+    assert!(aliases(&mut |world| {
+        world
+            .query_mut::<(Option<&mut Position>, Option<&mut Position>)>()
+            .count();
+    }));
+    assert!(aliases(&mut |world| {
+        QueryState::<(&Position, (Entity, &mut Position))>::new(world)
+            .iter_mut(world)
+            .count();
+    }));
+    assert!(aliases(&mut |world| {
+        world
+            .query_mut::<(&mut Position, Option<&Position>)>()
+            .par_for_each(|_, _| ());
+    }));
+
+    // Change filters only read the ticks so they can be used with a write
+    for (_, (position, velocity, ())) in
+        world.query_mut::<(&mut Position, &Velocity, Changed<Velocity>)>()
+    {
+        position.x += velocity.x;
+    }
+    let mut state = QueryState::<(&mut Position, Without<Empty>)>::new(&world);
+    for (_, (position, ())) in state.iter_mut(&mut world) {
+        position.y += 1.;
+    }
+    let position = world.query::<&Position>().next().unwrap().1;
+    assert_eq!(*position, Position { x: 1., y: 1. });
+}
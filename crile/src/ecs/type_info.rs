@@ -1,11 +1,35 @@
 use std::{alloc::Layout, any::TypeId, mem::ManuallyDrop};
 
-use crate::Component;
+use crate::{Component, DynamicComponent};
+
+/// Identifies a component type
+/// Either a Rust type or a component registered at runtime with [crate::World::register_component]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ComponentId {
+    Type(TypeId),
+    Dynamic(u32),
+}
+
+impl ComponentId {
+    pub fn of<T: 'static>() -> Self {
+        Self::Type(TypeId::of::<T>())
+    }
+}
+
+// Written by hand so it can be used with NoHashHasher
+impl std::hash::Hash for ComponentId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match self {
+            ComponentId::Type(id) => id.hash(state),
+            ComponentId::Dynamic(index) => state.write_u32(*index),
+        }
+    }
+}
 
 /// Stores information about a component type to be used inside component arrays
 #[derive(Debug, Clone)]
 pub struct TypeInfo {
-    pub id: TypeId,
+    pub id: ComponentId,
     pub layout: Layout,
     pub drop: unsafe fn(*mut u8),
     /// Calls the type's clone function on T at src cloning to dst
@@ -24,13 +48,24 @@ impl TypeInfo {
             drop: |ptr| unsafe {
                 ptr.cast::<T>().drop_in_place();
             },
-            id: TypeId::of::<T>(),
+            id: ComponentId::of::<T>(),
             layout: Layout::new::<T>(),
             typename: std::any::type_name::<T>(),
         }
     }
 }
 
+impl TypeInfo {
+    /// Type info of a runtime defined component which is stored as a [DynamicComponent]
+    pub(crate) fn dynamic(index: u32, name: &'static str) -> Self {
+        Self {
+            id: ComponentId::Dynamic(index),
+            typename: name,
+            ..Self::of::<DynamicComponent>()
+        }
+    }
+}
+
 impl std::fmt::Display for TypeInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "TypeInfo({})", self.typename)
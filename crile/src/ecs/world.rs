@@ -1,7 +1,16 @@
-use std::any::TypeId;
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use super::{Archetype, ComponentTuple, QueryIter, QueryIterMut, TypeInfo};
-use crate::Component;
+use super::{
+    Archetype, ArchetypeInfo, ArchetypeStats, ComponentArray, ComponentHooks, ComponentId,
+    ComponentSchema, ComponentSnapshot, ComponentStats, ComponentTicks, ComponentTuple,
+    DynamicComponent, DynamicComponents, Entity, EntitySnapshot, EntityStats, EventWriter, Events,
+    Hooks, Query, QueryIter, QueryIterMut, QueryTicks, ReadOnlyQuery, Resource, Resources,
+    Snapshot, SparseSet, StorageType, TypeInfo, WorldCell,
+};
+use crate::{Component, NoHashHashMap};
 
 #[derive(Clone, Copy, Default, Debug)]
 pub(crate) struct EntityLocation {
@@ -9,6 +18,24 @@ pub(crate) struct EntityLocation {
     component_index: usize,
 }
 
+/// Identifies a world and the layout of its archetypes for [crate::QueryState]
+/// Cloned worlds get a new id since their archetypes can diverge
+#[derive(PartialEq, Eq, Debug)]
+struct WorldId(u64);
+
+impl WorldId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Clone for WorldId {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
 /// Contains all the data for an ECS instance.
 ///
 /// This ECS works by storing a bunch of archetypes where each archetype stores all the components
@@ -17,7 +44,7 @@ pub(crate) struct EntityLocation {
 /// if that archetype contains the components in the query.
 /// This means that it is more optimized for querying components rather than adding/removing components
 /// and having few different archetypes.
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct World {
     pub(crate) archetypes: Vec<Archetype>,
     /// Maps to the archetype index inside self.archetypes from a array of component type ids
@@ -25,11 +52,55 @@ pub struct World {
 
     free_entity_indexs: Vec<usize>,
     pub(crate) entity_locations: Vec<EntityLocation>,
+    /// Generation of each entity slot, incremented every time an entity in the slot is despawned
+    entity_generations: Vec<u32>,
+    /// Highest generation each slot has had, so restoring an older handle with
+    /// [Self::spawn_snapshot] never lets the slot reuse a generation that was already handed out
+    max_generations: Vec<u32>,
     valid_entity_locations: Vec<bool>, // Keep bool seperate to save memory because of alignement
+
+    /// Tick that component additions and mutable accesses are marked with
+    change_tick: u32,
+    /// Value of change_tick at the last call to [Self::advance_tick]
+    last_change_tick: u32,
+
+    /// Global data that isn't tied to any entity
+    resources: Resources,
+    /// Component types registered at runtime
+    dynamic_components: DynamicComponents,
+    /// Functions to run when components are added or removed and entities are despawned
+    hooks: Hooks,
+    /// Components stored outside of archetypes, see [StorageType::SparseSet]
+    pub(crate) sparse_sets: NoHashHashMap<ComponentId, SparseSet>,
+    /// Functions that swap the buffers of each [Events] resource added with [Self::add_event]
+    event_updaters: Vec<fn(&mut World)>,
+    id: WorldId,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            archetypes: Vec::new(),
+            type_ids_index_map: Default::default(),
+            free_entity_indexs: Vec::new(),
+            entity_locations: Vec::new(),
+            entity_generations: Vec::new(),
+            max_generations: Vec::new(),
+            valid_entity_locations: Vec::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+            resources: Resources::default(),
+            dynamic_components: DynamicComponents::default(),
+            hooks: Hooks::default(),
+            sparse_sets: NoHashHashMap::default(),
+            event_updaters: Vec::new(),
+            id: WorldId::new(),
+        }
+    }
 }
 
 impl World {
-    pub fn spawn<T: ComponentTuple>(&mut self, components: T) -> usize {
+    pub fn spawn<T: ComponentTuple>(&mut self, components: T) -> Entity {
         self.spawn_raw(&T::type_infos(), |archetype| components.move_all(archetype))
     }
 
@@ -39,95 +110,780 @@ impl World {
         &mut self,
         type_infos: &[TypeInfo],
         put_func: impl FnOnce(&mut Archetype),
-    ) -> usize {
-        let index = self.next_free_index();
-        assert!(!self.exists(index), "id {index} already in use");
+    ) -> Entity {
+        let entity = self.alloc_entity();
+        self.spawn_raw_at(entity, type_infos, put_func);
+        entity
+    }
 
-        let archetype_index = self.archetype_index_from_infos(type_infos);
-        let archetype = &mut self.archetypes[archetype_index];
+    /// Same as [Self::spawn_raw] but with an entity handle that was already allocated
+    fn spawn_raw_at(
+        &mut self,
+        entity: Entity,
+        type_infos: &[TypeInfo],
+        put_func: impl FnOnce(&mut Archetype),
+    ) {
+        if !type_infos.iter().any(|info| self.is_sparse(info.id)) {
+            let archetype_index = self.archetype_index_from_infos(type_infos);
+            self.place_entity(entity, archetype_index, put_func);
+            self.trigger_spawn_hooks(entity, type_infos);
+            return;
+        }
 
-        let entity_index = archetype.new_entity(index);
-        put_func(archetype);
+        // Put the components into a temporary archetype first so they can be split up between the
+        // entity's archetype and the sparse sets
+        let mut staging = Archetype::new(type_infos.into());
+        staging.new_entity(entity);
+        put_func(&mut staging);
+
+        let is_sparse: Vec<bool> = type_infos
+            .iter()
+            .map(|info| self.is_sparse(info.id))
+            .collect();
+        let table_infos: Vec<TypeInfo> = type_infos
+            .iter()
+            .zip(&is_sparse)
+            .filter(|(_, is_sparse)| !**is_sparse)
+            .map(|(info, _)| info.clone())
+            .collect();
+
+        let archetype_index = self.archetype_index_from_infos(&table_infos);
+        self.place_entity(entity, archetype_index, |archetype| {
+            for (array, is_sparse) in staging.component_arrays.iter().zip(&is_sparse) {
+                if !is_sparse {
+                    unsafe { archetype.push_component(array.get_component_ptr(0), array.get_id()) };
+                }
+            }
+        });
+
+        let ticks = ComponentTicks::new(self.change_tick);
+        for (array, is_sparse) in staging.component_arrays.iter().zip(&is_sparse) {
+            if *is_sparse {
+                let set = self.sparse_sets.get_mut(&array.get_id()).unwrap();
+                unsafe { set.insert(entity, array.get_component_ptr(0), ticks) };
+            }
+        }
+
+        // Every component was moved out so none of them should be dropped
+        staging.remove_entity(0, false);
+        self.trigger_spawn_hooks(entity, type_infos);
+    }
+
+    /// Spawns an entity for each tuple of components
+    ///
+    /// Faster than calling [Self::spawn] for each one since the archetype is only looked up once
+    /// and space for all the entities is reserved up front.
+    pub fn spawn_batch<T: ComponentTuple>(
+        &mut self,
+        batch: impl IntoIterator<Item = T>,
+    ) -> Vec<Entity> {
+        let batch = batch.into_iter();
+        let type_infos = T::type_infos();
+        if type_infos.iter().any(|info| self.is_sparse(info.id)) {
+            return batch.map(|components| self.spawn(components)).collect();
+        }
+
+        let archetype_index = self.archetype_index_from_infos(&type_infos);
+
+        let (count, _) = batch.size_hint();
+        self.archetypes[archetype_index].reserve(count);
+        self.reserve_entities(count);
+
+        let mut entities = Vec::with_capacity(count);
+        for components in batch {
+            let entity = self.alloc_entity();
+            self.place_entity(entity, archetype_index, |archetype| {
+                components.move_all(archetype)
+            });
+            entities.push(entity);
+        }
+
+        for entity in &entities {
+            self.trigger_spawn_hooks(*entity, &type_infos);
+        }
+        entities
+    }
+
+    /// Adds the component to each entity or replaces it if the entity already has one
+    ///
+    /// Space is reserved in every archetype the entities move to before any of them are moved.
+    ///
+    /// # Panics
+    /// - Any of the entities don't exist
+    pub fn insert_batch<T: Component>(&mut self, batch: impl IntoIterator<Item = (Entity, T)>) {
+        let batch: Vec<(Entity, T)> = batch.into_iter().collect();
+        let id = ComponentId::of::<T>();
+
+        // Count how many entities move into each archetype
+        let mut counts: hashbrown::HashMap<usize, usize> = Default::default();
+        for (entity, _) in &batch {
+            let location = self
+                .location(*entity)
+                .unwrap_or_else(|| panic!("Tried to insert into non-existent {entity}"));
+            let source = location.archetype_index;
+            if self.is_sparse(id) || self.archetypes[source].has_id(id) {
+                continue;
+            }
+
+            let target = self.archetype_with(source, &TypeInfo::of::<T>());
+            *counts.entry(target).or_default() += 1;
+        }
 
-        if index >= self.entity_locations.len() {
-            self.entity_locations
-                .resize_with(index + 1, Default::default);
-            self.valid_entity_locations.resize_with(index + 1, || false);
+        for (target, count) in counts {
+            self.archetypes[target].reserve(count);
         }
 
+        for (entity, component) in batch {
+            self.entity_mut(entity).unwrap().insert(component);
+        }
+    }
+
+    /// Puts the new entity into the archetype without running any hooks
+    fn place_entity(
+        &mut self,
+        entity: Entity,
+        archetype_index: usize,
+        put_func: impl FnOnce(&mut Archetype),
+    ) {
+        let index = entity.index();
+        assert!(
+            !self.valid_entity_locations[index],
+            "{entity} already in use"
+        );
+
+        let archetype = &mut self.archetypes[archetype_index];
+        let component_index = archetype.new_entity(entity);
+        put_func(archetype);
+        archetype.set_ticks(component_index, ComponentTicks::new(self.change_tick));
+
         self.entity_locations[index] = EntityLocation {
             archetype_index,
-            component_index: entity_index,
+            component_index,
         };
         self.valid_entity_locations[index] = true;
+    }
 
-        index
+    fn trigger_spawn_hooks(&mut self, entity: Entity, type_infos: &[TypeInfo]) {
+        if !self.hooks.is_empty() {
+            for type_info in type_infos {
+                self.trigger_add(entity, type_info.id);
+            }
+        }
     }
 
-    pub fn spawn_from_world(&mut self, other_index: usize, world: &World) -> usize {
-        let location = world.location(other_index).expect("Entity does not exist");
-        let source_arch = &world.archetypes[location.archetype_index];
+    /// Spawns the entity from a snapshot with the same handle it had when the snapshot was taken
+    ///
+    /// # Panics
+    /// - The entity's slot is being used by another entity
+    pub fn spawn_snapshot(&mut self, snapshot: &EntitySnapshot) {
+        let entity = snapshot.entity;
+        self.alloc_entity_at(entity);
+        self.spawn_cloned_at(entity, &snapshot.components);
+    }
+
+    /// Spawns the entity with a clone of each component
+    fn spawn_cloned_at(&mut self, entity: Entity, components: &[ComponentSnapshot]) {
+        let type_infos: Vec<TypeInfo> = components
+            .iter()
+            .map(|component| component.type_info().clone())
+            .collect();
+        self.spawn_raw_at(entity, &type_infos, |archetype| {
+            for component in components {
+                unsafe { archetype.push_component_cloned(component.as_ptr(), component.id()) };
+            }
+        });
+    }
+
+    /// Copies every entity and all of their components
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.snapshot_entities(|_| true), None, self.change_tick)
+    }
+
+    /// Copies every entity but only the components with the ids
+    pub fn snapshot_of(&self, components: &[ComponentId]) -> Snapshot {
+        let entities = self.snapshot_entities(|id| components.contains(&id));
+        Snapshot::new(entities, Some(components.to_vec()), self.change_tick)
+    }
+
+    fn snapshot_entities(&self, filter: impl Fn(ComponentId) -> bool) -> Vec<EntitySnapshot> {
+        let mut entities = Vec::new();
+        for (archetype_index, archetype) in self.archetypes.iter().enumerate() {
+            for (component_index, entity) in archetype.entities.iter().enumerate() {
+                let location = EntityLocation {
+                    archetype_index,
+                    component_index,
+                };
+                entities.push(EntitySnapshot {
+                    entity: *entity,
+                    components: self.clone_components(location, *entity, &filter),
+                });
+            }
+        }
+        entities
+    }
+
+    /// Clones the components of the entity that pass the filter sorted by id
+    fn clone_components(
+        &self,
+        location: EntityLocation,
+        entity: Entity,
+        filter: impl Fn(ComponentId) -> bool,
+    ) -> Vec<ComponentSnapshot> {
+        let archetype = &self.archetypes[location.archetype_index];
+        let table = archetype
+            .component_arrays
+            .iter()
+            .map(|array| (array, location.component_index));
+        let sparse = self
+            .sparse_sets
+            .values()
+            .filter_map(|set| Some((&set.dense, set.dense_index(entity)?)));
+
+        let mut components: Vec<ComponentSnapshot> = table
+            .chain(sparse)
+            .filter(|(array, _)| filter(array.get_id()))
+            .map(|(array, index)| unsafe {
+                ComponentSnapshot::clone_from_ptr(
+                    array.type_info(),
+                    array.ticks[index],
+                    array.get_component_ptr(index),
+                )
+            })
+            .collect();
+        components.sort_unstable_by_key(|component| component.id());
+        components
+    }
+
+    /// Copies a single component of the entity
+    pub fn clone_component(&self, entity: Entity, id: ComponentId) -> Option<ComponentSnapshot> {
+        let location = self.location(entity)?;
+        self.clone_components(location, entity, |other| other == id)
+            .pop()
+    }
+
+    pub fn spawn_from_world(&mut self, other: Entity, world: &World) -> Entity {
+        let location = world.location(other).expect("Entity does not exist");
 
         // Clone the entity's components
-        self.spawn_raw(source_arch.type_infos(), |target_arch| {
-            for array in source_arch.component_arrays.iter() {
-                let ptr = array.get_component_ptr(location.component_index);
-                unsafe {
-                    target_arch.push_component_cloned(ptr, array.get_type_id());
+        // Runtime defined components can have other ids in this world so they are matched by
+        // name and registered if this world doesn't have them yet
+        let mut components: Vec<ComponentSnapshot> = world
+            .clone_components(location, other, |_| true)
+            .into_iter()
+            .map(|component| match component.id() {
+                ComponentId::Dynamic(_) => {
+                    let schema = world.component_schema(component.id()).unwrap();
+                    let id = self.register_component(schema.clone());
+                    component.with_type_info(self.dynamic_type_info(id).unwrap())
                 }
-            }
-        })
+                ComponentId::Type(_) => component,
+            })
+            .collect();
+        components.sort_unstable_by_key(|component| component.id());
+        let entity = self.alloc_entity();
+        self.spawn_cloned_at(entity, &components);
+        entity
     }
 
-    pub fn despawn(&mut self, index: usize) {
-        let location = self
-            .location(index)
-            .expect("Tried to despawn non-existent entity");
+    /// Despawns the entity after running the despawn observers and the remove hooks of each of its
+    /// components
+    pub fn despawn(&mut self, entity: Entity) {
+        assert!(self.exists(entity), "Tried to despawn non-existent entity");
+
+        if !self.hooks.is_empty() {
+            for observer in self.hooks.despawn_observers() {
+                if self.exists(entity) {
+                    observer(self, entity);
+                }
+            }
+
+            let ids = self
+                .entity(entity)
+                .map_or_else(Vec::new, |entity| entity.component_ids());
+            for id in ids {
+                self.trigger_remove(entity, id);
+            }
+
+            // The hooks could have despawned the entity themselves
+            if !self.exists(entity) {
+                return;
+            }
+        }
+
+        let location = self.location(entity).unwrap();
+
+        for set in self.sparse_sets.values_mut() {
+            set.remove(entity, true);
+        }
 
         let archetype = &mut self.archetypes[location.archetype_index];
-        let moved_index = archetype.remove_entity(location.component_index, true);
-        self.entity_locations[moved_index].component_index = location.component_index;
-        self.valid_entity_locations[index] = false;
+        let moved = archetype.remove_entity(location.component_index, true);
+        self.entity_locations[moved.index()].component_index = location.component_index;
 
+        let index = entity.index();
+        self.valid_entity_locations[index] = false;
+        // Invalidate any handles still pointing to this slot
+        let generation = self.entity_generations[index].max(self.max_generations[index]);
+        self.entity_generations[index] = generation.wrapping_add(1);
+        self.max_generations[index] = generation.wrapping_add(1);
         self.free_entity_indexs.push(index);
     }
 
-    pub fn query<T: ComponentTuple>(&self) -> QueryIter<T> {
+    /// Iterates over every entity that matches the query, see [Query] for what can be queried
+    pub fn query<Q: ReadOnlyQuery>(&self) -> QueryIter<'_, Q> {
         QueryIter::new(self)
     }
 
-    pub fn query_mut<T: ComponentTuple>(&mut self) -> QueryIterMut<T> {
+    pub fn query_mut<Q: Query>(&mut self) -> QueryIterMut<'_, Q> {
         QueryIterMut::new(self)
     }
 
-    pub fn entity(&self, index: usize) -> Option<EntityRef> {
-        Some(EntityRef::new(self, self.location(index)?, index))
+    pub fn entity(&self, entity: Entity) -> Option<EntityRef<'_>> {
+        Some(EntityRef::new(self, self.location(entity)?, entity))
     }
 
-    pub fn entity_mut(&mut self, index: usize) -> Option<EntityMut> {
-        Some(EntityMut::new(self, self.location(index)?, index))
+    pub fn entity_mut(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        Some(EntityMut::new(self, self.location(entity)?, entity))
     }
 
-    /// Gets a component from the entity index
-    /// Shorthand for self.entity(index)?.get<T>()?;
-    pub fn get<T: Component>(&self, index: usize) -> Option<&mut T> {
-        let location = self.location(index)?;
-        let archetype = &self.archetypes[location.archetype_index];
-        unsafe { archetype.borrow_component(location.component_index) }
+    /// Gets a component from the entity
+    /// Shorthand for self.entity(entity)?.get<T>()?;
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.entity(entity)?.get()
     }
 
-    pub fn exists(&self, index: usize) -> bool {
-        self.location(index).is_some()
+    /// Gets a component from the entity mutably and marks it as changed
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        let location = self.location(entity)?;
+        let ptr = self.component_ptr_mut(location, entity, ComponentId::of::<T>())?;
+        Some(unsafe { &mut *ptr.cast::<T>() })
     }
 
-    pub fn next_free_index(&mut self) -> usize {
-        self.free_entity_indexs
-            .pop()
-            .unwrap_or(self.entity_locations.len())
+    /// Sets where the components of the type are stored
+    ///
+    /// # Panics
+    /// - An entity already has the component
+    pub fn set_storage<T: Component>(&mut self, storage: StorageType) {
+        self.set_storage_of(TypeInfo::of::<T>(), storage);
+    }
+
+    /// Sets where a runtime defined component is stored
+    ///
+    /// # Panics
+    /// - The component isn't registered or an entity already has it
+    pub fn set_dynamic_storage(&mut self, id: ComponentId, storage: StorageType) {
+        let type_info = self
+            .dynamic_type_info(id)
+            .expect("Component is not registered")
+            .clone();
+        self.set_storage_of(type_info, storage);
+    }
+
+    fn set_storage_of(&mut self, type_info: TypeInfo, storage: StorageType) {
+        let id = type_info.id;
+        let is_used = self
+            .archetypes
+            .iter()
+            .any(|archetype| archetype.has_id(id) && archetype.count() > 0)
+            || self
+                .sparse_sets
+                .get(&id)
+                .is_some_and(|set| !set.entities().is_empty());
+        assert!(
+            !is_used || self.storage_type(id) == storage,
+            "Can't change the storage of {} since entities already have it",
+            type_info.typename
+        );
+
+        // Queries that were cached with the old storage need to be rebuilt
+        if self.storage_type(id) != storage {
+            self.id = WorldId::new();
+        }
+
+        match storage {
+            StorageType::Table => {
+                self.sparse_sets.remove(&id);
+            }
+            StorageType::SparseSet => {
+                self.sparse_sets
+                    .entry(id)
+                    .or_insert_with(|| SparseSet::new(type_info));
+            }
+        }
+    }
+
+    /// Unique id of the world used to check that a [crate::QueryState] belongs to it
+    pub(crate) fn id(&self) -> u64 {
+        self.id.0
+    }
+
+    pub fn storage_type(&self, id: ComponentId) -> StorageType {
+        if self.is_sparse(id) {
+            StorageType::SparseSet
+        } else {
+            StorageType::Table
+        }
+    }
+
+    pub(crate) fn is_sparse(&self, id: ComponentId) -> bool {
+        !self.sparse_sets.is_empty() && self.sparse_sets.contains_key(&id)
+    }
+
+    pub(crate) fn sparse_set(&self, id: ComponentId) -> Option<&SparseSet> {
+        self.sparse_sets.get(&id)
+    }
+
+    /// Allows multiple components to be borrowed mutably at the same time with the borrows checked
+    /// at runtime
+    pub fn cell(&mut self) -> WorldCell<'_> {
+        WorldCell::new(self)
+    }
+
+    /// Registers a component type defined at runtime which is stored as a [DynamicComponent]
+    /// Registering the same schema again returns the existing id
+    ///
+    /// # Panics
+    /// - A component with the same name but different fields was already registered
+    pub fn register_component(&mut self, schema: ComponentSchema) -> ComponentId {
+        self.dynamic_components.register(schema)
+    }
+
+    /// Gets the id of a component registered at runtime from its name
+    pub fn component_id(&self, name: &str) -> Option<ComponentId> {
+        self.dynamic_components.id(name)
+    }
+
+    pub fn component_schema(&self, id: ComponentId) -> Option<&ComponentSchema> {
+        self.dynamic_components.schema(id)
+    }
+
+    /// Iterates through every component registered at runtime
+    pub fn component_schemas(&self) -> impl Iterator<Item = (ComponentId, &ComponentSchema)> {
+        self.dynamic_components.schemas()
+    }
+
+    pub(crate) fn dynamic_type_info(&self, id: ComponentId) -> Option<&TypeInfo> {
+        self.dynamic_components.type_info(id)
     }
 
-    fn location(&self, index: usize) -> Option<EntityLocation> {
-        if *self.valid_entity_locations.get(index)? {
+    /// Gets a runtime defined component from the entity
+    pub fn get_dynamic(&self, entity: Entity, id: ComponentId) -> Option<&DynamicComponent> {
+        self.entity(entity)?.get_dynamic(id)
+    }
+
+    /// Gets a runtime defined component from the entity mutably and marks it as changed
+    pub fn get_dynamic_mut(
+        &mut self,
+        entity: Entity,
+        id: ComponentId,
+    ) -> Option<&mut DynamicComponent> {
+        self.entity_mut(entity)?.into_dynamic_mut(id)
+    }
+
+    /// Iterates through every entity with the runtime defined component
+    pub fn query_dynamic(
+        &self,
+        id: ComponentId,
+    ) -> impl Iterator<Item = (Entity, &DynamicComponent)> {
+        assert!(
+            matches!(id, ComponentId::Dynamic(_)),
+            "{id:?} is not a runtime defined component"
+        );
+        let table = self
+            .archetypes
+            .iter()
+            .filter_map(move |archetype| Some((archetype.get_array(id)?, &archetype.entities[..])));
+        let sparse = self
+            .sparse_sets
+            .get(&id)
+            .map(|set| (&set.dense, set.entities()));
+
+        table.chain(sparse).flat_map(|(array, entities)| {
+            let ptr = array.get_ptr().cast::<DynamicComponent>();
+            let entities = entities.iter().enumerate();
+            entities.map(move |(index, entity)| (*entity, unsafe { &*ptr.add(index) }))
+        })
+    }
+
+    /// Iterates through every entity with the runtime defined component mutably and marks them as
+    /// changed
+    pub fn query_dynamic_mut(
+        &mut self,
+        id: ComponentId,
+    ) -> impl Iterator<Item = (Entity, &mut DynamicComponent)> {
+        assert!(
+            matches!(id, ComponentId::Dynamic(_)),
+            "{id:?} is not a runtime defined component"
+        );
+        let change_tick = self.change_tick;
+        let table = self.archetypes.iter_mut().filter_map(move |archetype| {
+            let entities = &archetype.entities[..];
+            let array = archetype
+                .component_arrays
+                .iter_mut()
+                .find(|array| array.get_id() == id)?;
+            Some((array, entities))
+        });
+        let sparse = self.sparse_sets.get_mut(&id).map(SparseSet::split_mut);
+
+        table.chain(sparse).flat_map(move |(array, entities)| {
+            array
+                .ticks
+                .iter_mut()
+                .for_each(|ticks| ticks.changed = change_tick);
+            let ptr = array.get_ptr().cast::<DynamicComponent>();
+            // SAFETY: Each component is only handed out once
+            let entities = entities.iter().enumerate();
+            entities.map(move |(index, entity)| (*entity, unsafe { &mut *ptr.add(index) }))
+        })
+    }
+
+    /// Gets the hooks of the component type to set them
+    pub fn component_hooks<T: Component>(&mut self) -> &mut ComponentHooks {
+        self.component_hooks_by_id(ComponentId::of::<T>())
+    }
+
+    /// Gets the hooks of the component with the id which could be a Rust type or runtime defined
+    pub fn component_hooks_by_id(&mut self, id: ComponentId) -> &mut ComponentHooks {
+        self.hooks.component_mut(id)
+    }
+
+    /// Adds an observer that is called before any entity is despawned
+    /// The entity and its components can still be accessed in the observer
+    pub fn on_despawn(&mut self, observer: impl Fn(&mut World, Entity) + 'static) {
+        self.hooks.add_despawn_observer(Rc::new(observer));
+    }
+
+    /// Runs the hooks for a component newly added to the entity
+    fn trigger_add(&mut self, entity: Entity, id: ComponentId) {
+        if let Some(hook) = self.hooks.on_add(id) {
+            if self.has_id(entity, id) {
+                hook(self, entity);
+            }
+        }
+        self.trigger_insert(entity, id);
+    }
+
+    /// Runs the hooks for a component added to or replaced in the entity
+    fn trigger_insert(&mut self, entity: Entity, id: ComponentId) {
+        if let Some(hook) = self.hooks.on_insert(id) {
+            if self.has_id(entity, id) {
+                hook(self, entity);
+            }
+        }
+    }
+
+    /// Runs the hooks for a component about to be removed from the entity
+    fn trigger_remove(&mut self, entity: Entity, id: ComponentId) {
+        if let Some(hook) = self.hooks.on_remove(id) {
+            if self.has_id(entity, id) {
+                hook(self, entity);
+            }
+        }
+    }
+
+    fn has_id(&self, entity: Entity, id: ComponentId) -> bool {
+        self.entity(entity).is_some_and(|entity| entity.has_id(id))
+    }
+
+    /// Inserts a resource which only one of each type can exist
+    /// Returns the old resource if one was already inserted
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> Option<R> {
+        self.resources.insert(resource)
+    }
+
+    pub fn resource<R: Resource>(&self) -> Option<&R> {
+        self.resources.get()
+    }
+
+    pub fn resource_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        self.resources.get_mut()
+    }
+
+    /// # Safety
+    /// - Nothing else can access the resource while the reference is alive
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn resource_unchecked_mut<R: Resource>(&self) -> Option<&mut R> {
+        self.resources.get_unchecked_mut()
+    }
+
+    pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+        self.resources.remove()
+    }
+
+    pub fn has_resource<R: Resource>(&self) -> bool {
+        self.resources.contains::<R>()
+    }
+
+    /// Inserts an [Events] resource for E that gets updated by [Self::update_events]
+    /// Does nothing if it was already added
+    pub fn add_event<E: Resource>(&mut self) {
+        if self.has_resource::<Events<E>>() {
+            return;
+        }
+
+        self.insert_resource(Events::<E>::default());
+        self.event_updaters.push(|world| {
+            if let Some(events) = world.resource_mut::<Events<E>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Sends an event, adding the [Events] resource for E if it is missing
+    pub fn send_event<E: Resource>(&mut self, event: E) {
+        self.event_writer().send(event);
+    }
+
+    /// Gets a writer for the events of type E, adding the [Events] resource if it is missing
+    pub fn event_writer<E: Resource>(&mut self) -> EventWriter<'_, E> {
+        self.add_event::<E>();
+        EventWriter::new(self.resource_mut().unwrap())
+    }
+
+    pub fn events<E: Resource>(&self) -> Option<&Events<E>> {
+        self.resource()
+    }
+
+    /// Swaps the buffers of every [Events] resource added with [Self::add_event]
+    /// Called by the engine loop at the end of each frame, events are dropped after two calls
+    pub fn update_events(&mut self) {
+        for i in 0..self.event_updaters.len() {
+            (self.event_updaters[i])(self);
+        }
+    }
+
+    /// Ends the current change detection frame
+    /// [crate::Added] and [crate::Changed] queries will only match components added or changed after this
+    pub fn advance_tick(&mut self) {
+        self.last_change_tick = self.change_tick;
+        self.change_tick += 1;
+    }
+
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// Moves to a new change tick without ending the change detection frame and returns the old one
+    /// Lets changes made after this be told apart from the ones before it using the ticks
+    pub fn increment_change_tick(&mut self) -> u32 {
+        self.change_tick += 1;
+        self.change_tick - 1
+    }
+
+    pub(crate) fn query_ticks(&self) -> QueryTicks {
+        QueryTicks {
+            last_change_tick: self.last_change_tick,
+            change_tick: self.change_tick,
+        }
+    }
+
+    /// Gets the change detection ticks of a component of the entity
+    pub fn component_ticks<T: Component>(&self, entity: Entity) -> Option<ComponentTicks> {
+        let (array, index) = self.component_array(entity, ComponentId::of::<T>())?;
+        Some(array.ticks[index])
+    }
+
+    /// Marks the component as changed for when it was modified without going through a mutable query
+    pub fn set_changed<T: Component>(&mut self, entity: Entity) {
+        if let Some(location) = self.location(entity) {
+            self.component_ptr_mut(location, entity, ComponentId::of::<T>());
+        }
+    }
+
+    /// Returns false if the entity was despawned even if its index has been reused
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.location(entity).is_some()
+    }
+
+    /// Gets the array of the component type the entity is in and the index of the entity in it
+    pub(crate) fn component_array(
+        &self,
+        entity: Entity,
+        id: ComponentId,
+    ) -> Option<(&ComponentArray, usize)> {
+        self.component_array_at(self.location(entity)?, entity, id)
+    }
+
+    /// Same as [Self::component_array] but with the location of the entity already looked up
+    fn component_array_at(
+        &self,
+        location: EntityLocation,
+        entity: Entity,
+        id: ComponentId,
+    ) -> Option<(&ComponentArray, usize)> {
+        match self.sparse_set(id) {
+            Some(set) => Some((&set.dense, set.dense_index(entity)?)),
+            None => {
+                let array = self.archetypes[location.archetype_index].get_array(id)?;
+                Some((array, location.component_index))
+            }
+        }
+    }
+
+    /// Gets a pointer to the component of the entity that can be written to and marks it as changed
+    fn component_ptr_mut(
+        &mut self,
+        location: EntityLocation,
+        entity: Entity,
+        id: ComponentId,
+    ) -> Option<*mut u8> {
+        let change_tick = self.change_tick;
+        let (array, index) = self.component_array_at(location, entity, id)?;
+        // SAFETY: The world is mutably borrowed so nothing else is accessing the array
+        unsafe { (*array.get_ticks_ptr().add(index)).changed = change_tick };
+        Some(array.get_component_ptr(index))
+    }
+
+    /// Reserves the next free entity slot, growing the entity tables if there are none
+    fn alloc_entity(&mut self) -> Entity {
+        let index = self.free_entity_indexs.pop().unwrap_or_else(|| {
+            let index = self.entity_locations.len();
+            self.entity_locations.push(EntityLocation::default());
+            self.entity_generations.push(0);
+            self.max_generations.push(0);
+            self.valid_entity_locations.push(false);
+            index
+        });
+
+        Entity::new(index, self.entity_generations[index])
+    }
+
+    /// Reserves space in the entity tables for the entities that can't reuse a free slot
+    fn reserve_entities(&mut self, additional: usize) {
+        let additional = additional.saturating_sub(self.free_entity_indexs.len());
+        self.entity_locations.reserve(additional);
+        self.entity_generations.reserve(additional);
+        self.max_generations.reserve(additional);
+        self.valid_entity_locations.reserve(additional);
+    }
+
+    /// Reserves the slot of the entity so it can be spawned with the exact same handle
+    fn alloc_entity_at(&mut self, entity: Entity) {
+        let index = entity.index();
+        while self.entity_locations.len() <= index {
+            self.free_entity_indexs.push(self.entity_locations.len());
+            self.entity_locations.push(EntityLocation::default());
+            self.entity_generations.push(0);
+            self.max_generations.push(0);
+            self.valid_entity_locations.push(false);
+        }
+
+        let pos = self
+            .free_entity_indexs
+            .iter()
+            .position(|free| *free == index)
+            .unwrap_or_else(|| panic!("{entity} slot is already in use"));
+        self.free_entity_indexs.remove(pos);
+        self.max_generations[index] = self.max_generations[index].max(entity.generation);
+        self.entity_generations[index] = entity.generation;
+    }
+
+    fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        let index = entity.index();
+        if *self.valid_entity_locations.get(index)?
+            && self.entity_generations[index] == entity.generation
+        {
             self.entity_locations.get(index).copied()
         } else {
             None
@@ -142,115 +898,464 @@ impl World {
             self.archetypes.len() - 1
         })
     }
+
+    /// Gets the archetype with the components of the source archetype and the new one
+    ///
+    /// # Panics
+    /// - The source archetype already has the component
+    fn archetype_with(&mut self, source: usize, type_info: &TypeInfo) -> usize {
+        if let Some(target) = self.archetypes[source].edges.add.get(&type_info.id) {
+            return *target;
+        }
+
+        let mut type_infos = self.archetypes[source].type_infos().to_vec();
+        let pos = type_infos
+            .binary_search(type_info)
+            .expect_err("Tried to add component but it already exists");
+        type_infos.insert(pos, type_info.clone());
+
+        let target = self.archetype_index_from_infos(&type_infos);
+        self.add_edge(source, target, type_info.id);
+        target
+    }
+
+    /// Gets the archetype with the components of the source archetype except for the one with the id
+    ///
+    /// # Panics
+    /// - The source archetype doesn't have the component
+    fn archetype_without(&mut self, source: usize, id: ComponentId) -> usize {
+        if let Some(target) = self.archetypes[source].edges.remove.get(&id) {
+            return *target;
+        }
+
+        let mut type_infos = self.archetypes[source].type_infos().to_vec();
+        let pos = type_infos
+            .iter()
+            .position(|info| info.id == id)
+            .expect("Tried to remove component but it does not exist");
+        type_infos.remove(pos);
+
+        let target = self.archetype_index_from_infos(&type_infos);
+        self.add_edge(target, source, id);
+        target
+    }
+
+    /// Caches the transitions between the archetypes which only differ by the component
+    fn add_edge(&mut self, without: usize, with: usize, id: ComponentId) {
+        self.archetypes[without].edges.add.insert(id, with);
+        self.archetypes[with].edges.remove.insert(id, without);
+    }
+
+    /// Gets statistics about the archetypes of the world for diagnostics
+    pub fn archetype_stats(&self) -> ArchetypeStats {
+        let mut stats = ArchetypeStats {
+            archetypes: self.archetypes.len(),
+            ..Default::default()
+        };
+        for archetype in &self.archetypes {
+            if archetype.count() == 0 {
+                stats.empty_archetypes += 1;
+            }
+            stats.add_edges += archetype.edges.add.len();
+            stats.remove_edges += archetype.edges.remove.len();
+        }
+        stats
+    }
+
+    /// Describes every archetype of the world in creation order for debugging tools
+    pub fn archetype_infos(&self) -> Vec<ArchetypeInfo> {
+        self.archetypes
+            .iter()
+            .enumerate()
+            .map(|(index, archetype)| ArchetypeInfo {
+                index,
+                components: archetype
+                    .type_infos()
+                    .iter()
+                    .map(|info| info.typename)
+                    .collect(),
+                entities: archetype.count(),
+                capacity: archetype.capacity(),
+                bytes: archetype.allocated_bytes(),
+            })
+            .collect()
+    }
+
+    /// Gets the totals of every component type stored in the world sorted by name
+    pub fn component_stats(&self) -> Vec<ComponentStats> {
+        let mut stats: Vec<ComponentStats> = Vec::new();
+        for archetype in &self.archetypes {
+            for array in archetype.component_arrays.iter() {
+                let info = array.type_info();
+                let index = match stats.iter().position(|stats| stats.id == info.id) {
+                    Some(index) => index,
+                    None => {
+                        stats.push(ComponentStats {
+                            id: info.id,
+                            typename: info.typename,
+                            storage: StorageType::Table,
+                            entities: 0,
+                            archetypes: 0,
+                            bytes: 0,
+                        });
+                        stats.len() - 1
+                    }
+                };
+
+                let stats = &mut stats[index];
+                stats.entities += archetype.count();
+                stats.archetypes += 1;
+                stats.bytes += array.allocated_bytes();
+            }
+        }
+
+        stats.extend(self.sparse_sets.values().map(|set| ComponentStats {
+            id: set.id(),
+            typename: set.dense.type_info().typename,
+            storage: StorageType::SparseSet,
+            entities: set.entities().len(),
+            archetypes: 0,
+            bytes: set.allocated_bytes(),
+        }));
+        stats.sort_unstable_by(|a, b| a.typename.cmp(b.typename).then(a.id.cmp(&b.id)));
+        stats
+    }
+
+    /// Gets how many entity slots are used and waiting to be reused
+    pub fn entity_stats(&self) -> EntityStats {
+        EntityStats {
+            alive: self.entity_locations.len() - self.free_entity_indexs.len(),
+            slots: self.entity_locations.len(),
+            free: self.free_entity_indexs.len(),
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
 pub struct EntityRef<'a> {
-    archetype: &'a Archetype,
+    world: &'a World,
     location: EntityLocation,
-    index: usize,
+    entity: Entity,
 }
 
 impl<'a> EntityRef<'a> {
-    fn new(world: &'a World, location: EntityLocation, index: usize) -> Self {
+    fn new(world: &'a World, location: EntityLocation, entity: Entity) -> Self {
         Self {
-            archetype: &world.archetypes[location.archetype_index],
+            world,
             location,
-            index,
+            entity,
         }
     }
 
-    pub fn get<T: Component>(&'a self) -> Option<&'a mut T> {
-        unsafe {
-            self.archetype
-                .borrow_component(self.location.component_index)
-        }
+    fn get_ptr(&self, id: ComponentId) -> Option<*mut u8> {
+        let (array, index) = self
+            .world
+            .component_array_at(self.location, self.entity, id)?;
+        Some(array.get_component_ptr(index))
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&'a T> {
+        let ptr = self.get_ptr(ComponentId::of::<T>())?;
+        Some(unsafe { &*ptr.cast::<T>() })
+    }
+
+    pub fn get_dynamic(&self, id: ComponentId) -> Option<&'a DynamicComponent> {
+        let ComponentId::Dynamic(_) = id else {
+            return None;
+        };
+        let ptr = self.get_ptr(id)?;
+        Some(unsafe { &*ptr.cast::<DynamicComponent>() })
     }
 
     pub fn has<T: Component>(&self) -> bool {
-        self.archetype.has_component::<T>()
+        self.has_id(ComponentId::of::<T>())
+    }
+
+    pub fn has_id(&self, id: ComponentId) -> bool {
+        match self.world.sparse_set(id) {
+            Some(set) => set.contains(self.entity),
+            None => self.world.archetypes[self.location.archetype_index].has_id(id),
+        }
     }
 
-    pub fn index(&self) -> usize {
-        self.index
+    /// Gets a pointer to the component with the id which can be read as the type of the
+    /// [TypeInfo] with the same id, see [Self::type_infos]
+    pub fn get_by_id(&self, id: ComponentId) -> Option<*const u8> {
+        self.get_ptr(id).map(|ptr| ptr.cast_const())
+    }
+
+    /// The type infos of every component the entity has sorted by id
+    /// Lets tools list the components of an entity without knowing their types
+    pub fn type_infos(&self) -> Vec<TypeInfo> {
+        let archetype = &self.world.archetypes[self.location.archetype_index];
+        let mut infos = archetype.type_infos().to_vec();
+        for set in self.world.sparse_sets.values() {
+            if set.contains(self.entity) {
+                infos.push(set.dense.type_info().clone());
+            }
+        }
+        infos.sort_unstable();
+        infos
+    }
+
+    /// The ids of every component the entity has sorted
+    pub fn component_ids(&self) -> Vec<ComponentId> {
+        let archetype = &self.world.archetypes[self.location.archetype_index];
+        let mut ids: Vec<ComponentId> = archetype.type_infos().iter().map(|info| info.id).collect();
+        for set in self.world.sparse_sets.values() {
+            if set.contains(self.entity) {
+                ids.push(set.id());
+            }
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
     }
 }
 
 // Same as entity ref but with add and remove component functions
 pub struct EntityMut<'a> {
-    archetype: &'a mut Archetype,
     location: EntityLocation,
-    index: usize,
+    entity: Entity,
     world: &'a mut World,
 }
 
 impl<'a> EntityMut<'a> {
-    fn new(world: &'a mut World, location: EntityLocation, index: usize) -> Self {
-        // Safety:
-        // This archetype reference is never accessed after world is modified so it is safe to use
-        let archetype =
-            unsafe { &mut (*(world as *mut World)).archetypes[location.archetype_index] };
+    fn new(world: &'a mut World, location: EntityLocation, entity: Entity) -> Self {
         Self {
-            archetype,
             location,
-            index,
+            entity,
             world,
         }
     }
 
-    pub fn get<T: Component>(&'a self) -> Option<&'a mut T> {
-        unsafe {
-            self.archetype
-                .borrow_component(self.location.component_index)
-        }
+    fn as_ref(&self) -> EntityRef<'_> {
+        EntityRef::new(self.world, self.location, self.entity)
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.as_ref().get()
+    }
+
+    /// Gets the component mutably and marks it as changed
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        let ptr =
+            self.world
+                .component_ptr_mut(self.location, self.entity, ComponentId::of::<T>())?;
+        Some(unsafe { &mut *ptr.cast::<T>() })
+    }
+
+    pub fn get_dynamic(&self, id: ComponentId) -> Option<&DynamicComponent> {
+        self.as_ref().get_dynamic(id)
+    }
+
+    /// Gets the runtime defined component mutably and marks it as changed
+    pub fn get_dynamic_mut(&mut self, id: ComponentId) -> Option<&mut DynamicComponent> {
+        let ComponentId::Dynamic(_) = id else {
+            return None;
+        };
+        let ptr = self
+            .world
+            .component_ptr_mut(self.location, self.entity, id)?;
+        Some(unsafe { &mut *ptr.cast::<DynamicComponent>() })
+    }
+
+    /// Same as [Self::get_dynamic_mut] but keeps the borrow of the world
+    fn into_dynamic_mut(mut self, id: ComponentId) -> Option<&'a mut DynamicComponent> {
+        let component = self.get_dynamic_mut(id)? as *mut DynamicComponent;
+        Some(unsafe { &mut *component })
     }
 
     pub fn has<T: Component>(&self) -> bool {
-        self.archetype.has_component::<T>()
+        self.as_ref().has::<T>()
     }
 
-    pub fn index(&self) -> usize {
-        self.index
+    pub fn has_id(&self, id: ComponentId) -> bool {
+        self.as_ref().has_id(id)
+    }
+
+    pub fn type_infos(&self) -> Vec<TypeInfo> {
+        self.as_ref().type_infos()
+    }
+
+    pub fn component_ids(&self) -> Vec<ComponentId> {
+        self.as_ref().component_ids()
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Runs hooks which could move or despawn the entity so its location is looked up again after
+    fn run_hooks(&mut self, func: impl FnOnce(&mut World, Entity)) {
+        if self.world.hooks.is_empty() {
+            return;
+        }
+
+        func(self.world, self.entity);
+        self.location = self
+            .world
+            .location(self.entity)
+            .expect("Entity was despawned by a component hook");
     }
 
     pub fn add<T: Component>(&mut self, component: T) {
         // Make sure component doesn't have it's destructor called
         let component = std::mem::ManuallyDrop::new(component);
+        unsafe { self.add_raw(TypeInfo::of::<T>(), &*component as *const T as *const u8) };
+    }
+
+    /// Adds a runtime defined component
+    ///
+    /// # Panics
+    /// - The id is not registered or the component doesn't match its schema
+    pub fn add_dynamic(&mut self, id: ComponentId, component: DynamicComponent) {
+        let schema = self
+            .world
+            .component_schema(id)
+            .expect("Component is not registered");
+        assert!(
+            schema.matches(&component),
+            "Component does not match the fields of '{}'",
+            schema.name
+        );
+
+        let type_info = self.world.dynamic_type_info(id).unwrap().clone();
+        let component = std::mem::ManuallyDrop::new(component);
+        unsafe {
+            self.add_raw(
+                type_info,
+                &*component as *const DynamicComponent as *const u8,
+            )
+        };
+    }
+
+    /// Adds the runtime defined component or replaces it if the entity already has one
+    pub fn insert_dynamic(&mut self, id: ComponentId, component: DynamicComponent) {
+        let schema = self
+            .world
+            .component_schema(id)
+            .expect("Component is not registered");
+        assert!(
+            schema.matches(&component),
+            "Component does not match the fields of '{}'",
+            schema.name
+        );
+
+        match self.get_dynamic_mut(id) {
+            Some(existing) => {
+                *existing = component;
+                self.run_hooks(|world, entity| world.trigger_insert(entity, id));
+            }
+            None => self.add_dynamic(id, component),
+        }
+    }
+
+    /// # Safety
+    /// - The component must match the type info and must not be used after calling this
+    unsafe fn add_raw(&mut self, new_type_info: TypeInfo, component: *const u8) {
+        let new_id = new_type_info.id;
+        if let Some(set) = self.world.sparse_sets.get_mut(&new_id) {
+            assert!(
+                !set.contains(self.entity),
+                "Tried to add component but it already exists"
+            );
+            set.insert(
+                self.entity,
+                component,
+                ComponentTicks::new(self.world.change_tick),
+            );
+            self.run_hooks(|world, entity| world.trigger_add(entity, new_id));
+            return;
+        }
 
         // Get the new archetype that the entity belongs in with component added
-        let new_type_info = TypeInfo::of::<T>();
-        let mut type_infos = self.archetype.type_infos().to_vec();
-        let pos = type_infos
-            .binary_search(&new_type_info)
-            .expect_err("Tried to add component but it already exists");
-        type_infos.insert(pos, new_type_info);
+        let target = self
+            .world
+            .archetype_with(self.location.archetype_index, &new_type_info);
 
-        self.modify_components(&type_infos, |source_arch, target_arch, source_index| {
+        self.modify_components(target, |source_arch, target_arch, source_index| {
             // Move all the components into the new archetype
             for array in source_arch.component_arrays.iter() {
                 let ptr = array.get_component_ptr(source_index);
                 unsafe {
-                    target_arch.push_component(ptr, array.get_type_id());
+                    target_arch.push_component(ptr, array.get_id());
                 }
             }
 
             // Add the requested component into the new archetype
             unsafe {
-                target_arch.push_component(&*component as *const T as *const u8, TypeId::of::<T>());
+                target_arch.push_component(component, new_id);
             }
         });
+
+        self.run_hooks(|world, entity| world.trigger_add(entity, new_id));
+    }
+
+    /// Adds a copy of the component from a snapshot or replaces it if the entity already has one
+    pub fn insert_snapshot(&mut self, component: &ComponentSnapshot) {
+        let id = component.id();
+        let component = component.clone();
+
+        if let Some(ptr) = self.world.component_ptr_mut(self.location, self.entity, id) {
+            unsafe {
+                (component.type_info().drop)(ptr);
+                let size = component.type_info().layout.size();
+                std::ptr::copy_nonoverlapping(component.as_ptr(), ptr, size);
+            }
+            component.forget();
+            self.run_hooks(|world, entity| world.trigger_insert(entity, id));
+        } else {
+            unsafe { self.add_raw(component.type_info().clone(), component.as_ptr()) };
+            component.forget();
+        }
+    }
+
+    /// Adds the component or replaces it if the entity already has one
+    pub fn insert<T: Component>(&mut self, component: T) {
+        match self.get_mut::<T>() {
+            Some(existing) => {
+                *existing = component;
+                self.run_hooks(|world, entity| {
+                    world.trigger_insert(entity, ComponentId::of::<T>())
+                });
+            }
+            None => self.add(component),
+        }
     }
 
     pub fn remove<T: Component>(&mut self) {
-        // Remove the component from the type infos
-        let mut type_infos = self.archetype.type_infos().to_vec();
-        let pos = type_infos.binary_search(&TypeInfo::of::<T>()).unwrap();
-        type_infos.remove(pos);
+        self.remove_by_id(ComponentId::of::<T>());
+    }
 
-        self.modify_components(&type_infos, |source_arch, target_arch, source_index| {
+    /// Removes the component with the id which could be a Rust type or runtime defined
+    pub fn remove_by_id(&mut self, id: ComponentId) {
+        assert!(
+            self.has_id(id),
+            "Tried to remove component but it does not exist"
+        );
+        self.run_hooks(|world, entity| world.trigger_remove(entity, id));
+        // The hooks could have removed the component themselves
+        if !self.has_id(id) {
+            return;
+        }
+
+        if let Some(set) = self.world.sparse_sets.get_mut(&id) {
+            set.remove(self.entity, true);
+            return;
+        }
+
+        let target = self
+            .world
+            .archetype_without(self.location.archetype_index, id);
+
+        self.modify_components(target, |source_arch, target_arch, source_index| {
             // Move all the components into the new archetype except for the removed component
             for array in source_arch.component_arrays.iter_mut() {
-                if array.get_type_id() == TypeId::of::<T>() {
+                if array.get_id() == id {
                     // Call drop on removed component
                     unsafe {
                         array.drop_component(source_index);
@@ -259,7 +1364,7 @@ impl<'a> EntityMut<'a> {
                     // Put the component into the new archetype
                     let ptr = array.get_component_ptr(source_index);
                     unsafe {
-                        target_arch.push_component(ptr, array.get_type_id());
+                        target_arch.push_component(ptr, array.get_id());
                     }
                 }
             }
@@ -268,11 +1373,9 @@ impl<'a> EntityMut<'a> {
 
     fn modify_components(
         &mut self,
-        new_type_infos: &[TypeInfo],
+        target_arch_index: usize,
         modify_func: impl Fn(&mut Archetype, &mut Archetype, usize),
     ) {
-        let target_arch_index = self.world.archetype_index_from_infos(new_type_infos);
-
         if self.location.archetype_index == target_arch_index {
             return;
         }
@@ -283,19 +1386,26 @@ impl<'a> EntityMut<'a> {
             target_arch_index,
         );
 
-        let target_index = target_arch.new_entity(self.index);
+        let target_index = target_arch.new_entity(self.entity);
         let source_index = self.location.component_index;
         modify_func(source_arch, target_arch, source_index);
 
+        // Keep the ticks of the moved components and mark any new ones as added
+        for array in target_arch.component_arrays.iter_mut() {
+            array.ticks[target_index] = source_arch
+                .get_array(array.get_id())
+                .map(|source| source.ticks[source_index])
+                .unwrap_or(ComponentTicks::new(self.world.change_tick));
+        }
+
         // Remove the old entity
-        let moved_index = source_arch.remove_entity(source_index, false);
-        self.world.entity_locations[moved_index].component_index = source_index;
+        let moved = source_arch.remove_entity(source_index, false);
+        self.world.entity_locations[moved.index()].component_index = source_index;
 
-        // Set the new archetype and location
-        self.archetype = unsafe { &mut *(target_arch as *mut Archetype) };
+        // Set the new location
         self.location.component_index = target_index;
         self.location.archetype_index = target_arch_index;
-        self.world.entity_locations[self.index] = self.location;
+        self.world.entity_locations[self.entity.index()] = self.location;
     }
 }
 
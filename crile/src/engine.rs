@@ -1,8 +1,8 @@
 use std::collections::BTreeMap;
 
 use crate::{
-    AssetManager, Clipboard, Event, EventKind, GraphicsContext, Time, Window, WindowConfig,
-    WindowId,
+    AssetManager, Clipboard, Event, EventKind, GraphicsContext, Schedule, Stage, Time, Window,
+    WindowConfig, WindowId, World,
 };
 pub use winit::event_loop::ActiveEventLoop;
 use winit::platform::wayland::EventLoopBuilderExtWayland;
@@ -18,6 +18,10 @@ pub trait Application {
     fn main_window_config() -> WindowConfig {
         WindowConfig::default()
     }
+    /// The world that the systems of [Engine::schedule] run on every frame
+    fn world(&mut self) -> Option<&mut World> {
+        None
+    }
 }
 
 pub struct Engine {
@@ -25,6 +29,9 @@ pub struct Engine {
     pub time: Time,
     pub asset_manager: AssetManager,
     pub clipboard: Clipboard,
+    /// Systems that the engine loop runs on [Application::world], the startup stage runs once
+    /// after the application is created and the others every frame around its updates
+    pub schedule: Schedule,
     should_exit: bool,
     windows: BTreeMap<WindowId, Window>,
     main_window_id: WindowId,
@@ -73,6 +80,7 @@ impl Engine {
             should_exit: false,
             clipboard: Clipboard::default(),
             asset_manager: AssetManager::default(),
+            schedule: Schedule::default(),
             windows: BTreeMap::from([(winit.id(), Window::new(winit))]),
         }
     }
@@ -92,15 +100,32 @@ impl Engine {
         self.time.update();
         while self.time.should_call_fixed_update() {
             app.fixed_update(self);
+            self.run_stage(app, Stage::FixedUpdate);
         }
 
+        self.run_stage(app, Stage::PreUpdate);
         app.update(self, event_loop);
+        self.run_stage(app, Stage::Update);
+        self.run_stage(app, Stage::PostUpdate);
+        self.run_stage(app, Stage::RenderPrepare);
+
+        // This is the end of the frame so events sent during the previous one are dropped
+        if let Some(world) = app.world() {
+            world.update_events();
+        }
+
         for window in &mut self.windows.values_mut() {
             window.input.clear();
             window.winit.request_redraw();
         }
     }
 
+    fn run_stage(&mut self, app: &mut impl Application, stage: Stage) {
+        if let Some(world) = app.world() {
+            self.schedule.run(stage, world);
+        }
+    }
+
     fn event(&mut self, app: &mut impl Application, event: Event) {
         if let Some(window) = self.windows.get_mut(&event.window_id) {
             window.input.process_event(&event.kind);
@@ -128,7 +153,9 @@ impl<App: Application> Default for EngineRunner<App> {
 impl<App: Application> winit::application::ApplicationHandler<()> for EngineRunner<App> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let mut engine = Engine::new::<App>(event_loop);
-        self.state = Some((App::new(&mut engine), engine));
+        let mut app = App::new(&mut engine);
+        engine.run_stage(&mut app, Stage::Startup);
+        self.state = Some((app, engine));
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
     }
 
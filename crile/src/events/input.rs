@@ -8,6 +8,23 @@ struct InputState<T> {
     just_released: hashbrown::HashSet<T>,
 }
 
+impl<T: Clone> Clone for InputState<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pressed: self.pressed.clone(),
+            just_pressed: self.just_pressed.clone(),
+            just_released: self.just_released.clone(),
+        }
+    }
+
+    // Reuses the allocations of the sets since the input is copied every frame
+    fn clone_from(&mut self, source: &Self) {
+        self.pressed.clone_from(&source.pressed);
+        self.just_pressed.clone_from(&source.just_pressed);
+        self.just_released.clone_from(&source.just_released);
+    }
+}
+
 impl<T: Eq + Hash> Default for InputState<T> {
     fn default() -> Self {
         Self {
@@ -43,6 +60,24 @@ pub struct Input {
     key_modifiers: KeyModifiers,
 }
 
+impl Clone for Input {
+    fn clone(&self) -> Self {
+        Self {
+            key_state: self.key_state.clone(),
+            mouse_state: self.mouse_state.clone(),
+            mouse_position: self.mouse_position,
+            key_modifiers: self.key_modifiers,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.key_state.clone_from(&source.key_state);
+        self.mouse_state.clone_from(&source.mouse_state);
+        self.mouse_position = source.mouse_position;
+        self.key_modifiers = source.key_modifiers;
+    }
+}
+
 impl Input {
     pub fn key_pressed(&self, key_code: KeyCode) -> bool {
         self.key_state.pressed.contains(&key_code)
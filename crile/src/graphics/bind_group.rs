@@ -155,7 +155,7 @@ impl<'a> BindGroupBuilder<'a> {
         })
     }
 
-    pub fn entries(&self) -> &[wgpu::BindGroupEntry] {
+    pub fn entries(&self) -> &[wgpu::BindGroupEntry<'_>] {
         &self.entries[0..self.layout_builder.length]
     }
 }
@@ -24,7 +24,7 @@ pub struct BufferAllocation {
 }
 
 impl BufferAllocation {
-    pub fn as_slice(&self) -> wgpu::BufferSlice {
+    pub fn as_slice(&self) -> wgpu::BufferSlice<'_> {
         self.buffer.slice(self.offset..self.offset + self.size)
     }
 }
@@ -76,7 +76,7 @@ impl Mesh {
             })
     }
 
-    pub fn view(&self) -> MeshView {
+    pub fn view(&self) -> MeshView<'_> {
         MeshView::new(
             self.vertex_buffer.slice(..),
             self.index_buffer.slice(..),
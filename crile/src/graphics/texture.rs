@@ -131,7 +131,7 @@ impl Texture {
         );
     }
 
-    pub fn view(&self) -> TextureView {
+    pub fn view(&self) -> TextureView<'_> {
         TextureView::new(&self.gpu_texture, &self.gpu_view)
     }
 }
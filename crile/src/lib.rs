@@ -1,3 +1,6 @@
+// Lets the Reflect derive refer to crile:: from inside this crate too
+extern crate self as crile;
+
 mod asset;
 mod clipboard;
 mod ecs;
@@ -7,6 +10,7 @@ mod fs;
 mod graphics;
 mod hashmap;
 mod ref_id;
+mod reflect;
 mod scene;
 mod scripting;
 mod time;
@@ -20,6 +24,7 @@ pub use fs::*;
 pub use graphics::*;
 pub use hashmap::*;
 pub use ref_id::RefId;
+pub use reflect::*;
 pub use scene::*;
 pub use scripting::*;
 pub use time::*;
@@ -0,0 +1,336 @@
+use std::{any::Any, path::PathBuf};
+
+use crate::{Color, EntityLink, FieldValue, HierarchyId};
+
+pub use crile_derive::Reflect;
+
+/// Lets the fields of a type be listed, read and written by name at runtime
+///
+/// The scene serializer, the editor inspector and the Lua bindings are all driven by it, so a
+/// component only needs `#[derive(Reflect)]` to work with them. Structs expose their fields
+/// while primitives like numbers, strings and unit enums are read and written as [FieldValue]s.
+/// Fields marked with `#[reflect(skip)]` are hidden, which is meant for runtime only state.
+pub trait Reflect: Any {
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Names of the fields that can be accessed with [Self::field], empty for primitives
+    fn field_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn field(&self, _name: &str) -> Option<&dyn Reflect> {
+        None
+    }
+
+    fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect> {
+        None
+    }
+
+    /// The value of a primitive, None for types with fields
+    fn value(&self) -> Option<FieldValue> {
+        None
+    }
+
+    /// Returns false if the value is the wrong type for this primitive or doesn't fit in it
+    fn set_value(&mut self, _value: FieldValue) -> bool {
+        false
+    }
+
+    /// The names that can be set as the value of an enum
+    fn variants(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether the fields are serialized as a list in order like for vectors and colors
+    fn is_sequence(&self) -> bool {
+        false
+    }
+}
+
+impl dyn Reflect {
+    pub fn downcast_ref<T: Reflect>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Reflect>(&mut self) -> Option<&mut T> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+
+    /// Iterates through the fields along with their names
+    pub fn fields(&self) -> impl Iterator<Item = (&'static str, &dyn Reflect)> {
+        let names = self.field_names().iter();
+        names.filter_map(|name| Some((*name, self.field(name)?)))
+    }
+
+    /// Gets a nested field from a path of field names separated by dots like "translation.x"
+    /// An empty path returns the value itself
+    pub fn path(&self, path: &str) -> Option<&dyn Reflect> {
+        path.split('.')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |value, name| value.field(name))
+    }
+
+    pub fn path_mut(&mut self, path: &str) -> Option<&mut dyn Reflect> {
+        path.split('.')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |value, name| value.field_mut(name))
+    }
+
+    /// Gets the value of the primitive at the path
+    pub fn get_path(&self, path: &str) -> Option<FieldValue> {
+        self.path(path)?.value()
+    }
+
+    /// Sets the primitive at the path, returns false if it doesn't exist or has another type
+    pub fn set_path(&mut self, path: &str, value: FieldValue) -> bool {
+        self.path_mut(path)
+            .is_some_and(|field| field.set_value(value))
+    }
+
+    /// Paths of every primitive inside the value like "translation.x", see [Self::path]
+    pub fn leaf_paths(&self) -> Vec<String> {
+        if self.value().is_some() {
+            return vec![String::new()];
+        }
+
+        let mut paths = Vec::new();
+        for (name, field) in self.fields() {
+            for path in field.leaf_paths() {
+                if path.is_empty() {
+                    paths.push(name.to_owned());
+                } else {
+                    paths.push(format!("{name}.{path}"));
+                }
+            }
+        }
+        paths
+    }
+
+    /// Sets every primitive to the value of the same field in the other value
+    pub fn apply(&mut self, other: &dyn Reflect) {
+        if let Some(value) = other.value() {
+            self.set_value(value);
+            return;
+        }
+
+        for name in other.field_names() {
+            if let Some((field, other)) = self.field_mut(name).zip(other.field(name)) {
+                field.apply(other);
+            }
+        }
+    }
+
+    /// Calls the function with every value of the type inside this value including itself
+    pub fn visit_mut<T: Reflect>(&mut self, f: &mut dyn FnMut(&mut T)) {
+        if let Some(value) = self.downcast_mut::<T>() {
+            f(value);
+            return;
+        }
+
+        for name in self.field_names() {
+            if let Some(field) = self.field_mut(name) {
+                field.visit_mut(f);
+            }
+        }
+    }
+
+    /// Compares the values of every field
+    pub fn reflect_eq(&self, other: &dyn Reflect) -> bool {
+        if self.type_id() != other.type_id() {
+            return false;
+        }
+        match self.value() {
+            Some(value) => other.value() == Some(value),
+            None => self.fields().all(|(name, field)| {
+                other
+                    .field(name)
+                    .is_some_and(|other| field.reflect_eq(other))
+            }),
+        }
+    }
+}
+
+/// Implements Reflect for integer types which also accept floats without a fractional part
+/// Values that don't fit in the type are rejected instead of being truncated
+macro_rules! impl_reflect_int {
+    ($($type: ty),*) => {
+        $(
+            impl Reflect for $type {
+                fn value(&self) -> Option<FieldValue> {
+                    Some(FieldValue::Int(*self as i64))
+                }
+
+                fn set_value(&mut self, value: FieldValue) -> bool {
+                    let value = match value {
+                        FieldValue::Int(value) => value,
+                        // The cast saturates so floats outside the range of i64 are checked first
+                        FieldValue::Float(value)
+                            if value.fract() == 0.
+                                && value >= i64::MIN as f64
+                                && value < i64::MAX as f64 =>
+                        {
+                            value as i64
+                        }
+                        _ => return false,
+                    };
+                    match <$type>::try_from(value) {
+                        Ok(value) => *self = value,
+                        Err(_) => return false,
+                    }
+                    true
+                }
+            }
+        )*
+    };
+}
+
+/// Implements Reflect for float types which also accept integers
+macro_rules! impl_reflect_float {
+    ($($type: ty),*) => {
+        $(
+            impl Reflect for $type {
+                fn value(&self) -> Option<FieldValue> {
+                    Some(FieldValue::Float(*self as f64))
+                }
+
+                fn set_value(&mut self, value: FieldValue) -> bool {
+                    match value {
+                        FieldValue::Int(value) => *self = value as $type,
+                        // Finite values too big for the type would become infinity
+                        FieldValue::Float(value)
+                            if value.is_finite() && !(value as $type).is_finite() =>
+                        {
+                            return false
+                        }
+                        FieldValue::Float(value) => *self = value as $type,
+                        _ => return false,
+                    }
+                    true
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_int!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+impl_reflect_float!(f32, f64);
+
+impl Reflect for bool {
+    fn value(&self) -> Option<FieldValue> {
+        Some(FieldValue::Bool(*self))
+    }
+
+    fn set_value(&mut self, value: FieldValue) -> bool {
+        let FieldValue::Bool(value) = value else {
+            return false;
+        };
+        *self = value;
+        true
+    }
+}
+
+impl Reflect for String {
+    fn value(&self) -> Option<FieldValue> {
+        Some(FieldValue::String(self.clone()))
+    }
+
+    fn set_value(&mut self, value: FieldValue) -> bool {
+        let FieldValue::String(value) = value else {
+            return false;
+        };
+        *self = value;
+        true
+    }
+}
+
+/// An empty string means no path
+impl Reflect for Option<PathBuf> {
+    fn value(&self) -> Option<FieldValue> {
+        let path = self
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned());
+        Some(FieldValue::String(path.unwrap_or_default()))
+    }
+
+    fn set_value(&mut self, value: FieldValue) -> bool {
+        let FieldValue::String(value) = value else {
+            return false;
+        };
+        *self = (!value.is_empty()).then(|| PathBuf::from(value));
+        true
+    }
+}
+
+/// The hierarchy id of the entity where zero means no entity
+impl Reflect for EntityLink {
+    fn value(&self) -> Option<FieldValue> {
+        Some(FieldValue::Int(self.0.map_or(0, |id| id.0 as i64)))
+    }
+
+    fn set_value(&mut self, value: FieldValue) -> bool {
+        let FieldValue::Int(id) = value else {
+            return false;
+        };
+        // Ids that don't fit would point to a different entity
+        let Ok(id) = u32::try_from(id) else {
+            return false;
+        };
+        *self = EntityLink((id != 0).then_some(HierarchyId(id)));
+        true
+    }
+}
+
+/// Implements Reflect for glam vectors with each component as a field
+macro_rules! impl_reflect_vector {
+    ($type: ty, [$($field: ident),*]) => {
+        impl Reflect for $type {
+            fn field_names(&self) -> &'static [&'static str] {
+                &[$(stringify!($field)),*]
+            }
+
+            fn field(&self, name: &str) -> Option<&dyn Reflect> {
+                match name {
+                    $(stringify!($field) => Some(&self.$field),)*
+                    _ => None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+                match name {
+                    $(stringify!($field) => Some(&mut self.$field),)*
+                    _ => None,
+                }
+            }
+
+            fn is_sequence(&self) -> bool {
+                true
+            }
+        }
+    };
+}
+
+impl_reflect_vector!(glam::Vec2, [x, y]);
+impl_reflect_vector!(glam::Vec3, [x, y, z]);
+impl_reflect_vector!(glam::Vec4, [x, y, z, w]);
+
+impl Reflect for Color {
+    fn field_names(&self) -> &'static [&'static str] {
+        &["r", "g", "b", "a"]
+    }
+
+    fn field(&self, name: &str) -> Option<&dyn Reflect> {
+        let index = self.field_names().iter().position(|field| *field == name)?;
+        Some(&self.0[index])
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+        let index = self.field_names().iter().position(|field| *field == name)?;
+        Some(&mut self.0[index])
+    }
+
+    fn is_sequence(&self) -> bool {
+        true
+    }
+}
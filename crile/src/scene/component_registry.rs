@@ -0,0 +1,178 @@
+use hashbrown::HashMap;
+
+use crate::{
+    Archetype, CameraComponent, Component, ComponentId, Entity, MigrateFn, Reflect, Scene,
+    SceneMigration, SceneSerializer, ScriptComponent, SpriteComponent, TransformComponent,
+    TypeInfo, World,
+};
+
+/// Converts the component of the entity into a value scripts can use
+pub type IntoLuaFn = fn(&mlua::Lua, *mut Scene, Entity) -> mlua::Result<mlua::Value>;
+
+/// Pushes a default component into the archetype after letting the callback set its fields
+pub type PushToArchetypeFn = fn(&mut Archetype, &mut dyn FnMut(&mut dyn Reflect));
+
+/// Functions to work with a component type without knowing it at compile time
+/// Fields of the component are accessed through [Reflect] so the serializer, the inspector and
+/// scripts only need these to support it
+#[derive(Clone)]
+pub struct ComponentRegistration {
+    /// Name used in scene files and by scripts like "TransformComponent"
+    pub name: &'static str,
+    /// Name shown in the editor like "Transform"
+    pub pretty_name: &'static str,
+    pub type_info: TypeInfo,
+    pub default: fn() -> Box<dyn Reflect>,
+    pub get: fn(&World, Entity) -> Option<&dyn Reflect>,
+    pub get_mut: fn(&mut World, Entity) -> Option<&mut dyn Reflect>,
+    /// Adds the default component to the entity or replaces it if it already has one
+    pub insert_default: fn(&mut World, Entity),
+    /// [Archetype::new_entity] must have been called before this
+    pub push_to_archetype: PushToArchetypeFn,
+    pub into_lua: IntoLuaFn,
+}
+
+impl ComponentRegistration {
+    pub fn of<T: Component + Reflect>() -> Self {
+        Self {
+            name: crate::last_type_name::<T>(),
+            pretty_name: crate::get_pretty_name::<T>(),
+            type_info: TypeInfo::of::<T>(),
+            default: || Box::new(T::default()),
+            get: |world, entity| world.get::<T>(entity).map(|c| c as &dyn Reflect),
+            get_mut: |world, entity| world.get_mut::<T>(entity).map(|c| c as &mut dyn Reflect),
+            insert_default: |world, entity| {
+                if let Some(mut entity) = world.entity_mut(entity) {
+                    entity.insert(T::default());
+                }
+            },
+            push_to_archetype: |archetype, init| {
+                let mut component = T::default();
+                init(&mut component);
+                let component = std::mem::ManuallyDrop::new(component);
+                unsafe {
+                    archetype.push_component(
+                        &*component as *const T as *const u8,
+                        ComponentId::of::<T>(),
+                    );
+                }
+            },
+            into_lua: crate::scripting::reflect_into_lua::<T>,
+        }
+    }
+
+    /// Replaces how the component is given to scripts, by default only its fields are accessible
+    pub fn with_lua(&mut self, into_lua: IntoLuaFn) -> &mut Self {
+        self.into_lua = into_lua;
+        self
+    }
+
+    pub fn id(&self) -> ComponentId {
+        self.type_info.id
+    }
+}
+
+/// The component types that scenes know about
+///
+/// Games can register their own components with [Self::register] so they can be saved in scene
+/// files, shown in the inspector and used in scripts. The default registry has every component
+/// crile has. Changes to the components that break older scene files can be handled by adding
+/// migrations with [Self::add_migration].
+#[derive(Clone)]
+pub struct ComponentRegistry {
+    registrations: Vec<ComponentRegistration>,
+    name_map: HashMap<&'static str, usize>,
+    migrations: Vec<SceneMigration>,
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register::<TransformComponent>();
+        registry
+            .register::<CameraComponent>()
+            .with_lua(crate::scripting::camera_into_lua);
+        registry.register::<SpriteComponent>();
+        registry.register::<ScriptComponent>();
+        registry
+    }
+}
+
+impl ComponentRegistry {
+    pub fn empty() -> Self {
+        Self {
+            registrations: Vec::new(),
+            name_map: HashMap::new(),
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Registers the component or resets its registration if it was already registered
+    ///
+    /// # Panics
+    /// - Another type with the same name was already registered, since scene files and scripts
+    ///   refer to components by their type name without its module path
+    pub fn register<T: Component + Reflect>(&mut self) -> &mut ComponentRegistration {
+        let registration = ComponentRegistration::of::<T>();
+        let index = match self.name_map.get(registration.name) {
+            Some(&index) => {
+                let existing = &self.registrations[index];
+                assert!(
+                    existing.id() == registration.id(),
+                    "component name '{}' is already used by {}",
+                    registration.name,
+                    existing.type_info.typename
+                );
+                self.registrations[index] = registration;
+                index
+            }
+            None => {
+                self.name_map
+                    .insert(registration.name, self.registrations.len());
+                self.registrations.push(registration);
+                self.registrations.len() - 1
+            }
+        };
+        &mut self.registrations[index]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ComponentRegistration> {
+        let index = *self.name_map.get(name)?;
+        Some(&self.registrations[index])
+    }
+
+    pub fn get_by_id(&self, id: ComponentId) -> Option<&ComponentRegistration> {
+        self.iter().find(|registration| registration.id() == id)
+    }
+
+    /// Adds a migration that upgrades scene files saved before the version
+    /// The version has to be higher than [Self::scene_version] which it then becomes
+    pub fn add_migration(&mut self, version: u32, migrate: MigrateFn) -> &mut Self {
+        assert!(
+            version > self.scene_version(),
+            "migration version {version} has to be higher than {}",
+            self.scene_version()
+        );
+        self.migrations.push(SceneMigration { version, migrate });
+        self
+    }
+
+    /// The migrations ordered by version
+    pub fn migrations(&self) -> &[SceneMigration] {
+        &self.migrations
+    }
+
+    /// The version that scene files are saved with
+    pub fn scene_version(&self) -> u32 {
+        self.migrations
+            .last()
+            .map_or(SceneSerializer::FORMAT_VERSION, |migration| {
+                migration.version
+            })
+    }
+
+    /// Iterates through the registrations in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentRegistration> {
+        self.registrations.iter()
+    }
+}
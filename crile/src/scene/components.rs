@@ -1,17 +1,9 @@
-use serde::{Deserialize, Serialize};
+use crate::{Color, Component, RefId, Reflect, Script, Texture};
 
-use crate::{Color, RefId, Script, Texture};
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(default)]
+#[derive(Reflect, Debug, Clone, Component)]
 pub struct TransformComponent {
-    #[serde(skip_serializing_if = "default")]
     pub translation: glam::Vec3,
-
-    #[serde(skip_serializing_if = "default")]
     pub rotation: glam::Vec3,
-
-    #[serde(skip_serializing_if = "default")]
     pub scale: glam::Vec3,
 }
 
@@ -33,54 +25,70 @@ impl TransformComponent {
             self.translation,
         )
     }
+
+    pub fn from_matrix(matrix: glam::Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation: rotation.to_scaled_axis(),
+            scale,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
-#[serde(default)]
+/// World space transform of an entity which is its transform combined with all its ancestors
+/// Updated by [crate::Scene::propagate_transforms] so it is not saved with the scene
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct GlobalTransform(pub glam::Mat4);
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self(glam::Mat4::IDENTITY)
+    }
+}
+
+impl GlobalTransform {
+    pub fn translation(&self) -> glam::Vec3 {
+        self.0.w_axis.truncate()
+    }
+
+    /// Splits the matrix back into a translation, rotation and scale
+    pub fn to_transform(&self) -> TransformComponent {
+        TransformComponent::from_matrix(self.0)
+    }
+}
+
+#[derive(Reflect, Debug, Default, Clone, Component)]
 pub struct SpriteComponent {
-    #[serde(skip_serializing_if = "default")]
     pub color: Color,
-    #[serde(skip)]
+    #[reflect(skip)]
     pub texture: Option<RefId<Texture>>,
     pub texture_path: AssetPath,
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, PartialEq, Eq, Reflect, Clone)]
 pub enum ProjectionKind {
     Perspective,
     #[default]
     Orthographic,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(default)]
+#[derive(Debug, Reflect, Clone, Component)]
 pub struct CameraComponent {
-    #[serde(skip)]
+    #[reflect(skip)]
     pub viewport_size: glam::Vec2,
 
-    #[serde(skip)]
+    #[reflect(skip)]
     pub projection: glam::Mat4,
 
-    #[serde(skip)]
+    #[reflect(skip)]
     pub view_projection: glam::Mat4,
 
-    #[serde(skip)]
-    pub dirty: bool,
-
-    #[serde(skip_serializing_if = "default")]
     pub near: f32,
-
-    #[serde(skip_serializing_if = "default")]
     pub far: f32,
-
-    #[serde(skip_serializing_if = "default")]
     pub orthographic_zoom: f32,
-
     /// Vertical field-of-view of the camera
-    #[serde(skip_serializing_if = "default")]
     pub perspective_fov: f32,
-
-    #[serde(skip_serializing_if = "default")]
     pub projection_kind: ProjectionKind,
 }
 
@@ -90,7 +98,6 @@ impl Default for CameraComponent {
             viewport_size: Default::default(),
             near: -1.0,
             far: 1.0,
-            dirty: true,
             perspective_fov: 45.,
             orthographic_zoom: 1.,
             projection: glam::Mat4::IDENTITY,
@@ -125,7 +132,6 @@ impl CameraComponent {
         }
 
         self.view_projection = self.projection * transform.inverse();
-        self.dirty = false;
     }
 
     pub fn screen_to_world(&self, screen: glam::Vec2) -> glam::Vec2 {
@@ -140,37 +146,16 @@ impl CameraComponent {
     }
 }
 
-#[derive(Serialize, Default, Deserialize, Clone)]
+#[derive(Reflect, Default, Clone, Component)]
 pub struct ScriptComponent {
-    #[serde(skip)]
+    #[reflect(skip)]
     pub script: Option<RefId<Script>>,
     pub script_path: AssetPath,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
-#[serde(default)]
+#[derive(Reflect, Debug, Default, Clone)]
 pub struct AssetPath {
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<std::path::PathBuf>,
-
-    #[serde(skip)]
+    #[reflect(skip)]
     pub open_picker: bool,
 }
-
-/// Calls a macro with all the components crile has excluding MetaDataComponent
-#[macro_export]
-macro_rules! with_components {
-    ($macro: ident) => {{
-        use $crate::*;
-        $macro!([
-            TransformComponent,
-            CameraComponent,
-            SpriteComponent,
-            ScriptComponent
-        ])
-    }};
-}
-
-fn default<T: Default + PartialEq>(t: &T) -> bool {
-    *t == Default::default()
-}
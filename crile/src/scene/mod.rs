@@ -1,11 +1,17 @@
+mod component_registry;
 mod components;
+mod prefab;
 #[allow(clippy::module_inception)]
 mod scene;
+mod scene_migration;
 mod scene_runner;
 mod scene_serializer;
 
+pub use component_registry::*;
 pub use components::*;
+pub use prefab::*;
 pub use scene::*;
+pub use scene_migration::*;
 pub use scene_runner::*;
 pub use scene_serializer::*;
 
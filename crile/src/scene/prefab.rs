@@ -0,0 +1,383 @@
+use std::path::{Path, PathBuf};
+
+use hashbrown::HashMap;
+
+use crate::{
+    Component, ComponentRegistry, Entity, EntityLink, FieldValue, HierarchyId, HierarchyNode,
+    NoHashHashMap, RefId, Reflect, Scene, SceneSerializer,
+};
+
+/// A subtree of entities saved to a .prefab file that can be instantiated many times
+///
+/// The prefab is stored as a scene where the root is the top of the subtree. Every entity of an
+/// instance remembers the prefab entity it came from with [PrefabInstance] so changes to the
+/// prefab can be brought to its instances with [Scene::sync_prefab].
+pub struct Prefab {
+    pub scene: Scene,
+}
+
+impl Prefab {
+    /// Reads the prefab file keeping the components in the registry
+    /// The registry should be the one of the scene the prefab gets instantiated in since any
+    /// component it doesn't know about is dropped and then removed from instances when syncing
+    pub fn load(path: &Path, registry: ComponentRegistry) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read prefab {path:?}: {err}"))?;
+        let scene = SceneSerializer::deserialize_with_registry(source, registry)
+            .map_err(|err| format!("Failed to load prefab {path:?}: {err}"))?;
+        Ok(Self { scene })
+    }
+
+    /// Copies the entity and its descendants into a new prefab
+    /// Entities that are linked to a prefab through the entity keep their source ids so the
+    /// instance stays linked to the new prefab
+    pub fn from_subtree(scene: &Scene, entity: Entity) -> Self {
+        let mut prefab = Scene::default();
+        prefab.registry = scene.registry.clone();
+
+        let mut ids = NoHashHashMap::default();
+        for other in scene.hierarchy_iter(entity) {
+            let node = scene.get_node(other).unwrap();
+            let id = match scene.world.get::<PrefabInstance>(other) {
+                Some(instance) if scene.prefab_root(other) == Some(entity) => instance.source_id,
+                _ => node.id,
+            };
+            let parent = match other == entity {
+                true => HierarchyId(0),
+                false => ids[&node.parent],
+            };
+
+            let prefab_entity = prefab.world.spawn(());
+            prefab.copy_components(prefab_entity, scene, other);
+            prefab.add_to_hierarchy(HierarchyNode::new(&node.name, id, parent), prefab_entity);
+            ids.insert(node.id, id);
+        }
+
+        let prefab_entities = prefab.hierarchy_iter(Scene::ROOT).collect::<Vec<_>>();
+        for prefab_entity in prefab_entities {
+            prefab.remap_entity_links(prefab_entity, &ids);
+        }
+
+        Self { scene: prefab }
+    }
+}
+
+/// Links an entity to the prefab entity it was instantiated from
+#[derive(Clone, Default, Component)]
+pub struct PrefabInstance {
+    /// Path to the prefab file which is only set on the root of the instance
+    pub prefab_path: Option<PathBuf>,
+    /// The loaded prefab which is only set on the root of the instance
+    pub prefab: Option<RefId<Prefab>>,
+    /// Why the prefab file couldn't be loaded which is only set on the root of the instance
+    pub load_error: Option<String>,
+    /// Hierarchy id of the entity inside the prefab
+    pub source_id: HierarchyId,
+    /// Properties changed in this instance like "TransformComponent.translation.x" which are kept
+    /// when syncing with the prefab, a component name means the component was added and one
+    /// starting with "-" like "-SpriteComponent" means it was removed
+    pub overrides: Vec<String>,
+}
+
+impl PrefabInstance {
+    /// Whether the component was removed from this instance
+    pub fn is_removed(&self, component_name: &str) -> bool {
+        self.overrides
+            .iter()
+            .any(|o| o.strip_prefix('-') == Some(component_name))
+    }
+
+    /// Whether the property or any of its parents was overridden
+    pub fn is_overridden(&self, path: &str) -> bool {
+        self.overrides.iter().any(|o| {
+            path.strip_prefix(o.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+    }
+}
+
+impl Scene {
+    /// Spawns the prefab under the parent with new hierarchy ids and returns the instance root
+    pub fn instantiate_prefab(
+        &mut self,
+        prefab: RefId<Prefab>,
+        prefab_path: PathBuf,
+        parent: Entity,
+    ) -> Entity {
+        let copies = self.spawn_copies(&prefab.scene, Scene::ROOT, parent);
+        for &(source, entity) in &copies {
+            let source_id = prefab.scene.get_node(source).unwrap().id;
+            self.world
+                .entity_mut(entity)
+                .unwrap()
+                .insert(PrefabInstance {
+                    source_id,
+                    ..Default::default()
+                });
+        }
+
+        let root = copies[0].1;
+        let instance = self.world.get_mut::<PrefabInstance>(root).unwrap();
+        instance.prefab_path = Some(prefab_path);
+        instance.prefab = Some(prefab);
+        root
+    }
+
+    /// Makes a prefab from the entity and its descendants which become an instance of it
+    /// Any prefab instances inside the subtree get unlinked from their prefabs
+    pub fn create_prefab(&mut self, entity: Entity, prefab_path: PathBuf) -> RefId<Prefab> {
+        let subtree = self.hierarchy_iter(entity).collect::<Vec<_>>();
+        for &entity in &subtree {
+            let mut entity = self.world.entity_mut(entity).unwrap();
+            if entity.has::<PrefabInstance>() {
+                entity.remove::<PrefabInstance>();
+            }
+        }
+
+        let prefab = RefId::new(Prefab::from_subtree(self, entity));
+        for &entity in &subtree {
+            let source_id = self.get_node(entity).unwrap().id;
+            self.world
+                .entity_mut(entity)
+                .unwrap()
+                .insert(PrefabInstance {
+                    source_id,
+                    ..Default::default()
+                });
+        }
+
+        let instance = self.world.get_mut::<PrefabInstance>(entity).unwrap();
+        instance.prefab_path = Some(prefab_path);
+        instance.prefab = Some(prefab.clone());
+        prefab
+    }
+
+    /// Gets the root of the prefab instance that the entity is inside of
+    pub fn prefab_root(&self, entity: Entity) -> Option<Entity> {
+        std::iter::once(entity)
+            .chain(self.ancestor_iter(entity))
+            .find(|&entity| {
+                self.world
+                    .get::<PrefabInstance>(entity)
+                    .is_some_and(|instance| instance.prefab_path.is_some())
+            })
+    }
+
+    /// Records the properties of the instance that are different from the prefab as overrides
+    pub fn update_prefab_overrides(&mut self, root: Entity) {
+        let Some(prefab) = self.loaded_prefab(root) else {
+            return;
+        };
+
+        let links = self.prefab_links(root);
+        let ids = self.instance_ids(&links);
+        for (source_id, entity) in links {
+            let Some(&source) = prefab.scene.hierachy_id_entity_map.get(&source_id) else {
+                continue;
+            };
+
+            let mut overrides = Vec::new();
+            for registration in self.registry.iter() {
+                let component = (registration.get)(&self.world, entity);
+                let source_component = (registration.get)(&prefab.scene.world, source);
+                match (component, source_component) {
+                    (Some(component), Some(source_component)) => {
+                        for path in source_component.leaf_paths() {
+                            let value = component.get_path(&path);
+                            let source_field = source_component.path(&path).unwrap();
+                            if value.is_some() && value != instance_value(source_field, &ids) {
+                                overrides.push(join_path(registration.name, &path));
+                            }
+                        }
+                    }
+                    (Some(_), None) => overrides.push(registration.name.to_owned()),
+                    (None, Some(_)) => overrides.push(format!("-{}", registration.name)),
+                    (None, None) => (),
+                }
+            }
+
+            let instance = self.world.get_mut::<PrefabInstance>(entity).unwrap();
+            for path in overrides {
+                if !instance.is_overridden(&path) {
+                    instance.overrides.push(path);
+                }
+            }
+        }
+    }
+
+    /// Updates the instance to match its prefab while keeping the overridden properties
+    /// Entities added to the prefab are spawned and the ones removed from it are despawned
+    pub fn sync_prefab(&mut self, root: Entity) {
+        let Some(prefab) = self.loaded_prefab(root) else {
+            return;
+        };
+
+        let mut linked = HashMap::new();
+        linked.insert(prefab.scene.root_node().id, root);
+        for (source_id, entity) in self.prefab_links(root) {
+            if prefab.scene.hierachy_id_entity_map.contains_key(&source_id) {
+                linked.insert(source_id, entity);
+            } else if entity != root && self.world.exists(entity) {
+                self.despawn(entity);
+            }
+        }
+
+        // Prefab entities are visited parents first so new entities always have a parent
+        let mut synced = Vec::new();
+        for source in prefab.scene.hierarchy_iter(Scene::ROOT) {
+            let node = prefab.scene.get_node(source).unwrap();
+            let entity = if source == Scene::ROOT {
+                root
+            } else if let Some(&entity) = linked.get(&node.id) {
+                entity
+            } else {
+                let Some(&parent) = linked.get(&node.parent) else {
+                    continue;
+                };
+                let instance = PrefabInstance {
+                    source_id: node.id,
+                    ..Default::default()
+                };
+                let entity = self.spawn(&node.name, instance, parent);
+                linked.insert(node.id, entity);
+                entity
+            };
+            synced.push((entity, source));
+        }
+
+        // Every entity has to exist before syncing so links between them can be remapped
+        let links = linked.into_iter().collect::<Vec<_>>();
+        let ids = self.instance_ids(&links);
+        for (entity, source) in synced {
+            self.sync_prefab_entity(entity, &prefab.scene, source, &ids);
+        }
+    }
+
+    /// Removes every override of the instance so it matches its prefab again
+    pub fn revert_prefab_overrides(&mut self, root: Entity) {
+        for (_, entity) in self.prefab_links(root) {
+            let instance = self.world.get_mut::<PrefabInstance>(entity).unwrap();
+            instance.overrides.clear();
+        }
+        self.sync_prefab(root);
+    }
+
+    /// Makes a new version of the prefab from the instance including its overrides
+    /// The instance gets linked to the new prefab without any overrides
+    pub fn apply_prefab_overrides(&mut self, root: Entity) -> RefId<Prefab> {
+        let prefab = RefId::new(Prefab::from_subtree(self, root));
+
+        let subtree = self.hierarchy_iter(root).collect::<Vec<_>>();
+        for entity in subtree {
+            let in_instance = self.prefab_root(entity) == Some(root);
+            if let Some(instance) = self.world.get_mut::<PrefabInstance>(entity) {
+                if in_instance {
+                    instance.overrides.clear();
+                }
+            } else {
+                // Entities added to the instance are now part of the prefab
+                let source_id = self.get_node(entity).unwrap().id;
+                self.world
+                    .entity_mut(entity)
+                    .unwrap()
+                    .insert(PrefabInstance {
+                        source_id,
+                        ..Default::default()
+                    });
+            }
+        }
+
+        let instance = self.world.get_mut::<PrefabInstance>(root).unwrap();
+        instance.prefab = Some(prefab.clone());
+        prefab
+    }
+
+    fn loaded_prefab(&self, root: Entity) -> Option<RefId<Prefab>> {
+        self.world.get::<PrefabInstance>(root)?.prefab.clone()
+    }
+
+    /// Gets the entities of the instance with the prefab ids they are linked to
+    fn prefab_links(&self, root: Entity) -> Vec<(HierarchyId, Entity)> {
+        self.hierarchy_iter(root)
+            .filter(|&entity| self.prefab_root(entity) == Some(root))
+            .filter_map(|entity| {
+                let instance = self.world.get::<PrefabInstance>(entity)?;
+                Some((instance.source_id, entity))
+            })
+            .collect()
+    }
+
+    /// Maps the prefab ids to the hierarchy ids of the entities in the instance
+    fn instance_ids(
+        &self,
+        links: &[(HierarchyId, Entity)],
+    ) -> NoHashHashMap<HierarchyId, HierarchyId> {
+        links
+            .iter()
+            .filter_map(|&(source_id, entity)| Some((source_id, self.get_node(entity)?.id)))
+            .collect()
+    }
+
+    /// Sets every property of the entity that isn't overridden to the one in the prefab
+    fn sync_prefab_entity(
+        &mut self,
+        entity: Entity,
+        prefab: &Scene,
+        source: Entity,
+        ids: &NoHashHashMap<HierarchyId, HierarchyId>,
+    ) {
+        let Some(instance) = self.world.get::<PrefabInstance>(entity).cloned() else {
+            return;
+        };
+
+        for registration in self.registry.iter() {
+            let has_component = (registration.get)(&self.world, entity).is_some();
+            let Some(source_component) = (registration.get)(&prefab.world, source) else {
+                if has_component && !instance.is_overridden(registration.name) {
+                    let mut entity = self.world.entity_mut(entity).unwrap();
+                    entity.remove_by_id(registration.id());
+                }
+                continue;
+            };
+
+            if !has_component {
+                if instance.is_removed(registration.name) {
+                    continue;
+                }
+                // Cloning keeps the fields that are not reflected like textures and scripts
+                let component = prefab.world.clone_component(source, registration.id());
+                let mut entity = self.world.entity_mut(entity).unwrap();
+                entity.insert_snapshot(&component.unwrap());
+            }
+
+            let component = (registration.get_mut)(&mut self.world, entity).unwrap();
+            for path in source_component.leaf_paths() {
+                if instance.is_overridden(&join_path(registration.name, &path)) {
+                    continue;
+                }
+                let source_field = source_component.path(&path).unwrap();
+                if let Some(value) = instance_value(source_field, ids) {
+                    component.set_path(&path, value);
+                }
+            }
+        }
+    }
+}
+
+/// The value of the prefab field in the instance where links point to the instance entities
+fn instance_value(
+    field: &dyn Reflect,
+    ids: &NoHashHashMap<HierarchyId, HierarchyId>,
+) -> Option<FieldValue> {
+    match field.downcast_ref::<EntityLink>() {
+        Some(link) => link.remap(ids).value(),
+        None => field.value(),
+    }
+}
+
+fn join_path(component_name: &str, path: &str) -> String {
+    match path.is_empty() {
+        true => component_name.to_owned(),
+        false => format!("{component_name}.{path}"),
+    }
+}
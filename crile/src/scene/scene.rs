@@ -1,8 +1,9 @@
 use rand::Rng;
 
 use crate::{
-    CameraComponent, ComponentTuple, DrawUniform, NoHashHashMap, RefId, RenderInstance, RenderPass,
-    SpriteComponent, Texture, TransformComponent, World,
+    CameraComponent, Commands, Component, ComponentId, ComponentRegistry, ComponentTuple,
+    DrawUniform, Entity, GlobalTransform, NoHashHashMap, PrefabInstance, RefId, RenderInstance,
+    RenderPass, SpriteComponent, Texture, TransformComponent, World,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
@@ -27,51 +28,61 @@ impl HierarchyNode {
     }
 }
 
+/// A reference to another entity of the scene that can be used as a component field
+///
+/// It stores the hierarchy id of the entity so it stays the same after saving and loading, use
+/// [Scene::resolve_link] to get the entity it points to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct EntityLink(pub Option<HierarchyId>);
+
+impl EntityLink {
+    /// Points the link to the mapped id if its id is in the map
+    pub fn remap(self, ids: &NoHashHashMap<HierarchyId, HierarchyId>) -> Self {
+        Self(self.0.map(|id| ids.get(&id).copied().unwrap_or(id)))
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct Scene {
     pub world: World,
+    /// Components that can be saved, inspected and used by scripts
+    pub registry: ComponentRegistry,
     /// Maps entity index (inside world) to hierachy node information
     pub(crate) hierarchy_nodes: Vec<HierarchyNode>,
-    /// Maps a hierarchy id to an entity index
-    pub(crate) hierachy_id_index_map: NoHashHashMap<HierarchyId, usize>,
+    /// Maps a hierarchy id to an entity
+    pub(crate) hierachy_id_entity_map: NoHashHashMap<HierarchyId, Entity>,
     render_instances_map: NoHashHashMap<RefId<Texture>, Vec<RenderInstance>>,
+    /// World change tick of the last [Self::propagate_transforms]
+    transform_tick: u32,
     pub running: bool,
 }
 
 impl Scene {
-    /// Index of the root entity (this will always be zero since it is the first one spawned in)
-    pub const ROOT_INDEX: usize = 0;
+    /// The root entity (this will always be at index zero since it is the first one spawned in)
+    pub const ROOT: Entity = Entity {
+        index: 0,
+        generation: 0,
+    };
 
     /// Creates a scene with a root entity
     pub fn with_root() -> Self {
         let mut scene = Scene::default();
         let id = scene.random_hierarchy_id();
-        scene.world.spawn(());
-        scene.add_to_hierarchy(
-            HierarchyNode::new("Root", id, HierarchyId(0)),
-            Self::ROOT_INDEX,
-        );
+        let root = scene.world.spawn(());
+        scene.add_to_hierarchy(HierarchyNode::new("Root", id, HierarchyId(0)), root);
         scene
     }
 
     // TODO: Render back to front to support transparency
     pub fn render(&mut self, render_pass: &mut RenderPass, view_projection: glam::Mat4) {
+        self.propagate_transforms();
         for instances in self.render_instances_map.values_mut() {
             instances.clear();
         }
 
-        for (index, (transform, sprite)) in
-            self.world.query::<(TransformComponent, SpriteComponent)>()
+        for (_, (global_transform, sprite)) in
+            self.world.query::<(&GlobalTransform, &SpriteComponent)>()
         {
-            // Go through each parent and multiply by their transforms
-            // TODO: a bit inefficient think about caching?
-            let mut global_transform = transform.matrix();
-            for parent_index in self.ancestor_iter(index) {
-                if let Some(transform) = self.world.get::<TransformComponent>(parent_index) {
-                    global_transform = transform.matrix() * global_transform;
-                }
-            }
-
             let texture = sprite
                 .texture
                 .as_ref()
@@ -79,7 +90,7 @@ impl Scene {
             let instances = self.render_instances_map.entry_ref(texture).or_default();
 
             instances.push(RenderInstance {
-                transform: global_transform
+                transform: global_transform.0
                     * glam::Mat4::from_scale(texture.view().size().as_vec2().extend(1.)),
                 color: sprite.color,
             })
@@ -98,8 +109,7 @@ impl Scene {
     }
 
     pub fn set_viewport(&mut self, viewport_size: glam::Vec2) {
-        for (_, (camera,)) in self.world.query_mut::<(CameraComponent,)>() {
-            camera.dirty = true;
+        for (_, (camera,)) in self.world.query_mut::<(&mut CameraComponent,)>() {
             camera.viewport_size = viewport_size;
         }
     }
@@ -109,98 +119,329 @@ impl Scene {
         &mut self,
         name: impl ToString,
         components: T,
-        parent_index: usize,
-    ) -> usize {
-        let index = self.world.spawn(components);
-        let parent_id = self.hierarchy_nodes[parent_index].id;
+        parent: Entity,
+    ) -> Entity {
+        let entity = self.world.spawn(components);
+        let parent_id = self.get_node(parent).expect("Parent does not exist").id;
         let entity_id = self.random_hierarchy_id();
-        self.add_to_hierarchy(HierarchyNode::new(name, entity_id, parent_id), index);
-        index
+        self.add_to_hierarchy(HierarchyNode::new(name, entity_id, parent_id), entity);
+        entity
     }
 
-    pub fn add_to_hierarchy(&mut self, node: HierarchyNode, entity_index: usize) {
-        debug_assert!(
-            self.world.exists(entity_index),
-            "entity {entity_index} does not exist"
-        );
+    pub fn add_to_hierarchy(&mut self, node: HierarchyNode, entity: Entity) {
+        debug_assert!(self.world.exists(entity), "{entity} does not exist");
 
-        if entity_index >= self.hierarchy_nodes.len() {
+        let index = entity.index();
+        if index >= self.hierarchy_nodes.len() {
             self.hierarchy_nodes
-                .resize_with(entity_index + 1, HierarchyNode::default);
+                .resize_with(index + 1, HierarchyNode::default);
         }
 
         let parent_id = node.parent;
         let node_id = node.id;
-        self.hierarchy_nodes[entity_index] = node;
-        self.hierachy_id_index_map.insert(node_id, entity_index);
+        self.hierarchy_nodes[index] = node;
+        self.hierachy_id_entity_map.insert(node_id, entity);
 
-        if entity_index != Self::ROOT_INDEX {
+        if entity != Self::ROOT {
             debug_assert!(
-                self.hierachy_id_index_map.contains_key(&parent_id),
+                self.hierachy_id_entity_map.contains_key(&parent_id),
                 "parent {parent_id:?} does not exist"
             );
-            let parent_node = self.get_node_mut(self.id_to_index(parent_id)).unwrap();
+            let parent_node = self.get_node_mut(self.id_to_entity(parent_id)).unwrap();
             parent_node.children.push(node_id);
         }
     }
 
-    pub fn spawn_from_scene(&mut self, other: &Scene) {
-        for other_index in other.hierarchy_iter(Scene::ROOT_INDEX) {
-            let other_node = other.get_node(other_index).unwrap().clone();
+    /// Spawns copies of every entity of the other scene under the parent with new hierarchy ids
+    /// Returns the copy of the other scene's root
+    pub fn spawn_from_scene(&mut self, other: &Scene, parent: Entity) -> Entity {
+        self.spawn_copies(other, Scene::ROOT, parent)[0].1
+    }
 
-            // TODO: Regen id if conflicts
-            // if self.hierachy_id_index_map.contains_key(&other_node.id) {
-            //     other_node.id = self.random_hierarchy_id();
-            // }
+    /// Copies the entity of the other scene and its descendants under the parent
+    /// Returns pairs of the entities in the other scene and their copies in hierarchy order
+    pub(crate) fn spawn_copies(
+        &mut self,
+        other: &Scene,
+        other_entity: Entity,
+        parent: Entity,
+    ) -> Vec<(Entity, Entity)> {
+        let mut copies = Vec::new();
+        let mut parents = NoHashHashMap::default();
+        let mut ids = NoHashHashMap::default();
+        for other_entity in other.hierarchy_iter(other_entity) {
+            let other_node = other.get_node(other_entity).unwrap();
+            let parent = parents.get(&other_node.parent).copied().unwrap_or(parent);
+
+            let entity = self.spawn(other_node.name.clone(), (), parent);
+            self.copy_components(entity, other, other_entity);
+            parents.insert(other_node.id, entity);
+            ids.insert(other_node.id, self.get_node(entity).unwrap().id);
+            copies.push((other_entity, entity));
+        }
 
-            let new_index = self.world.spawn_from_world(other_index, &other.world);
-            self.add_to_hierarchy(other_node, new_index);
+        // Links between the copied entities have to point to the copies
+        for &(_, entity) in &copies {
+            self.remap_entity_links(entity, &ids);
+        }
+        copies
+    }
+
+    /// Inserts copies of the components from the entity of the other scene
+    /// Components are cloned whole so fields that are not reflected are kept as well
+    pub(crate) fn copy_components(&mut self, entity: Entity, other: &Scene, other_entity: Entity) {
+        // The prefab link and the cached world transform belong to the other scene's hierarchy
+        let skipped = [
+            ComponentId::of::<PrefabInstance>(),
+            ComponentId::of::<GlobalTransform>(),
+        ];
+        let other_ids = other.world.entity(other_entity).unwrap().component_ids();
+        for id in other_ids {
+            if !matches!(id, ComponentId::Type(_)) || skipped.contains(&id) {
+                continue;
+            }
+            let component = other.world.clone_component(other_entity, id).unwrap();
+            let mut entity = self.world.entity_mut(entity).unwrap();
+            entity.insert_snapshot(&component);
+        }
+
+        // Runtime defined components are matched by name since their ids can be different
+        for (other_id, schema) in other.world.component_schemas() {
+            let Some(component) = other.world.get_dynamic(other_entity, other_id) else {
+                continue;
+            };
+            let id = match self.world.component_id(&schema.name) {
+                Some(id) => id,
+                None => self.world.register_component(schema.clone()),
+            };
+            let mut entity = self.world.entity_mut(entity).unwrap();
+            entity.insert_dynamic(id, component.clone());
+        }
+    }
+
+    /// Changes the links inside the components of the entity that point to the keys of the map
+    pub(crate) fn remap_entity_links(
+        &mut self,
+        entity: Entity,
+        ids: &NoHashHashMap<HierarchyId, HierarchyId>,
+    ) {
+        for registration in self.registry.iter() {
+            if let Some(component) = (registration.get_mut)(&mut self.world, entity) {
+                component.visit_mut(&mut |link: &mut EntityLink| *link = link.remap(ids));
+            }
         }
     }
 
     /// Despawns the entity and its children recursively
-    pub fn despawn(&mut self, entity_index: usize) {
-        assert!(
-            entity_index != Self::ROOT_INDEX,
-            "cannot despawn the root entity"
-        );
+    pub fn despawn(&mut self, entity: Entity) {
+        assert!(entity != Self::ROOT, "cannot despawn the root entity");
 
-        let node_id = self
-            .get_node(entity_index)
-            .expect("Entity index does not exist")
-            .id;
-        let parent_index = self
-            .ancestor_iter(entity_index)
+        let node_id = self.get_node(entity).expect("Entity does not exist").id;
+        let parent = self
+            .ancestor_iter(entity)
             .next()
             .expect("Parent was invalid");
 
         // Remove the child from the children array inside the parent
-        let parent_node = &mut self.hierarchy_nodes[parent_index];
+        let parent_node = &mut self.hierarchy_nodes[parent.index()];
         if let Some(pos) = parent_node.children.iter().position(|x| *x == node_id) {
             parent_node.children.remove(pos);
         }
 
-        let to_remove = self.hierarchy_iter(entity_index).collect::<Vec<_>>();
-        for index in to_remove {
-            self.world.despawn(index);
-            let node = &self.hierarchy_nodes[index];
-            self.hierachy_id_index_map.remove(&node.id);
+        let to_remove = self.hierarchy_iter(entity).collect::<Vec<_>>();
+        for entity in to_remove {
+            // Despawn hooks could have already despawned some of the children
+            if self.world.exists(entity) {
+                self.world.despawn(entity);
+            }
+            let node = &self.hierarchy_nodes[entity.index()];
+            self.hierachy_id_entity_map.remove(&node.id);
+        }
+    }
+
+    /// Moves the entity to be the last child of the new parent
+    ///
+    /// If keep_world_transform is true the transform is changed so the entity stays where it is
+    /// in the world, otherwise it moves along with the new parent.
+    pub fn set_parent(&mut self, entity: Entity, new_parent: Entity, keep_world_transform: bool) {
+        assert!(entity != Self::ROOT, "cannot reparent the root entity");
+        assert!(
+            self.is_valid_parent(entity, new_parent),
+            "cannot parent an entity to itself or its decendents"
+        );
+
+        let global_transform = keep_world_transform
+            .then(|| self.global_transform(entity))
+            .flatten();
+
+        let node_id = self.get_node(entity).expect("Entity does not exist").id;
+        let new_parent_id = self.get_node(new_parent).expect("Parent does not exist").id;
+        let old_parent = self.id_to_entity(self.hierarchy_nodes[entity.index()].parent);
+
+        let old_parent_node = &mut self.hierarchy_nodes[old_parent.index()];
+        old_parent_node.children.retain(|id| *id != node_id);
+        self.hierarchy_nodes[new_parent.index()]
+            .children
+            .push(node_id);
+        self.hierarchy_nodes[entity.index()].parent = new_parent_id;
+
+        // The global transforms of the whole subtree depend on the new parent
+        for entity in self.hierarchy_iter(entity).collect::<Vec<_>>() {
+            self.world.set_changed::<TransformComponent>(entity);
+        }
+
+        let has_transform = self.world.get::<TransformComponent>(entity).is_some();
+        if let Some(global_transform) = global_transform.filter(|_| has_transform) {
+            self.set_global_transform(entity, global_transform);
+        }
+    }
+
+    /// Whether the entity can be moved under the new parent without creating a cycle
+    pub fn is_valid_parent(&self, entity: Entity, new_parent: Entity) -> bool {
+        entity != new_parent && !self.ancestor_iter(new_parent).any(|e| e == entity)
+    }
+
+    /// Moves the entity to the position among the children of its parent
+    /// Positions past the last child move it to the end
+    pub fn move_sibling(&mut self, entity: Entity, position: usize) {
+        assert!(entity != Self::ROOT, "cannot move the root entity");
+        let node = self.get_node(entity).expect("Entity does not exist");
+        let node_id = node.id;
+        let parent = self.id_to_entity(node.parent);
+
+        let siblings = &mut self.hierarchy_nodes[parent.index()].children;
+        siblings.retain(|id| *id != node_id);
+        siblings.insert(position.min(siblings.len()), node_id);
+    }
+
+    /// Gets the parent of the entity which is None for the root
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.ancestor_iter(entity).next()
+    }
+
+    /// Updates the [GlobalTransform] of every entity with a [TransformComponent]
+    ///
+    /// Only the subtrees where a transform was changed since the last call are recomputed, which
+    /// is detected with the change ticks of [TransformComponent].
+    pub fn propagate_transforms(&mut self) {
+        let last_tick = self.transform_tick;
+        let mut stack = vec![(Self::ROOT, glam::Mat4::IDENTITY, false)];
+
+        while let Some((entity, parent_matrix, parent_changed)) = stack.pop() {
+            let ticks = self.world.component_ticks::<TransformComponent>(entity);
+            let Some(mut entity_mut) = self.world.entity_mut(entity) else {
+                continue;
+            };
+
+            let mut matrix = parent_matrix;
+            let mut changed = parent_changed;
+            match (ticks, entity_mut.get::<GlobalTransform>().copied()) {
+                // Entities without a transform are positioned at their parent
+                (None, global) => {
+                    // The children were placed relative to the removed transform
+                    if global.is_some() {
+                        entity_mut.remove::<GlobalTransform>();
+                        changed = true;
+                    }
+                }
+                (Some(ticks), Some(global)) if !changed && ticks.changed <= last_tick => {
+                    matrix = global.0;
+                }
+                (Some(_), _) => {
+                    let transform = entity_mut.get::<TransformComponent>().unwrap();
+                    matrix = parent_matrix * transform.matrix();
+                    entity_mut.insert(GlobalTransform(matrix));
+                    changed = true;
+                }
+            }
+
+            let node = &self.hierarchy_nodes[entity.index()];
+            for child in &node.children {
+                stack.push((self.id_to_entity(*child), matrix, changed));
+            }
+        }
+
+        self.transform_tick = self.world.increment_change_tick();
+    }
+
+    /// Gets the world space transform of the entity
+    /// The cached [GlobalTransform] is used unless the entity or one of its ancestors has changed
+    /// since the last [Self::propagate_transforms]
+    pub fn global_transform(&self, entity: Entity) -> Option<GlobalTransform> {
+        self.get_node(entity)?;
+        let chain = std::iter::once(entity)
+            .chain(self.ancestor_iter(entity))
+            .collect::<Vec<_>>();
+
+        // Entities without a transform are positioned at their nearest ancestor that has one
+        if !chain.iter().any(|entity| self.is_transform_stale(*entity)) {
+            let cached = chain
+                .iter()
+                .find_map(|entity| self.world.get::<GlobalTransform>(*entity));
+            return Some(cached.copied().unwrap_or_default());
+        }
+
+        let matrix = chain
+            .iter()
+            .filter_map(|entity| self.world.get::<TransformComponent>(*entity))
+            .fold(glam::Mat4::IDENTITY, |matrix, transform| {
+                transform.matrix() * matrix
+            });
+        Some(GlobalTransform(matrix))
+    }
+
+    /// Sets the transform of the entity so that it ends up at the world space transform
+    /// A [TransformComponent] is added if the entity doesn't have one
+    pub fn set_global_transform(&mut self, entity: Entity, global_transform: GlobalTransform) {
+        let parent = self
+            .ancestor_iter(entity)
+            .next()
+            .and_then(|parent| self.global_transform(parent))
+            .unwrap_or_default();
+
+        let transform = TransformComponent::from_matrix(parent.0.inverse() * global_transform.0);
+        self.world
+            .entity_mut(entity)
+            .expect("Entity does not exist")
+            .insert(transform);
+    }
+
+    /// Whether the transform has changed since the cached [GlobalTransform] was computed
+    fn is_transform_stale(&self, entity: Entity) -> bool {
+        let has_global = self
+            .world
+            .entity(entity)
+            .is_some_and(|entity| entity.has::<GlobalTransform>());
+        match self.world.component_ticks::<TransformComponent>(entity) {
+            Some(ticks) => !has_global || ticks.changed > self.transform_tick,
+            None => has_global,
         }
     }
 
     /// Returns an iterator that returns the entity itself then all its children and all its decendents
-    pub fn hierarchy_iter(&self, entity_index: usize) -> SceneHierarchyIter {
-        SceneHierarchyIter::new(self, entity_index)
+    pub fn hierarchy_iter(&self, entity: Entity) -> SceneHierarchyIter<'_> {
+        SceneHierarchyIter::new(self, entity)
     }
 
     /// Returns an iterator that goes through all the entity parents and all its ancestors
-    pub fn ancestor_iter(&self, entity_index: usize) -> SceneAncestorIter {
-        SceneAncestorIter::new(self, entity_index)
+    pub fn ancestor_iter(&self, entity: Entity) -> SceneAncestorIter<'_> {
+        SceneAncestorIter::new(self, entity)
+    }
+
+    /// Makes a link to the entity that can be stored inside a component
+    pub fn link(&self, entity: Entity) -> EntityLink {
+        EntityLink(self.get_node(entity).map(|node| node.id))
+    }
+
+    /// Gets the entity the link points to if it still exists
+    pub fn resolve_link(&self, link: EntityLink) -> Option<Entity> {
+        self.hierachy_id_entity_map.get(&link.0?).copied()
     }
 
     pub fn random_hierarchy_id(&self) -> HierarchyId {
         let id = HierarchyId(rand::rng().random());
-        if self.hierachy_id_index_map.contains_key(&id) {
+        // Zero is used for no id like the parent of the root
+        if id.0 == 0 || self.hierachy_id_entity_map.contains_key(&id) {
             // Regen id if conflicts
             self.random_hierarchy_id()
         } else {
@@ -208,89 +449,148 @@ impl Scene {
         }
     }
 
-    pub fn id_to_index(&self, id: HierarchyId) -> usize {
+    pub fn id_to_entity(&self, id: HierarchyId) -> Entity {
         *self
-            .hierachy_id_index_map
+            .hierachy_id_entity_map
             .get(&id)
             .expect("Id should exist")
     }
 
-    pub fn get_node(&self, entity_index: usize) -> Option<&HierarchyNode> {
-        if !self.world.exists(entity_index) {
+    pub fn get_node(&self, entity: Entity) -> Option<&HierarchyNode> {
+        if !self.world.exists(entity) {
             return None;
         }
-        self.hierarchy_nodes.get(entity_index)
+        self.hierarchy_nodes.get(entity.index())
     }
 
-    pub fn get_node_mut(&mut self, entity_index: usize) -> Option<&mut HierarchyNode> {
-        if !self.world.exists(entity_index) {
+    pub fn get_node_mut(&mut self, entity: Entity) -> Option<&mut HierarchyNode> {
+        if !self.world.exists(entity) {
             return None;
         }
-        self.hierarchy_nodes.get_mut(entity_index)
+        self.hierarchy_nodes.get_mut(entity.index())
     }
 
     pub fn root_node(&self) -> &HierarchyNode {
-        self.hierarchy_nodes
-            .get(Self::ROOT_INDEX)
-            .expect("Should be a root node")
+        self.get_node(Self::ROOT).expect("Should be a root node")
+    }
+}
+
+/// Commands that keep the scene hierarchy in sync with the world
+/// Entities are identified by their hierarchy id since the entity does not exist until applied
+impl Commands<Scene> {
+    /// Spawns an entity under the parent and returns the hierarchy id it will have
+    pub fn spawn<T: ComponentTuple + 'static>(
+        &mut self,
+        scene: &Scene,
+        name: impl ToString,
+        components: T,
+        parent: Entity,
+    ) -> HierarchyId {
+        let id = scene.random_hierarchy_id();
+        let parent_id = scene.get_node(parent).expect("Parent does not exist").id;
+        let name = name.to_string();
+        self.push(move |scene| {
+            if scene.hierachy_id_entity_map.contains_key(&parent_id) {
+                let entity = scene.world.spawn(components);
+                scene.add_to_hierarchy(HierarchyNode::new(name, id, parent_id), entity);
+            }
+        });
+        id
+    }
+
+    /// Despawns the entity and its children if it still exists when applied
+    pub fn despawn(&mut self, entity: Entity) {
+        self.push(move |scene| {
+            if scene.world.exists(entity) {
+                scene.despawn(entity);
+            }
+        });
+    }
+
+    /// Adds the component to the entity or replaces it if it already has one
+    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) {
+        self.push(move |scene| {
+            if let Some(mut entity) = scene.world.entity_mut(entity) {
+                entity.insert(component);
+            }
+        });
+    }
+
+    pub fn remove<C: Component>(&mut self, entity: Entity) {
+        self.push(move |scene| {
+            if let Some(mut entity) = scene.world.entity_mut(entity) {
+                if entity.has::<C>() {
+                    entity.remove::<C>();
+                }
+            }
+        });
+    }
+
+    /// Skipped if the parent became a descendant of the entity before being applied
+    pub fn set_parent(&mut self, entity: Entity, new_parent: Entity, keep_world_transform: bool) {
+        self.push(move |scene| {
+            if scene.world.exists(entity)
+                && scene.world.exists(new_parent)
+                && scene.is_valid_parent(entity, new_parent)
+            {
+                scene.set_parent(entity, new_parent, keep_world_transform);
+            }
+        });
     }
 }
 
 pub struct SceneHierarchyIter<'a> {
     scene: &'a Scene,
-    next_indexes_stack: Vec<usize>,
+    next_entities_stack: Vec<Entity>,
 }
 
 impl<'a> SceneHierarchyIter<'a> {
-    fn new(scene: &'a Scene, start_index: usize) -> Self {
+    fn new(scene: &'a Scene, start: Entity) -> Self {
         Self {
             scene,
-            next_indexes_stack: vec![start_index],
+            next_entities_stack: vec![start],
         }
     }
 }
 
 impl Iterator for SceneHierarchyIter<'_> {
-    type Item = usize;
+    type Item = Entity;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let index = self.next_indexes_stack.pop()?;
-        let node = self.scene.hierarchy_nodes.get(index)?;
-        self.next_indexes_stack
+        let entity = self.next_entities_stack.pop()?;
+        let node = self.scene.get_node(entity)?;
+        self.next_entities_stack
             .extend(node.children.iter().rev().map(|id| {
-                // Add the children entity indexes
-                self.scene.id_to_index(*id)
+                // Add the children entities
+                self.scene.id_to_entity(*id)
             }));
 
-        Some(index)
+        Some(entity)
     }
 }
 
 pub struct SceneAncestorIter<'a> {
     scene: &'a Scene,
-    next_index: usize,
+    next: Entity,
 }
 
 impl<'a> SceneAncestorIter<'a> {
-    fn new(scene: &'a Scene, start_index: usize) -> Self {
-        Self {
-            scene,
-            next_index: start_index,
-        }
+    fn new(scene: &'a Scene, start: Entity) -> Self {
+        Self { scene, next: start }
     }
 }
 
 impl Iterator for SceneAncestorIter<'_> {
-    type Item = usize;
+    type Item = Entity;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next_index == Scene::ROOT_INDEX {
+        if self.next == Scene::ROOT {
             return None;
         }
 
-        let node = self.scene.hierarchy_nodes.get(self.next_index)?;
-        let parent_index = *self.scene.hierachy_id_index_map.get(&node.parent)?;
-        self.next_index = parent_index;
-        Some(parent_index)
+        let node = self.scene.get_node(self.next)?;
+        let parent = *self.scene.hierachy_id_entity_map.get(&node.parent)?;
+        self.next = parent;
+        Some(parent)
     }
 }
@@ -0,0 +1,62 @@
+/// Upgrades the table of a whole scene file from the previous version
+pub type MigrateFn = fn(&mut toml::Table);
+
+/// A change to the scene file format like a renamed component or field
+///
+/// Scene files store the version they were saved with and every migration with a newer version
+/// is run on the file in order before it gets deserialized, see
+/// [crate::ComponentRegistry::add_migration].
+#[derive(Clone)]
+pub struct SceneMigration {
+    /// The version that the scene is at after this migration
+    pub version: u32,
+    pub migrate: MigrateFn,
+}
+
+/// Iterates through the tables of every entity in the scene file
+pub fn entity_tables_mut(scene: &mut toml::Table) -> impl Iterator<Item = &mut toml::Table> {
+    scene
+        .get_mut("entity")
+        .and_then(|entities| entities.as_array_mut())
+        .into_iter()
+        .flatten()
+        .filter_map(|entity| entity.as_table_mut())
+}
+
+/// Calls the function with the value of the component in every entity that has it
+pub fn migrate_component(
+    scene: &mut toml::Table,
+    component_name: &str,
+    mut migrate: impl FnMut(&mut toml::Value),
+) {
+    for entity in entity_tables_mut(scene) {
+        if let Some(component) = entity.get_mut(component_name) {
+            migrate(component);
+        }
+    }
+}
+
+pub fn rename_component(scene: &mut toml::Table, old_name: &str, new_name: &str) {
+    for entity in entity_tables_mut(scene) {
+        if let Some(component) = entity.remove(old_name) {
+            entity.insert(new_name.to_owned(), component);
+        }
+    }
+}
+
+/// Renames the field at the path like "texture_path.path" to the new name keeping it in the same
+/// table
+pub fn rename_field(scene: &mut toml::Table, component_name: &str, path: &str, new_name: &str) {
+    let (parent_path, old_name) = path.rsplit_once('.').unwrap_or(("", path));
+    migrate_component(scene, component_name, |component| {
+        let parent = parent_path
+            .split('.')
+            .filter(|name| !name.is_empty())
+            .try_fold(component, |value, name| value.get_mut(name));
+        if let Some(table) = parent.and_then(|parent| parent.as_table_mut()) {
+            if let Some(value) = table.remove(old_name) {
+                table.insert(new_name.to_owned(), value);
+            }
+        }
+    });
+}
@@ -1,52 +1,125 @@
-use crate::{CameraComponent, RenderPass, ScriptComponent, ScriptingEngine, TransformComponent};
+use crate::{
+    CameraComponent, Changed, Engine, GlobalTransform, Or, RenderPass, Resource, Schedule,
+    ScriptComponent, ScriptingEngine, Stage, System, SystemContext, WindowId, World,
+};
 
 pub struct SceneRunner {
     scripting: ScriptingEngine,
+    /// The window the scene is running in which input is read from
+    window_id: WindowId,
+    /// Rust systems that run alongside the scripts
+    pub schedule: Schedule,
 }
 
 impl SceneRunner {
-    pub fn new(scripting: ScriptingEngine) -> Self {
-        SceneRunner { scripting }
+    pub fn new(scripting: ScriptingEngine, window_id: WindowId) -> Self {
+        let mut schedule = Schedule::default();
+        schedule.add_system(
+            Stage::RenderPrepare,
+            System::new("update_cameras", update_cameras)
+                .reads::<GlobalTransform>()
+                .writes::<CameraComponent>(),
+        );
+
+        SceneRunner {
+            scripting,
+            window_id,
+            schedule,
+        }
     }
 
-    pub fn start(&mut self) -> mlua::Result<()> {
+    pub fn start(&mut self, engine: &Engine) -> mlua::Result<()> {
         self.scripting.setup()?;
+        self.sync_resources(engine)?;
 
         let scene = unsafe { &mut *self.scripting.scene };
+        self.schedule.run(Stage::Startup, &mut scene.world);
 
-        for (index, (script,)) in scene.world.query::<(ScriptComponent,)>() {
+        for (entity, (script,)) in scene.world.query::<(&ScriptComponent,)>() {
             if let Some(script) = &script.script {
-                self.scripting.run(index, script)?;
+                self.scripting.run(entity, script)?;
             }
         }
 
+        self.scripting.apply_commands();
         Ok(())
     }
 
-    pub fn update(&mut self) -> mlua::Result<()> {
-        self.scripting.call_signal("MainEvents.Update")
+    pub fn update(&mut self, engine: &Engine) -> mlua::Result<()> {
+        self.sync_resources(engine)?;
+        let scene = unsafe { &mut *self.scripting.scene };
+
+        self.schedule.run(Stage::PreUpdate, &mut scene.world);
+        let result = self
+            .scripting
+            .dispatch_events()
+            .and_then(|_| self.scripting.call_signal("MainEvents.Update"));
+        self.scripting.apply_commands();
+        result?;
+
+        self.schedule.run(Stage::Update, &mut scene.world);
+        self.schedule.run(Stage::PostUpdate, &mut scene.world);
+        Ok(())
     }
 
-    pub fn fixed_update(&mut self) -> mlua::Result<()> {
-        self.scripting.call_signal("MainEvents.FixedUpdate")
+    pub fn fixed_update(&mut self, engine: &Engine) -> mlua::Result<()> {
+        self.sync_resources(engine)?;
+        let scene = unsafe { &mut *self.scripting.scene };
+
+        let result = self.scripting.call_signal("MainEvents.FixedUpdate");
+        self.scripting.apply_commands();
+        result?;
+
+        self.schedule.run(Stage::FixedUpdate, &mut scene.world);
+        Ok(())
     }
 
     pub fn render(&mut self, render_pass: &mut RenderPass) {
         let scene = unsafe { &mut *self.scripting.scene };
+        scene.propagate_transforms();
+        self.schedule.run(Stage::RenderPrepare, &mut scene.world);
 
-        if let Some((_, (camera_transform, camera))) = scene
-            .world
-            .query_mut::<(TransformComponent, CameraComponent)>()
-            .next()
-        {
-            if camera.dirty {
-                camera.update_projection(camera_transform.matrix());
-            }
-
+        if let Some((_, (camera,))) = scene.world.query::<(&CameraComponent,)>().next() {
             let view_projection = camera.view_projection;
             scene.render(render_pass, view_projection);
         }
+
+        // Rendering is the end of the frame so start tracking changes for the next one
+        // Events are updated by the engine loop since it knows when the frame ends
+        scene.world.advance_tick();
     }
 
     pub fn stop(&mut self) {}
+
+    /// Copies the engine state that scripts can read into the world resources
+    fn sync_resources(&mut self, engine: &Engine) -> mlua::Result<()> {
+        let scene = unsafe { &mut *self.scripting.scene };
+        let window = engine
+            .get_window(self.window_id)
+            .ok_or(mlua::Error::runtime("Window ID invalid"))?;
+
+        sync_resource(&mut scene.world, &window.input);
+        sync_resource(&mut scene.world, &engine.time);
+        Ok(())
+    }
+}
+
+/// Updates the resource in place so its allocations are reused or inserts it the first time
+fn sync_resource<R: Resource + Clone>(world: &mut World, value: &R) {
+    match world.resource_mut::<R>() {
+        Some(resource) => resource.clone_from(value),
+        None => {
+            world.insert_resource(value.clone());
+        }
+    }
+}
+
+/// Recalculates the view projection of cameras that moved or had their settings changed
+fn update_cameras(context: &mut SystemContext) {
+    type CameraChanged = Or<Changed<GlobalTransform>, Changed<CameraComponent>>;
+    for (_, (transform, camera, ())) in
+        context.query_mut::<(&GlobalTransform, &mut CameraComponent, CameraChanged)>()
+    {
+        camera.update_projection(transform.0);
+    }
 }
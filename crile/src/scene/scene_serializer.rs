@@ -1,91 +1,180 @@
-use std::any::TypeId;
-
 use serde::{de::Error, Deserialize, Serialize};
 
 use crate::{
-    with_components, Archetype, Component, EntityRef, HierarchyId, HierarchyNode, Scene, TypeInfo,
+    ComponentRegistry, ComponentSchema, DynamicComponent, FieldValue, HierarchyId, HierarchyNode,
+    PrefabInstance, Reflect, Scene,
 };
 
+/// How values that can't be deserialized are handled
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum DeserializeMode {
+    /// Log the values and use the defaults instead
+    #[default]
+    Lenient,
+    /// Fail with an error that lists every value that would be lost
+    Strict,
+}
+
 #[derive(Default, Deserialize, Serialize)]
 struct SerializedScene {
+    /// Version the scene was saved with, files from before versioning are version 0
+    #[serde(default)]
+    version: u32,
+    /// Components defined at runtime which need to be registered before loading the entities
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    component: Vec<ComponentSchema>,
     entity: Vec<toml::Table>,
 }
 
 pub struct SceneSerializer;
 
 impl SceneSerializer {
+    /// Version of the scene format crile writes, games can go above it by adding migrations
+    pub const FORMAT_VERSION: u32 = 1;
+
     pub fn serialize(scene: &Scene) -> Result<String, toml::ser::Error> {
-        let mut output = SerializedScene::default();
+        let mut output = SerializedScene {
+            version: scene.registry.scene_version(),
+            component: scene
+                .world
+                .component_schemas()
+                .map(|(_, schema)| schema.clone())
+                .collect(),
+            ..Default::default()
+        };
 
-        for index in scene.hierarchy_iter(Scene::ROOT_INDEX) {
+        for entity in scene.hierarchy_iter(Scene::ROOT) {
             let mut table = toml::Table::new();
-            let node = scene.get_node(index).unwrap();
+            let node = scene.get_node(entity).unwrap();
             table.insert("id".to_owned(), toml::Value::Integer(node.id.0 as i64));
             table.insert("name".to_owned(), toml::Value::String(node.name.clone()));
 
-            if index != Scene::ROOT_INDEX {
+            if entity != Scene::ROOT {
                 table.insert(
                     "parent".to_owned(),
                     toml::Value::Integer(node.parent.0 as i64),
                 );
             }
 
-            let entity = scene.world.entity(index).unwrap();
-            macro_rules! serialize_components {
-                ( [$($component: ty),*]) => {{
-                    $( serialize_component::<$component>(&mut table, entity)?; )*
-                }};
+            for registration in scene.registry.iter() {
+                if let Some(component) = (registration.get)(&scene.world, entity) {
+                    let default = (registration.default)();
+                    let serialized = reflect_to_toml(component, &*default);
+                    table.insert(registration.name.into(), serialized);
+                }
+            }
+
+            if let Some(instance) = scene.world.get::<PrefabInstance>(entity) {
+                table.insert("prefab".to_owned(), serialize_prefab_instance(instance));
+            }
+
+            let entity = scene.world.entity(entity).unwrap();
+            for (id, schema) in scene.world.component_schemas() {
+                if let Some(component) = entity.get_dynamic(id) {
+                    table.insert(
+                        schema.name.clone(),
+                        serialize_dynamic_component(schema, component),
+                    );
+                }
             }
 
-            with_components!(serialize_components);
             output.entity.push(table);
         }
 
         toml::to_string(&output)
     }
 
+    /// Deserializes a scene that only has the components crile has
     pub fn deserialize(source: String) -> Result<Scene, toml::de::Error> {
+        Self::deserialize_with_registry(source, ComponentRegistry::default())
+    }
+
+    /// Deserializes a scene which can have any of the components in the registry
+    pub fn deserialize_with_registry(
+        source: String,
+        registry: ComponentRegistry,
+    ) -> Result<Scene, toml::de::Error> {
+        Self::deserialize_with_mode(source, registry, DeserializeMode::Lenient)
+    }
+
+    /// Deserializes a scene after upgrading it with the migrations of the registry
+    pub fn deserialize_with_mode(
+        source: String,
+        registry: ComponentRegistry,
+        mode: DeserializeMode,
+    ) -> Result<Scene, toml::de::Error> {
+        let mut table = toml::from_str::<toml::Table>(&source)?;
+        Self::migrate(&mut table, &registry, mode)?;
+
         let mut scene = Scene::default();
-        let output = toml::from_str::<SerializedScene>(&source)?;
+        scene.registry = registry;
+        let output = toml::Value::Table(table).try_into::<SerializedScene>()?;
+        // Values that can't be deserialized which are either logged or returned as an error
+        let mut issues = Vec::new();
+
+        for schema in output.component {
+            scene.world.register_component(schema);
+        }
 
         for entity_table in output.entity {
             let mut type_infos = Vec::new();
+            let mut dynamic_components = Vec::new();
+
+            let id = get_value::<u32>(&entity_table, "id")?;
+            let name = get_value::<String>(&entity_table, "name")?;
 
             for key in entity_table.keys() {
-                macro_rules! add_component_types {
-                    ( [$($component: ty),*]) => {{
-                        $( add_component_type::<$component>(&mut type_infos, key); )*
-                    }};
+                if let Some(registration) = scene.registry.get(key) {
+                    type_infos.push(registration.type_info.clone());
+                } else if let Some(id) = scene.world.component_id(key) {
+                    let schema = scene.world.component_schema(id).unwrap();
+                    let path = format!("{name}.{key}");
+                    let value = &entity_table[key];
+                    let component =
+                        deserialize_dynamic_component(schema, value, &path, &mut issues);
+                    type_infos.push(scene.world.dynamic_type_info(id).unwrap().clone());
+                    dynamic_components.push((id, component));
+                } else if !["id", "name", "parent", "prefab"].contains(&key.as_str()) {
+                    issues.push(format!("{name}.{key}: component does not exist"));
                 }
-
-                with_components!(add_component_types);
             }
 
             type_infos.sort_unstable();
 
-            let id = get_value::<u32>(&entity_table, "id")?;
-            let name = get_value::<String>(&entity_table, "name")?;
-
-            let index = scene.world.spawn_raw(&type_infos, |archetype| {
+            let registry = &scene.registry;
+            let entity = scene.world.spawn_raw(&type_infos, |archetype| {
                 for (key, value) in &entity_table {
-                    macro_rules! deserialize_components {
-                        ( [$($component: ty),*]) => {{
-                            $( deserialize_component::<$component>(key, value, archetype); )*
-                        }};
+                    if let Some(registration) = registry.get(key) {
+                        let path = format!("{name}.{key}");
+                        (registration.push_to_archetype)(archetype, &mut |component| {
+                            apply_toml(component, value, &path, &mut issues)
+                        });
                     }
+                }
 
-                    with_components!(deserialize_components);
+                for (id, component) in &dynamic_components {
+                    unsafe {
+                        archetype.push_component_cloned(
+                            component as *const DynamicComponent as *const u8,
+                            *id,
+                        );
+                    }
                 }
             });
 
+            if let Some(value) = entity_table.get("prefab") {
+                let instance = deserialize_prefab_instance(value)?;
+                scene.world.entity_mut(entity).unwrap().add(instance);
+            }
+
             if let Ok(parent_id) = get_value::<u32>(&entity_table, "parent") {
                 scene.add_to_hierarchy(
                     HierarchyNode::new(name, HierarchyId(id), HierarchyId(parent_id)),
-                    index,
+                    entity,
                 );
             } else {
                 // Doesn't have a parent then must be the root
-                if index != Scene::ROOT_INDEX {
+                if entity != Scene::ROOT {
                     return Err(toml::de::Error::custom(format!(
                         "Entity '{name}' listed without parents but was not the first entity",
                     )));
@@ -93,7 +182,7 @@ impl SceneSerializer {
 
                 scene.add_to_hierarchy(
                     HierarchyNode::new(name, HierarchyId(id), HierarchyId(0)),
-                    index,
+                    entity,
                 );
             }
         }
@@ -102,49 +191,206 @@ impl SceneSerializer {
             return Err(toml::de::Error::custom("scene was empty"));
         }
 
+        if mode == DeserializeMode::Strict && !issues.is_empty() {
+            return Err(toml::de::Error::custom(format!(
+                "values would be lost:\n{}",
+                issues.join("\n")
+            )));
+        }
+        for issue in issues {
+            log::error!("Failed to deserialize {issue}");
+        }
+
         Ok(scene)
     }
+
+    /// Runs the migrations newer than the version of the scene file
+    fn migrate(
+        table: &mut toml::Table,
+        registry: &ComponentRegistry,
+        mode: DeserializeMode,
+    ) -> Result<(), toml::de::Error> {
+        let version = match table.get("version") {
+            Some(version) => version.clone().try_into::<u32>()?,
+            None => 0,
+        };
+
+        let newest_version = registry.scene_version();
+        if version > newest_version {
+            let message = format!("scene version {version} is newer than {newest_version}");
+            match mode {
+                DeserializeMode::Strict => return Err(toml::de::Error::custom(message)),
+                DeserializeMode::Lenient => log::warn!("{message} so it might not load correctly"),
+            }
+        }
+
+        for migration in registry.migrations() {
+            if migration.version > version {
+                (migration.migrate)(table);
+            }
+        }
+        Ok(())
+    }
 }
 
-fn serialize_component<T: Component + serde::Serialize>(
-    table: &mut toml::Table,
-    entity: EntityRef,
-) -> Result<(), toml::ser::Error> {
-    let type_name = crate::last_type_name::<T>();
-    if let Some(component) = entity.get::<T>() {
-        let serialized = toml::Value::try_from(component)?;
-        table.insert(type_name.into(), serialized);
+/// Converts the reflected fields into a table leaving out the ones that are the same as default
+/// Vectors and colors are written as arrays with every field
+fn reflect_to_toml(value: &dyn Reflect, default: &dyn Reflect) -> toml::Value {
+    if let Some(value) = value.value() {
+        return field_value_to_toml(value);
     }
 
-    Ok(())
+    if value.is_sequence() {
+        let array = value
+            .fields()
+            .map(|(_, field)| reflect_to_toml(field, field))
+            .collect();
+        return toml::Value::Array(array);
+    }
+
+    let table = value
+        .fields()
+        .filter_map(|(name, field)| {
+            let default = default.field(name)?;
+            (!field.reflect_eq(default)).then(|| (name.to_owned(), reflect_to_toml(field, default)))
+        })
+        .collect();
+    toml::Value::Table(table)
 }
 
-fn add_component_type<T: Component>(type_infos: &mut Vec<TypeInfo>, key: &str) {
-    let type_name = crate::last_type_name::<T>();
-    if key == type_name {
-        type_infos.push(TypeInfo::of::<T>());
+/// Sets the reflected fields from the toml value, adding the ones that don't match to the issues
+/// Structs also accept arrays with their fields in order
+fn apply_toml(target: &mut dyn Reflect, value: &toml::Value, path: &str, issues: &mut Vec<String>) {
+    if target.value().is_some() {
+        let is_set = toml_to_field_value(value).is_some_and(|value| target.set_value(value));
+        if !is_set {
+            issues.push(format!(
+                "{path}: {value} has the wrong type or is out of range"
+            ));
+        }
+        return;
+    }
+
+    match value {
+        toml::Value::Table(table) => {
+            for (name, value) in table {
+                let path = format!("{path}.{name}");
+                match target.field_mut(name) {
+                    Some(field) => apply_toml(field, value, &path, issues),
+                    None => issues.push(format!("{path}: field does not exist")),
+                }
+            }
+        }
+        toml::Value::Array(array) if array.len() == target.field_names().len() => {
+            for (name, value) in target.field_names().iter().zip(array) {
+                let path = format!("{path}.{name}");
+                apply_toml(target.field_mut(name).unwrap(), value, &path, issues);
+            }
+        }
+        _ => issues.push(format!("{path}: expected a table")),
     }
 }
 
-fn deserialize_component<T: Component + for<'a> serde::Deserialize<'a>>(
-    key: &str,
+fn field_value_to_toml(value: FieldValue) -> toml::Value {
+    match value {
+        FieldValue::Bool(value) => toml::Value::Boolean(value),
+        FieldValue::Int(value) => toml::Value::Integer(value),
+        FieldValue::Float(value) => toml::Value::Float(value),
+        FieldValue::String(value) => toml::Value::String(value),
+    }
+}
+
+fn toml_to_field_value(value: &toml::Value) -> Option<FieldValue> {
+    match value {
+        toml::Value::Boolean(value) => Some(FieldValue::Bool(*value)),
+        toml::Value::Integer(value) => Some(FieldValue::Int(*value)),
+        toml::Value::Float(value) => Some(FieldValue::Float(*value)),
+        toml::Value::String(value) => Some(FieldValue::String(value.clone())),
+        _ => None,
+    }
+}
+
+fn serialize_dynamic_component(
+    schema: &ComponentSchema,
+    component: &DynamicComponent,
+) -> toml::Value {
+    let table = schema
+        .fields
+        .iter()
+        .zip(component.values())
+        .map(|(field, value)| (field.name.clone(), field_value_to_toml(value.clone())))
+        .collect();
+
+    toml::Value::Table(table)
+}
+
+fn deserialize_dynamic_component(
+    schema: &ComponentSchema,
     value: &toml::Value,
-    archetype: &mut Archetype,
-) {
-    let type_name = crate::last_type_name::<T>();
-    if key == type_name {
-        let component = value
-            .clone()
-            .try_into()
-            .inspect_err(|err| log::error!("Failed to deserialize {type_name}: {err}"))
-            .unwrap_or_default();
-        let component = std::mem::ManuallyDrop::new(component);
-
-        unsafe {
-            archetype
-                .push_component_cloned(&*component as *const T as *const u8, TypeId::of::<T>());
+    path: &str,
+    issues: &mut Vec<String>,
+) -> DynamicComponent {
+    let mut component = schema.default_component();
+    let Some(table) = value.as_table() else {
+        issues.push(format!("{path}: expected a table"));
+        return component;
+    };
+
+    for (key, value) in table {
+        let value = toml_to_field_value(value);
+        let is_set = schema
+            .field_index(key)
+            .zip(value)
+            .is_some_and(|(index, value)| component.set(index, value));
+        if !is_set {
+            issues.push(format!(
+                "{path}.{key}: field does not exist or has the wrong type"
+            ));
         }
     }
+
+    component
+}
+
+/// Only the link is saved, the prefab itself gets loaded from the path by the editor
+fn serialize_prefab_instance(instance: &PrefabInstance) -> toml::Value {
+    let mut table = toml::Table::new();
+    if let Some(path) = &instance.prefab_path {
+        let path = path.to_string_lossy().into_owned();
+        table.insert("path".to_owned(), toml::Value::String(path));
+    }
+    table.insert(
+        "source".to_owned(),
+        toml::Value::Integer(instance.source_id.0 as i64),
+    );
+    if !instance.overrides.is_empty() {
+        let overrides = instance.overrides.iter().cloned().map(toml::Value::String);
+        table.insert(
+            "overrides".to_owned(),
+            toml::Value::Array(overrides.collect()),
+        );
+    }
+    toml::Value::Table(table)
+}
+
+fn deserialize_prefab_instance(value: &toml::Value) -> Result<PrefabInstance, toml::de::Error> {
+    let table = value
+        .as_table()
+        .ok_or_else(|| toml::de::Error::custom("prefab must be a table"))?;
+
+    Ok(PrefabInstance {
+        prefab_path: table
+            .get("path")
+            .and_then(|path| path.as_str())
+            .map(Into::into),
+        prefab: None,
+        load_error: None,
+        source_id: HierarchyId(get_value::<u32>(table, "source")?),
+        overrides: match table.get("overrides") {
+            Some(overrides) => overrides.clone().try_into()?,
+            None => Vec::new(),
+        },
+    })
 }
 
 fn get_value<T: for<'a> serde::Deserialize<'a>>(
@@ -1,13 +1,10 @@
 pub use super::*;
+use crate::{Color, Commands, Component, ComponentSchema, FieldKind, FieldValue, RefId, Reflect};
 
 #[test]
 pub fn spawn_hierachy() {
     let mut scene = Scene::with_root();
-    let parent = scene.spawn(
-        "Parent",
-        (TransformComponent::default(),),
-        Scene::ROOT_INDEX,
-    );
+    let parent = scene.spawn("Parent", (TransformComponent::default(),), Scene::ROOT);
     let child = scene.spawn("Child", (TransformComponent::default(),), parent);
     let parent_node = scene.get_node(parent).unwrap();
     let child_node = scene.get_node(child).unwrap();
@@ -23,23 +20,15 @@ pub fn spawn_hierachy() {
 #[test]
 pub fn children_iterator() {
     let mut scene = Scene::with_root();
-    let parent = scene.spawn(
-        "Parent",
-        (TransformComponent::default(),),
-        Scene::ROOT_INDEX,
-    );
+    let parent = scene.spawn("Parent", (TransformComponent::default(),), Scene::ROOT);
     let child = scene.spawn("Child", (TransformComponent::default(),), parent);
     let child2 = scene.spawn("Child2", (TransformComponent::default(),), parent);
-    let parent2 = scene.spawn(
-        "Parent2",
-        (TransformComponent::default(),),
-        Scene::ROOT_INDEX,
-    );
+    let parent2 = scene.spawn("Parent2", (TransformComponent::default(),), Scene::ROOT);
 
-    let mut iter = scene.hierarchy_iter(Scene::ROOT_INDEX);
-    let index = iter.next().unwrap();
-    assert_eq!(scene.get_node(index).unwrap().name, "Root");
-    assert_eq!(index, Scene::ROOT_INDEX);
+    let mut iter = scene.hierarchy_iter(Scene::ROOT);
+    let entity = iter.next().unwrap();
+    assert_eq!(scene.get_node(entity).unwrap().name, "Root");
+    assert_eq!(entity, Scene::ROOT);
 
     assert_eq!(iter.next().unwrap(), parent);
     assert_eq!(iter.next().unwrap(), child);
@@ -51,47 +40,837 @@ pub fn children_iterator() {
 #[test]
 pub fn parent_iterator() {
     let mut scene = Scene::with_root();
-    let parent = scene.spawn(
-        "Parent",
-        (TransformComponent::default(),),
-        Scene::ROOT_INDEX,
-    );
+    let parent = scene.spawn("Parent", (TransformComponent::default(),), Scene::ROOT);
     scene.spawn("Child", (TransformComponent::default(),), parent);
     let child = scene.spawn("Child2", (TransformComponent::default(),), parent);
-    scene.spawn(
-        "Parent2",
-        (TransformComponent::default(),),
-        Scene::ROOT_INDEX,
-    );
+    scene.spawn("Parent2", (TransformComponent::default(),), Scene::ROOT);
 
     let mut iter = scene.ancestor_iter(child);
-    let index = iter.next().unwrap();
-    assert_eq!(scene.get_node(index).unwrap().name, "Parent");
-    assert_eq!(index, parent);
-    assert_eq!(iter.next().unwrap(), Scene::ROOT_INDEX);
+    let entity = iter.next().unwrap();
+    assert_eq!(scene.get_node(entity).unwrap().name, "Parent");
+    assert_eq!(entity, parent);
+    assert_eq!(iter.next().unwrap(), Scene::ROOT);
     assert_eq!(iter.next(), None);
 }
 
-// #[test]
-// pub fn despawn() {
-//     let mut scene = Scene::with_root();
-//     let parent = scene.spawn(
-//         "Parent",
-//         (TransformComponent::default(),),
-//         Scene::ROOT_INDEX,
-//     );
-//     scene.spawn("Child", (TransformComponent::default(),), parent);
-//     scene.spawn("Child2", (TransformComponent::default(),), parent);
-//     let parent2 = scene.spawn(
-//         "Parent2",
-//         (TransformComponent::default(),),
-//         Scene::ROOT_INDEX,
-//     );
-
-//     scene.despawn(parent);
-
-//     assert_eq!(
-//         scene.get_node(Scene::ROOT_INDEX).unwrap().children,
-//         vec![scene.get_node(parent2).unwrap().id]
-//     );
-// }
+#[test]
+pub fn despawn() {
+    let mut scene = Scene::with_root();
+    let parent = scene.spawn("Parent", (TransformComponent::default(),), Scene::ROOT);
+    scene.spawn("Child", (TransformComponent::default(),), parent);
+    scene.spawn("Child2", (TransformComponent::default(),), parent);
+    let parent2 = scene.spawn("Parent2", (TransformComponent::default(),), Scene::ROOT);
+
+    let child = scene.spawn("Child3", (TransformComponent::default(),), parent);
+    scene.despawn(parent);
+    assert!(scene.get_node(parent).is_none());
+    assert!(scene.get_node(child).is_none());
+
+    assert_eq!(
+        scene.get_node(Scene::ROOT).unwrap().children,
+        vec![scene.get_node(parent2).unwrap().id]
+    );
+}
+
+#[test]
+pub fn set_parent() {
+    let mut scene = Scene::with_root();
+    let parent = scene.spawn("Parent", (), Scene::ROOT);
+    let child = scene.spawn("Child", (), parent);
+    let parent2 = scene.spawn("Parent2", (), Scene::ROOT);
+
+    scene.set_parent(child, parent2, false);
+    assert!(scene.get_node(parent).unwrap().children.is_empty());
+    assert_eq!(
+        scene.get_node(parent2).unwrap().children,
+        vec![scene.get_node(child).unwrap().id]
+    );
+    assert_eq!(scene.ancestor_iter(child).next(), Some(parent2));
+}
+
+#[test]
+#[should_panic]
+pub fn set_parent_cycle() {
+    let mut scene = Scene::with_root();
+    let parent = scene.spawn("Parent", (), Scene::ROOT);
+    let child = scene.spawn("Child", (), parent);
+    scene.set_parent(parent, child, false);
+}
+
+#[test]
+pub fn scene_commands() {
+    let mut scene = Scene::with_root();
+    let parent = scene.spawn("Parent", (TransformComponent::default(),), Scene::ROOT);
+    let child = scene.spawn("Child", (TransformComponent::default(),), parent);
+
+    let mut commands = Commands::<Scene>::default();
+    let mut spawned_id = None;
+    for (entity, _) in scene.world.query::<(&TransformComponent,)>() {
+        if entity == child {
+            spawned_id = Some(commands.spawn(&scene, "Spawned", (), entity));
+            commands.set_parent(entity, Scene::ROOT, false);
+        }
+    }
+    commands.despawn(parent);
+    commands.apply(&mut scene);
+
+    let spawned = scene.id_to_entity(spawned_id.unwrap());
+    assert!(scene.get_node(parent).is_none());
+    assert_eq!(scene.get_node(spawned).unwrap().name, "Spawned");
+    assert_eq!(
+        scene.ancestor_iter(spawned).collect::<Vec<_>>(),
+        vec![child, Scene::ROOT]
+    );
+}
+
+#[test]
+pub fn serialize_dynamic_components() {
+    let mut scene = Scene::with_root();
+    let health = scene.world.register_component(
+        ComponentSchema::new("Health")
+            .field("current", FieldKind::Float)
+            .field("name", FieldKind::String),
+    );
+    let entity = scene.spawn("Player", (TransformComponent::default(),), Scene::ROOT);
+    let mut component = scene
+        .world
+        .component_schema(health)
+        .unwrap()
+        .default_component();
+    component.set(0, FieldValue::Float(2.5));
+    component.set(1, FieldValue::String("Bob".to_owned()));
+    scene
+        .world
+        .entity_mut(entity)
+        .unwrap()
+        .add_dynamic(health, component.clone());
+
+    let source = SceneSerializer::serialize(&scene).unwrap();
+    let loaded = SceneSerializer::deserialize(source).unwrap();
+    let health = loaded.world.component_id("Health").unwrap();
+    let (_, loaded_component) = loaded.world.query_dynamic(health).next().unwrap();
+    assert_eq!(loaded_component, &component);
+}
+
+#[test]
+pub fn reflect_paths() {
+    #[derive(Reflect, Default)]
+    struct Stats {
+        health: f32,
+        team: ProjectionKind,
+        #[reflect(skip)]
+        _cache: Vec<u8>,
+        transform: TransformComponent,
+    }
+
+    let mut stats = Stats::default();
+    let reflect: &mut dyn Reflect = &mut stats;
+    assert_eq!(reflect.field_names(), ["health", "team", "transform"]);
+    assert!(reflect.field("_cache").is_none());
+    assert_eq!(
+        reflect.path("transform.scale").unwrap().type_name(),
+        "glam::f32::vec3::Vec3"
+    );
+
+    assert!(reflect.set_path("transform.translation.x", FieldValue::Int(3)));
+    assert!(reflect.set_path("team", FieldValue::String("Perspective".to_owned())));
+    assert!(!reflect.set_path("team", FieldValue::String("Isometric".to_owned())));
+    assert!(!reflect.set_path("health", FieldValue::Bool(true)));
+    assert!(!reflect.set_path("transform.missing", FieldValue::Int(1)));
+    assert_eq!(
+        reflect.get_path("transform.translation.x"),
+        Some(FieldValue::Float(3.))
+    );
+    assert_eq!(reflect.path("team").unwrap().variants().len(), 2);
+    assert_eq!(stats.team, ProjectionKind::Perspective);
+    assert_eq!(stats.transform.translation.x, 3.);
+}
+
+#[test]
+pub fn serialize_reflected_components() {
+    let mut scene = Scene::with_root();
+    let sprite = SpriteComponent {
+        color: Color::from_rgba(1., 0.5, 0., 1.),
+        ..Default::default()
+    };
+    let camera = CameraComponent {
+        projection_kind: ProjectionKind::Perspective,
+        far: 100.,
+        ..Default::default()
+    };
+    let entity = scene.spawn(
+        "Entity",
+        (TransformComponent::default(), sprite, camera),
+        Scene::ROOT,
+    );
+
+    let source = SceneSerializer::serialize(&scene).unwrap();
+    // Fields that are the same as the component's default are left out
+    assert!(!source.contains("scale"));
+    assert!(source.contains("projection_kind = \"Perspective\""));
+
+    let loaded = SceneSerializer::deserialize(source).unwrap();
+    let entity = loaded.world.entity(entity).unwrap();
+    assert_eq!(
+        entity.get::<SpriteComponent>().unwrap().color.0,
+        [1., 0.5, 0., 1.]
+    );
+    let camera = entity.get::<CameraComponent>().unwrap();
+    assert_eq!(camera.projection_kind, ProjectionKind::Perspective);
+    assert_eq!((camera.near, camera.far), (-1., 100.));
+    assert_eq!(
+        entity.get::<TransformComponent>().unwrap().scale,
+        glam::Vec3::ONE
+    );
+
+    // Vectors are written as arrays and fields not in the file keep their default
+    let source = r#"
+        [[entity]]
+        id = 0
+        name = "Root"
+
+        [entity.TransformComponent]
+        translation = [1.0, 2.0, 3.0]
+        rotation = { z = 2 }
+    "#;
+    let loaded = SceneSerializer::deserialize(source.to_owned()).unwrap();
+    let transform = loaded.world.get::<TransformComponent>(Scene::ROOT).unwrap();
+    assert_eq!(transform.translation, glam::vec3(1., 2., 3.));
+    assert_eq!(transform.rotation, glam::vec3(0., 0., 2.));
+    assert_eq!(transform.scale, glam::Vec3::ONE);
+}
+
+#[test]
+pub fn script_reflected_fields() {
+    let mut scene = Scene::with_root();
+    let entity = scene.spawn(
+        "Sprite",
+        (TransformComponent::default(), SpriteComponent::default()),
+        Scene::ROOT,
+    );
+    let mut scripting = unsafe { crate::ScriptingEngine::new(&mut scene) };
+    scripting.setup().unwrap();
+    scripting.lua.globals().set("entity", entity).unwrap();
+    scripting
+        .lua
+        .load(
+            r#"
+            local transform = get_component("TransformComponent")
+            transform.translation = transform.translation + Vector3.new(1, 2, 3)
+            transform.scale.x = 5 -- Vectors are copies so this does nothing
+
+            local sprite = get_component("SpriteComponent")
+            sprite.color.g = 0.5
+            sprite.texture_path = { path = "sprite.png" }
+            assert(sprite.texture_path.path == "sprite.png")
+            assert(not pcall(function() sprite.color = 1 end))
+            assert(not pcall(function() return sprite.missing end))
+            "#,
+        )
+        .exec()
+        .unwrap();
+    drop(scripting);
+
+    let transform = scene.world.get::<TransformComponent>(entity).unwrap();
+    assert_eq!(transform.translation, glam::vec3(1., 2., 3.));
+    assert_eq!(transform.scale, glam::Vec3::ONE);
+    let sprite = scene.world.get::<SpriteComponent>(entity).unwrap();
+    assert_eq!(sprite.color.0, [1., 0.5, 1., 1.]);
+    assert_eq!(
+        sprite.texture_path.path,
+        Some(std::path::PathBuf::from("sprite.png"))
+    );
+}
+
+#[test]
+pub fn registered_components() {
+    #[derive(Reflect, Default, Clone, Component)]
+    struct Health {
+        value: i32,
+        regen: f32,
+    }
+
+    let mut registry = ComponentRegistry::default();
+    registry.register::<Health>();
+    assert_eq!(registry.get("Health").unwrap().pretty_name, "Health");
+    assert!(registry
+        .get_by_id(crate::ComponentId::of::<Health>())
+        .is_some());
+
+    let mut scene = Scene::with_root();
+    scene.registry = registry.clone();
+    let entity = scene.spawn(
+        "Player",
+        Health {
+            value: 10,
+            regen: 0.5,
+        },
+        Scene::ROOT,
+    );
+
+    let source = SceneSerializer::serialize(&scene).unwrap();
+    assert!(source.contains("[entity.Health]"));
+
+    // Components that aren't registered are ignored
+    let loaded = SceneSerializer::deserialize(source.clone()).unwrap();
+    assert!(!loaded.world.entity(entity).unwrap().has::<Health>());
+
+    let mut loaded = SceneSerializer::deserialize_with_registry(source, registry).unwrap();
+    let health = loaded.world.get::<Health>(entity).unwrap();
+    assert_eq!((health.value, health.regen), (10, 0.5));
+
+    let mut scripting = unsafe { crate::ScriptingEngine::new(&mut loaded) };
+    scripting.setup().unwrap();
+    scripting.lua.globals().set("entity", entity).unwrap();
+    scripting
+        .lua
+        .load(
+            r#"
+            local health = get_component("Health")
+            health.value = health.value - 3
+            assert(get_component("Health").value == 7)
+            remove_component("Health")
+            add_component("SpriteComponent")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    scripting.apply_commands();
+    drop(scripting);
+
+    let entity = loaded.world.entity(entity).unwrap();
+    assert!(!entity.has::<Health>());
+    assert!(entity.has::<SpriteComponent>());
+}
+
+#[test]
+#[should_panic(expected = "component name 'Health' is already used")]
+pub fn registered_component_name_conflict() {
+    mod player {
+        #[derive(crate::Reflect, Default, Clone, crate::Component)]
+        pub struct Health {
+            pub value: i32,
+        }
+    }
+    #[derive(Reflect, Default, Clone, Component)]
+    struct Health {
+        value: f32,
+    }
+
+    let mut registry = ComponentRegistry::default();
+    registry.register::<player::Health>();
+    registry.register::<player::Health>();
+    registry.register::<Health>();
+}
+
+#[test]
+pub fn propagate_global_transforms() {
+    let mut scene = Scene::with_root();
+    let at = |x: f32, y: f32| TransformComponent {
+        translation: glam::vec3(x, y, 0.),
+        ..Default::default()
+    };
+    let parent = scene.spawn("Parent", at(1., 0.), Scene::ROOT);
+    let group = scene.spawn("Group", (), parent);
+    let child = scene.spawn("Child", at(0., 2.), group);
+    let other = scene.spawn("Other", at(5., 0.), Scene::ROOT);
+
+    scene.propagate_transforms();
+    let translation = |scene: &Scene, entity| scene.global_transform(entity).unwrap().translation();
+    assert_eq!(translation(&scene, child), glam::vec3(1., 2., 0.));
+    assert_eq!(translation(&scene, group), glam::vec3(1., 0., 0.));
+    assert!(!scene.world.entity(group).unwrap().has::<GlobalTransform>());
+
+    // Changes are seen before propagating but only the changed subtree gets recomputed
+    scene.world.advance_tick();
+    scene
+        .world
+        .get_mut::<TransformComponent>(parent)
+        .unwrap()
+        .translation
+        .x = 3.;
+    assert_eq!(translation(&scene, child), glam::vec3(3., 2., 0.));
+    let change_tick = scene.world.change_tick();
+    scene.propagate_transforms();
+    let global_changed = |entity| {
+        let ticks = scene.world.component_ticks::<GlobalTransform>(entity);
+        ticks.unwrap().changed == change_tick
+    };
+    assert!(global_changed(child));
+    assert!(!global_changed(other));
+    assert_eq!(
+        scene
+            .world
+            .get::<GlobalTransform>(child)
+            .unwrap()
+            .translation(),
+        glam::vec3(3., 2., 0.)
+    );
+
+    scene.world.advance_tick();
+    scene.set_parent(child, other, false);
+    assert_eq!(translation(&scene, child), glam::vec3(5., 2., 0.));
+
+    scene.set_global_transform(
+        child,
+        GlobalTransform(glam::Mat4::from_translation(glam::Vec3::ONE)),
+    );
+    assert_eq!(
+        scene
+            .world
+            .get::<TransformComponent>(child)
+            .unwrap()
+            .translation,
+        glam::vec3(-4., 1., 1.)
+    );
+    scene.propagate_transforms();
+    assert_eq!(
+        scene
+            .world
+            .get::<GlobalTransform>(child)
+            .unwrap()
+            .translation(),
+        glam::Vec3::ONE
+    );
+
+    let mut scripting = unsafe { crate::ScriptingEngine::new(&mut scene) };
+    scripting.setup().unwrap();
+    scripting.lua.globals().set("entity", child).unwrap();
+    scripting
+        .lua
+        .load(
+            r#"
+            local global = get_global_transform()
+            assert(global.translation.x == 1 and global.scale.y == 1)
+            set_global_transform({ translation = Vector3.new(5, 0, 0) })
+            assert(get_global_transform().translation.x == 5)
+            "#,
+        )
+        .exec()
+        .unwrap();
+    drop(scripting);
+    assert_eq!(
+        scene
+            .world
+            .get::<TransformComponent>(child)
+            .unwrap()
+            .translation,
+        glam::Vec3::ZERO
+    );
+
+    // Removing a transform moves the children back to the parent above it
+    scene.propagate_transforms();
+    let child_global = |scene: &Scene| scene.world.get::<GlobalTransform>(child).copied();
+    assert_eq!(child_global(&scene).unwrap().translation().x, 5.);
+    scene.world.advance_tick();
+    scene
+        .world
+        .entity_mut(other)
+        .unwrap()
+        .remove::<TransformComponent>();
+    scene.propagate_transforms();
+    assert!(!scene.world.entity(other).unwrap().has::<GlobalTransform>());
+    assert_eq!(
+        child_global(&scene).unwrap().translation(),
+        glam::Vec3::ZERO
+    );
+}
+
+#[test]
+pub fn reparent_and_reorder() {
+    let mut scene = Scene::with_root();
+    let at = |x: f32| TransformComponent {
+        translation: glam::vec3(x, 0., 0.),
+        ..Default::default()
+    };
+    let parent = scene.spawn("Parent", at(10.), Scene::ROOT);
+    let a = scene.spawn("A", at(1.), Scene::ROOT);
+    let b = scene.spawn("B", at(2.), Scene::ROOT);
+    assert!(!scene.is_valid_parent(parent, parent));
+    assert!(scene.is_valid_parent(a, parent));
+
+    scene.set_parent(a, parent, true);
+    assert_eq!(scene.parent(a), Some(parent));
+    assert_eq!(
+        scene
+            .world
+            .get::<TransformComponent>(a)
+            .unwrap()
+            .translation,
+        glam::vec3(-9., 0., 0.)
+    );
+    assert_eq!(
+        scene.global_transform(a).unwrap().translation(),
+        glam::vec3(1., 0., 0.)
+    );
+    assert!(!scene.is_valid_parent(parent, a));
+
+    scene.set_parent(b, parent, false);
+    assert_eq!(
+        scene.global_transform(b).unwrap().translation(),
+        glam::vec3(12., 0., 0.)
+    );
+
+    let ids = |scene: &Scene| scene.get_node(parent).unwrap().children.clone();
+    let (a_id, b_id) = (scene.get_node(a).unwrap().id, scene.get_node(b).unwrap().id);
+    assert_eq!(ids(&scene), vec![a_id, b_id]);
+    scene.move_sibling(b, 0);
+    assert_eq!(ids(&scene), vec![b_id, a_id]);
+    scene.move_sibling(b, 10);
+    assert_eq!(ids(&scene), vec![a_id, b_id]);
+}
+
+#[test]
+pub fn prefab_instances() {
+    let mut scene = Scene::with_root();
+    let at = |x: f32| TransformComponent {
+        translation: glam::vec3(x, 0., 0.),
+        ..Default::default()
+    };
+    let enemy = scene.spawn("Enemy", at(1.), Scene::ROOT);
+    scene.spawn("Gun", at(2.), enemy);
+    let path = std::path::PathBuf::from("enemy.prefab");
+    let prefab = scene.create_prefab(enemy, path.clone());
+    assert_eq!(prefab.scene.hierarchy_iter(Scene::ROOT).count(), 2);
+
+    // Instances get new ids but stay linked to the prefab entities
+    let instance = scene.instantiate_prefab(prefab.clone(), path.clone(), Scene::ROOT);
+    let gun = scene.id_to_entity(scene.get_node(instance).unwrap().children[0]);
+    assert_ne!(
+        scene.get_node(instance).unwrap().id,
+        prefab.scene.root_node().id
+    );
+    assert_eq!(scene.prefab_root(gun), Some(instance));
+
+    let translation = |scene: &Scene, entity| {
+        let transform = scene.world.get::<TransformComponent>(entity).unwrap();
+        transform.translation.x
+    };
+    scene
+        .world
+        .get_mut::<TransformComponent>(gun)
+        .unwrap()
+        .translation
+        .x = 5.;
+    scene.update_prefab_overrides(instance);
+    let overrides = &scene.world.get::<PrefabInstance>(gun).unwrap().overrides;
+    assert_eq!(
+        overrides,
+        &vec!["TransformComponent.translation.x".to_owned()]
+    );
+
+    // Overrides are kept when other edits to the prefab are synced
+    let mut edited = Prefab::from_subtree(&scene, enemy);
+    let edited_gun = edited
+        .scene
+        .id_to_entity(edited.scene.root_node().children[0]);
+    let transform = edited.scene.world.get_mut::<TransformComponent>(edited_gun);
+    transform.unwrap().translation = glam::vec3(3., 3., 0.);
+    scene
+        .world
+        .get_mut::<PrefabInstance>(instance)
+        .unwrap()
+        .prefab = Some(edited.into());
+    scene.sync_prefab(instance);
+    assert_eq!(translation(&scene, gun), 5.);
+    let transform = scene.world.get::<TransformComponent>(gun).unwrap();
+    assert_eq!(transform.translation.y, 3.);
+
+    // The link and overrides are saved but the prefab has to be loaded again
+    let data = SceneSerializer::serialize(&scene).unwrap();
+    let mut loaded = SceneSerializer::deserialize(data).unwrap();
+    let loaded_instance = loaded.id_to_entity(scene.get_node(instance).unwrap().id);
+    let loaded_gun = loaded.id_to_entity(scene.get_node(gun).unwrap().id);
+    let root_instance = loaded.world.get::<PrefabInstance>(loaded_instance).unwrap();
+    assert_eq!(root_instance.prefab_path, Some(path));
+    assert!(root_instance.prefab.is_none());
+    let overrides = &loaded
+        .world
+        .get::<PrefabInstance>(loaded_gun)
+        .unwrap()
+        .overrides;
+    assert_eq!(
+        overrides,
+        &vec!["TransformComponent.translation.x".to_owned()]
+    );
+
+    loaded
+        .world
+        .get_mut::<PrefabInstance>(loaded_instance)
+        .unwrap()
+        .prefab = Some(prefab);
+    loaded.revert_prefab_overrides(loaded_instance);
+    assert_eq!(translation(&loaded, loaded_gun), 2.);
+
+    // Applying makes entities added to the instance part of the prefab
+    loaded.spawn("Hat", at(4.), loaded_instance);
+    let applied = loaded.apply_prefab_overrides(loaded_instance);
+    assert_eq!(applied.scene.hierarchy_iter(Scene::ROOT).count(), 3);
+    let synced = scene.instantiate_prefab(applied, "enemy.prefab".into(), Scene::ROOT);
+    assert_eq!(scene.get_node(synced).unwrap().children.len(), 2);
+}
+
+#[test]
+pub fn prefab_game_components() {
+    #[derive(Reflect, Default, Clone, Component)]
+    struct Health {
+        value: i32,
+    }
+    #[derive(Default, Clone, PartialEq, Debug, Component)]
+    struct Loot(u32);
+
+    let mut scene = Scene::with_root();
+    scene.registry.register::<Health>();
+    let enemy = scene.spawn("Enemy", (Health { value: 3 }, Loot(7)), Scene::ROOT);
+    let path = std::env::temp_dir().join(format!("crile-{}.prefab", std::process::id()));
+    let prefab = scene.create_prefab(enemy, path.clone());
+
+    // Components that aren't registered are cloned into instances
+    let instance = scene.instantiate_prefab(prefab.clone(), path.clone(), Scene::ROOT);
+    assert_eq!(scene.world.get::<Loot>(instance), Some(&Loot(7)));
+    assert_eq!(scene.world.get::<Health>(instance).unwrap().value, 3);
+    crate::write_file(&path, &SceneSerializer::serialize(&prefab.scene).unwrap());
+
+    // Prefabs are loaded with the registry of the scene so syncing keeps game components
+    let mut assets = crate::AssetManager::default();
+    let loaded = assets.load_prefab(&path, &scene.registry).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    scene.world.get_mut::<PrefabInstance>(enemy).unwrap().prefab = Some(loaded);
+    scene.sync_prefab(enemy);
+    assert_eq!(scene.world.get::<Health>(enemy).unwrap().value, 3);
+    assert!(Prefab::load(&path, scene.registry.clone()).is_err());
+
+    // Removing a component is an override that syncing keeps until it's reverted
+    scene.world.entity_mut(enemy).unwrap().remove::<Health>();
+    scene.update_prefab_overrides(enemy);
+    let instance = scene.world.get::<PrefabInstance>(enemy).unwrap();
+    assert_eq!(instance.overrides, vec!["-Health".to_owned()]);
+    scene.sync_prefab(enemy);
+    assert!(!scene.world.entity(enemy).unwrap().has::<Health>());
+    scene.revert_prefab_overrides(enemy);
+    assert_eq!(scene.world.get::<Health>(enemy).unwrap().value, 3);
+}
+
+#[test]
+pub fn entity_links() {
+    #[derive(Reflect, Default, Clone, Component)]
+    struct Follow {
+        target: EntityLink,
+    }
+
+    let mut registry = ComponentRegistry::default();
+    registry.register::<Follow>();
+    let mut scene = Scene::with_root();
+    scene.registry = registry.clone();
+    let group = scene.spawn("Group", (), Scene::ROOT);
+    let target = scene.spawn("Target", (), group);
+    let follow = Follow {
+        target: scene.link(target),
+    };
+    let follower = scene.spawn("Follower", follow, group);
+    assert_eq!(scene.resolve_link(scene.link(target)), Some(target));
+    assert_eq!(scene.resolve_link(EntityLink(None)), None);
+
+    // Ids that don't fit in a hierarchy id are rejected instead of linking another entity
+    let mut link = scene.link(target);
+    assert!(!link.set_value(FieldValue::Int(-1)));
+    assert!(!link.set_value(FieldValue::Int(u32::MAX as i64 + 1)));
+    assert_eq!(link, scene.link(target));
+    assert!(link.set_value(FieldValue::Int(0)));
+    assert_eq!(link, EntityLink(None));
+
+    // Links point to the same entity after loading even though entity indices can change
+    let source = SceneSerializer::serialize(&scene).unwrap();
+    let mut loaded = SceneSerializer::deserialize_with_registry(source, registry).unwrap();
+    let loaded_follower = loaded.id_to_entity(scene.get_node(follower).unwrap().id);
+    let link = loaded.world.get::<Follow>(loaded_follower).unwrap().target;
+    assert_eq!(
+        loaded
+            .get_node(loaded.resolve_link(link).unwrap())
+            .unwrap()
+            .name,
+        "Target"
+    );
+
+    // Copies link to the copied entities instead of the originals
+    let copy = loaded.spawn_from_scene(&scene, Scene::ROOT);
+    let copy_group = loaded.id_to_entity(loaded.get_node(copy).unwrap().children[0]);
+    let copy_children = loaded.get_node(copy_group).unwrap().children.clone();
+    let (copy_target, copy_follower) = (copy_children[0], copy_children[1]);
+    let link = loaded
+        .world
+        .get::<Follow>(loaded.id_to_entity(copy_follower))
+        .unwrap()
+        .target;
+    assert_eq!(link, EntityLink(Some(copy_target)));
+
+    let prefab = scene.create_prefab(group, "group.prefab".into());
+    let instance = scene.instantiate_prefab(prefab, "group.prefab".into(), Scene::ROOT);
+    let instance_children = scene.get_node(instance).unwrap().children.clone();
+    let instance_follower = scene.id_to_entity(instance_children[1]);
+    let link = scene.world.get::<Follow>(instance_follower).unwrap().target;
+    assert_eq!(link, EntityLink(Some(instance_children[0])));
+
+    // Syncing keeps links inside the instance and doesn't see them as overrides
+    scene.update_prefab_overrides(instance);
+    scene.sync_prefab(instance);
+    let link = scene.world.get::<Follow>(instance_follower).unwrap().target;
+    assert_eq!(link, EntityLink(Some(instance_children[0])));
+    let instance_follower = scene.world.get::<PrefabInstance>(instance_follower);
+    assert!(instance_follower.unwrap().overrides.is_empty());
+
+    let mut scripting = unsafe { crate::ScriptingEngine::new(&mut scene) };
+    scripting.setup().unwrap();
+    scripting.lua.globals().set("entity", follower).unwrap();
+    scripting.lua.globals().set("other", group).unwrap();
+    scripting
+        .lua
+        .load(
+            r#"
+            local follow = get_component("Follow")
+            assert(follow.target ~= nil)
+            follow.target = other
+            "#,
+        )
+        .exec()
+        .unwrap();
+    drop(scripting);
+    let link = scene.world.get::<Follow>(follower).unwrap().target;
+    assert_eq!(scene.resolve_link(link), Some(group));
+}
+
+#[test]
+pub fn scene_migrations() {
+    #[derive(Reflect, Default, Clone, Component)]
+    struct Health {
+        value: i32,
+        armor: u8,
+    }
+
+    // Saved before the component was renamed from "Hp" and its field from "amount"
+    let source = r#"
+        [[entity]]
+        id = 1
+        name = "Root"
+
+        [[entity]]
+        id = 2
+        name = "Player"
+        parent = 1
+        Hp = { amount = 5 }
+        TransformComponent = { translation = [1.0, 2.0, 3.0] }
+    "#;
+
+    let mut registry = ComponentRegistry::default();
+    registry.register::<Health>();
+    let result = SceneSerializer::deserialize_with_mode(
+        source.to_owned(),
+        registry.clone(),
+        DeserializeMode::Strict,
+    );
+    assert!(result.err().unwrap().message().contains("Player.Hp"));
+
+    registry.add_migration(2, |scene| {
+        rename_component(scene, "Hp", "Health");
+        rename_field(scene, "Health", "amount", "value");
+        migrate_component(scene, "TransformComponent", |transform| {
+            transform.as_table_mut().unwrap().remove("translation");
+        });
+    });
+    assert_eq!(registry.scene_version(), 2);
+
+    let scene = SceneSerializer::deserialize_with_mode(
+        source.to_owned(),
+        registry.clone(),
+        DeserializeMode::Strict,
+    )
+    .unwrap();
+    let player = scene.id_to_entity(HierarchyId(2));
+    assert_eq!(scene.world.get::<Health>(player).unwrap().value, 5);
+    let transform = scene.world.get::<TransformComponent>(player).unwrap();
+    assert_eq!(transform.translation, glam::Vec3::ZERO);
+
+    // Saved scenes have the newest version so the migrations don't run on them again
+    let saved = SceneSerializer::serialize(&scene).unwrap();
+    assert!(saved.starts_with("version = 2"));
+    let mut broken = saved.replace("value = 5", "value = \"five\"");
+    let result = SceneSerializer::deserialize_with_mode(
+        broken.clone(),
+        registry.clone(),
+        DeserializeMode::Strict,
+    );
+    assert!(result
+        .err()
+        .unwrap()
+        .message()
+        .contains("Player.Health.value"));
+    let scene =
+        SceneSerializer::deserialize_with_registry(broken.clone(), registry.clone()).unwrap();
+    assert_eq!(scene.world.get::<Health>(player).unwrap().value, 0);
+
+    // Numbers that would be truncated to fit the field are wrong too
+    for armor in ["300", "-1", "2.5", "1e30"] {
+        let broken = saved.replace("value = 5", &format!("value = 5\narmor = {armor}"));
+        let result = SceneSerializer::deserialize_with_mode(
+            broken,
+            registry.clone(),
+            DeserializeMode::Strict,
+        );
+        let message = result.err().unwrap().message().to_owned();
+        assert!(
+            message.contains("Player.Health.armor"),
+            "{armor}: {message}"
+        );
+    }
+    let whole = saved.replace("value = 5", "value = 5\narmor = 3.0");
+    let scene =
+        SceneSerializer::deserialize_with_mode(whole, registry.clone(), DeserializeMode::Strict)
+            .unwrap();
+    assert_eq!(scene.world.get::<Health>(player).unwrap().armor, 3);
+
+    broken = saved.replace("version = 2", "version = 3");
+    let result = SceneSerializer::deserialize_with_mode(broken, registry, DeserializeMode::Strict);
+    assert!(result.is_err());
+}
+
+#[test]
+pub fn copies_keep_unreflected_fields() {
+    let mut scene = Scene::with_root();
+    let script = RefId::new(crate::Script {
+        bytecode: vec![1, 2, 3],
+        source: None,
+    });
+    let camera = CameraComponent {
+        viewport_size: glam::vec2(800.0, 600.0),
+        ..Default::default()
+    };
+    let script_component = ScriptComponent {
+        script: Some(script.clone()),
+        ..Default::default()
+    };
+    let player = scene.spawn("Player", (camera, script_component), Scene::ROOT);
+
+    // Fields that are skipped by reflection can only be kept by cloning the components
+    let mut other = Scene::with_root();
+    let copy = other.spawn_from_scene(&scene, Scene::ROOT);
+    let copy = other.id_to_entity(other.get_node(copy).unwrap().children[0]);
+    let camera = other.world.get::<CameraComponent>(copy).unwrap();
+    assert_eq!(camera.viewport_size, glam::vec2(800.0, 600.0));
+    let copied_script = other.world.get::<ScriptComponent>(copy).unwrap();
+    assert_eq!(copied_script.script.as_ref().unwrap().id(), script.id());
+
+    let prefab = scene.create_prefab(player, "player.prefab".into());
+    let instance = scene.instantiate_prefab(prefab, "player.prefab".into(), Scene::ROOT);
+    let camera = scene.world.get::<CameraComponent>(instance).unwrap();
+    assert_eq!(camera.viewport_size, glam::vec2(800.0, 600.0));
+
+    // Components that were missing from the instance are cloned from the prefab on sync
+    scene
+        .world
+        .entity_mut(instance)
+        .unwrap()
+        .remove::<ScriptComponent>();
+    scene.sync_prefab(instance);
+    let synced_script = scene.world.get::<ScriptComponent>(instance).unwrap();
+    assert_eq!(synced_script.script.as_ref().unwrap().id(), script.id());
+}
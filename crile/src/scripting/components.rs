@@ -1,69 +1,553 @@
-use mlua::IntoLua;
+use std::{cell::RefCell, marker::PhantomData, rc::Rc};
+
+use mlua::{FromLua, IntoLua};
 
 use super::vector::*;
 use crate::{
-    with_components, CameraComponent, Scene, ScriptComponent, SpriteComponent, TransformComponent,
+    CameraComponent, Commands, Component, ComponentId, ComponentSchema, DynamicComponent, Entity,
+    EntityLink, FieldKind, FieldValue, GlobalTransform, Reflect, Scene, TransformComponent, World,
 };
 
-impl mlua::UserData for &mut TransformComponent {
-    fn add_fields<F: mlua::prelude::LuaUserDataFields<Self>>(fields: &mut F) {
-        fields.add_field_method_get("translation", |_, this| Ok(Vector3(this.translation)));
-        fields.add_field_method_set("translation", |_, this, val: Vector3| {
-            this.translation = val.0;
-            Ok(())
+/// Handle to a component of an entity that scripts can hold onto
+/// The component is looked up on every access so the handle stays valid when the world changes
+struct ComponentHandle<T> {
+    scene: *mut Scene,
+    entity: Entity,
+    marker: PhantomData<T>,
+}
+
+impl<T: Component + Reflect> ComponentHandle<T> {
+    fn get(&self) -> mlua::Result<&T> {
+        let scene = unsafe { &*self.scene };
+        scene.world.get(self.entity).ok_or_else(component_removed)
+    }
+
+    /// Handle to the whole component which its reflected fields are accessed through
+    fn root(&self) -> FieldHandle {
+        FieldHandle::root::<T>(self.scene, self.entity)
+    }
+
+    /// Exposes the reflected fields of the component to scripts
+    fn add_field_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(mlua::MetaMethod::Index, |lua, this, field: String| {
+            this.root().index(lua, &field)
         });
+        methods.add_meta_method_mut(
+            mlua::MetaMethod::NewIndex,
+            |lua, this, (field, value): (String, mlua::Value)| {
+                this.root().new_index(lua, &field, value)
+            },
+        );
+    }
+}
 
-        fields.add_field_method_get("rotation", |_, this| Ok(Vector3(this.rotation)));
-        fields.add_field_method_set("rotation", |_, this, val: Vector3| {
-            this.rotation = val.0;
-            Ok(())
+fn component_removed() -> mlua::Error {
+    mlua::Error::runtime("Component or entity has been removed")
+}
+
+impl mlua::UserData for ComponentHandle<CameraComponent> {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        Self::add_field_methods(methods);
+        methods.add_method("screen_to_world", |_, this, val: Vector2| {
+            Ok(Vector2(this.get()?.screen_to_world(val.0)))
         });
+    }
+}
 
-        fields.add_field_method_get("scale", |_, this| Ok(Vector3(this.scale)));
-        fields.add_field_method_set("scale", |_, this, val: Vector3| {
-            this.scale = val.0;
-            Ok(())
+/// Gives scripts a handle to the reflected fields of the component
+pub(crate) fn reflect_into_lua<T: Component + Reflect>(
+    lua: &mlua::Lua,
+    scene: *mut Scene,
+    entity: Entity,
+) -> mlua::Result<mlua::Value> {
+    FieldHandle::root::<T>(scene, entity).into_lua(lua)
+}
+
+/// Cameras also have methods to convert between screen and world positions
+pub(crate) fn camera_into_lua(
+    lua: &mlua::Lua,
+    scene: *mut Scene,
+    entity: Entity,
+) -> mlua::Result<mlua::Value> {
+    ComponentHandle::<CameraComponent> {
+        scene,
+        entity,
+        marker: PhantomData,
+    }
+    .into_lua(lua)
+}
+
+/// Handle to a field inside a component like `sprite.color` so writing to its fields changes
+/// the component instead of a copy
+struct FieldHandle {
+    scene: *mut Scene,
+    entity: Entity,
+    get: fn(&World, Entity) -> Option<&dyn Reflect>,
+    get_mut: fn(&mut World, Entity) -> Option<&mut dyn Reflect>,
+    /// Path of the field inside the component, see [Reflect::path]
+    path: String,
+}
+
+impl FieldHandle {
+    fn root<T: Component + Reflect>(scene: *mut Scene, entity: Entity) -> Self {
+        FieldHandle {
+            scene,
+            entity,
+            get: |world, entity| world.get::<T>(entity).map(|c| c as &dyn Reflect),
+            get_mut: |world, entity| world.get_mut::<T>(entity).map(|c| c as &mut dyn Reflect),
+            path: String::new(),
+        }
+    }
+
+    fn field_path(&self, field: &str) -> String {
+        if self.path.is_empty() {
+            field.to_owned()
+        } else {
+            format!("{}.{field}", self.path)
+        }
+    }
+
+    fn index(&self, lua: &mlua::Lua, field: &str) -> mlua::Result<mlua::Value> {
+        let scene = unsafe { &*self.scene };
+        let component = (self.get)(&scene.world, self.entity).ok_or_else(component_removed)?;
+        let path = self.field_path(field);
+        let value = component
+            .path(&path)
+            .ok_or_else(|| no_field(component, &path))?;
+
+        // Vectors are copied so they can be used with the vector operators
+        if let Some(vector) = value.downcast_ref::<glam::Vec3>() {
+            return Vector3(*vector).into_lua(lua);
+        }
+        if let Some(vector) = value.downcast_ref::<glam::Vec2>() {
+            return Vector2(*vector).into_lua(lua);
+        }
+        // Links are given as the entity they point to or nil if it doesn't exist anymore
+        if let Some(link) = value.downcast_ref::<EntityLink>() {
+            return scene.resolve_link(*link).into_lua(lua);
+        }
+
+        match value.value() {
+            Some(value) => value.into_lua(lua),
+            None => FieldHandle { path, ..*self }.into_lua(lua),
+        }
+    }
+
+    fn new_index(&self, lua: &mlua::Lua, field: &str, value: mlua::Value) -> mlua::Result<()> {
+        let scene = unsafe { &mut *self.scene };
+        let path = self.field_path(field);
+        let component = (self.get)(&scene.world, self.entity).ok_or_else(component_removed)?;
+        let Some(target) = component.path(&path) else {
+            return Err(no_field(component, &path));
+        };
+
+        // Links are set from the entity since scripts don't know hierarchy ids
+        let link = if target.downcast_ref::<EntityLink>().is_some() {
+            let entity = Option::<Entity>::from_lua(value.clone(), lua)?;
+            Some(entity.map_or(EntityLink(None), |entity| scene.link(entity)))
+        } else {
+            None
+        };
+
+        let component = (self.get_mut)(&mut scene.world, self.entity).unwrap();
+        let target = component.path_mut(&path).unwrap();
+        match link {
+            Some(link) => *target.downcast_mut::<EntityLink>().unwrap() = link,
+            None => set_from_lua(lua, target, value, &path)?,
+        }
+        Ok(())
+    }
+}
+
+impl mlua::UserData for FieldHandle {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(mlua::MetaMethod::Index, |lua, this, field: String| {
+            this.index(lua, &field)
         });
+        methods.add_meta_method_mut(
+            mlua::MetaMethod::NewIndex,
+            |lua, this, (field, value): (String, mlua::Value)| this.new_index(lua, &field, value),
+        );
+    }
+}
+
+/// Sets a reflected value from lua, structs can be set from a table of their fields
+fn set_from_lua(
+    lua: &mlua::Lua,
+    target: &mut dyn Reflect,
+    value: mlua::Value,
+    path: &str,
+) -> mlua::Result<()> {
+    if let Some(vector) = target.downcast_mut::<glam::Vec3>() {
+        *vector = Vector3::from_lua(value, lua)?.0;
+        return Ok(());
+    }
+    if let Some(vector) = target.downcast_mut::<glam::Vec2>() {
+        *vector = Vector2::from_lua(value, lua)?.0;
+        return Ok(());
+    }
+
+    if target.value().is_some() {
+        if !target.set_value(FieldValue::from_lua(value, lua)?) {
+            return Err(mlua::Error::RuntimeError(format!(
+                "Wrong type for field \"{path}\""
+            )));
+        }
+        return Ok(());
+    }
+
+    let table = mlua::Table::from_lua(value, lua)?;
+    for pair in table.pairs::<String, mlua::Value>() {
+        let (name, value) = pair?;
+        let field_path = format!("{path}.{name}");
+        match target.field_mut(&name) {
+            Some(field) => set_from_lua(lua, field, value, &field_path)?,
+            None => return Err(no_field(target, &name)),
+        }
+    }
+    Ok(())
+}
+
+fn no_field(value: &dyn Reflect, path: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(format!("\"{}\" has no field \"{path}\"", value.type_name()))
+}
+
+/// Handle to a runtime defined component where each field can be accessed by name
+struct DynamicComponentHandle {
+    scene: *mut Scene,
+    entity: Entity,
+    id: ComponentId,
+}
+
+impl DynamicComponentHandle {
+    fn schema(&self) -> &ComponentSchema {
+        let scene = unsafe { &*self.scene };
+        scene.world.component_schema(self.id).unwrap()
+    }
+
+    fn field_index(&self, field: &str) -> mlua::Result<usize> {
+        let schema = self.schema();
+        schema.field_index(field).ok_or_else(|| {
+            mlua::Error::RuntimeError(format!("\"{}\" has no field \"{field}\"", schema.name))
+        })
+    }
+
+    fn get(&self) -> mlua::Result<&DynamicComponent> {
+        let scene = unsafe { &*self.scene };
+        let component = scene.world.get_dynamic(self.entity, self.id);
+        component.ok_or_else(component_removed)
+    }
+
+    fn get_mut(&mut self) -> mlua::Result<&mut DynamicComponent> {
+        let scene = unsafe { &mut *self.scene };
+        let component = scene.world.get_dynamic_mut(self.entity, self.id);
+        component.ok_or_else(component_removed)
     }
 }
 
-impl mlua::UserData for &mut CameraComponent {
+impl mlua::UserData for DynamicComponentHandle {
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method("screen_to_world", |_, this, val: Vector2| {
-            Ok(Vector2(this.screen_to_world(val.0)))
+        methods.add_meta_method(mlua::MetaMethod::Index, |_, this, field: String| {
+            let index = this.field_index(&field)?;
+            Ok(this.get()?.get(index).cloned())
+        });
+
+        methods.add_meta_method_mut(
+            mlua::MetaMethod::NewIndex,
+            |_, this, (field, value): (String, FieldValue)| {
+                let index = this.field_index(&field)?;
+                if !this.get_mut()?.set(index, value) {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Wrong type for field \"{field}\""
+                    )));
+                }
+                Ok(())
+            },
+        );
+    }
+}
+
+impl mlua::IntoLua for FieldValue {
+    fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+        match self {
+            FieldValue::Bool(value) => value.into_lua(lua),
+            FieldValue::Int(value) => value.into_lua(lua),
+            FieldValue::Float(value) => value.into_lua(lua),
+            FieldValue::String(value) => value.into_lua(lua),
+        }
+    }
+}
+
+impl mlua::FromLua for FieldValue {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Boolean(value) => Ok(FieldValue::Bool(value)),
+            mlua::Value::Integer(value) => Ok(FieldValue::Int(value.into())),
+            mlua::Value::Number(value) => Ok(FieldValue::Float(value)),
+            mlua::Value::String(value) => Ok(FieldValue::String(value.to_str()?.to_owned())),
+            _ => Err(mlua::Error::RuntimeError(
+                "Expected a boolean, number or string".to_string(),
+            )),
+        }
+    }
+}
+
+impl mlua::FromLua for FieldKind {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        let string = value
+            .as_string()
+            .ok_or_else(|| mlua::Error::RuntimeError("Expected a string".to_string()))?
+            .to_str()?;
+
+        match &*string {
+            "bool" => Ok(FieldKind::Bool),
+            "int" => Ok(FieldKind::Int),
+            "float" => Ok(FieldKind::Float),
+            "string" => Ok(FieldKind::String),
+            _ => Err(mlua::Error::RuntimeError(format!(
+                "Field type '{string}' is not valid."
+            ))),
+        }
+    }
+}
+
+impl mlua::UserData for Entity {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(mlua::MetaMethod::ToString, |_, this, ()| {
+            Ok(this.to_string())
+        });
+        methods.add_meta_method(mlua::MetaMethod::Eq, |_, this, other: Entity| {
+            Ok(*this == other)
         });
     }
 }
 
-impl mlua::UserData for &mut SpriteComponent {}
+impl mlua::FromLua for Entity {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::UserData(ud) => Ok(*ud.borrow::<Entity>()?),
+            _ => Err(mlua::Error::RuntimeError("Expected an entity".to_string())),
+        }
+    }
+}
+
+fn component_not_found(component_name: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(format!("\"{component_name}\" does not exist"))
+}
 
-impl mlua::UserData for &mut ScriptComponent {}
+pub fn register_entity_funcs(
+    lua: &mlua::Lua,
+    scene_ptr: *mut Scene,
+    commands: Rc<RefCell<Commands<Scene>>>,
+) -> mlua::Result<()> {
+    let scene: &'static Scene = unsafe { &*scene_ptr };
 
-pub fn register_entity_funcs(lua: &mlua::Lua, scene: &'static Scene) -> mlua::Result<()> {
     // Class to access details about the entity like parent children and components
     lua.globals().set(
         "get_component",
-        lua.create_function(|lua, component_name: String| {
-            let index = lua.globals().get("entity_index")?;
-
-            macro_rules! match_components {
-                ([$($component: ty),*]) => {
-                    match component_name.as_str() {
-                        $(
-                            stringify!($component) => scene
-                                .world
-                                .get::<$component>(index)
-                                .map(|c| c.into_lua(lua)),
-                        )*
-                        _ => None,
+        lua.create_function(move |lua, component_name: String| {
+            let entity: Entity = lua.globals().get("entity")?;
+            let has_id = |id| scene.world.entity(entity).is_some_and(|e| e.has_id(id));
+
+            let value = match scene.registry.get(&component_name) {
+                Some(registration) => has_id(registration.id())
+                    .then(|| (registration.into_lua)(lua, scene_ptr, entity)),
+                None => scene
+                    .world
+                    .component_id(&component_name)
+                    .filter(|id| has_id(*id))
+                    .map(|id| {
+                        DynamicComponentHandle {
+                            scene: scene_ptr,
+                            entity,
+                            id,
+                        }
+                        .into_lua(lua)
+                    }),
+            };
+            value.ok_or_else(move || component_not_found(&component_name))?
+        })?,
+    )?;
+
+    // Structural changes are queued and applied after the script or signal has finished running
+    // since the scene could be in the middle of being iterated through
+    let spawn_commands = commands.clone();
+    lua.globals().set(
+        "spawn",
+        lua.create_function(move |_, (name, parent): (Option<String>, Option<Entity>)| {
+            let name = name.unwrap_or_else(|| "Empty".to_owned());
+            let parent = parent.unwrap_or(Scene::ROOT);
+            if scene.get_node(parent).is_none() {
+                return Err(mlua::Error::runtime("Parent does not exist"));
+            }
+
+            spawn_commands.borrow_mut().spawn(
+                scene,
+                name,
+                (TransformComponent::default(),),
+                parent,
+            );
+            Ok(())
+        })?,
+    )?;
+
+    let despawn_commands = commands.clone();
+    lua.globals().set(
+        "despawn",
+        lua.create_function(move |lua, entity: Option<Entity>| {
+            let entity = entity.map_or_else(|| lua.globals().get("entity"), Ok)?;
+            if entity == Scene::ROOT {
+                return Err(mlua::Error::runtime("Cannot despawn the root entity"));
+            }
+
+            despawn_commands.borrow_mut().despawn(entity);
+            Ok(())
+        })?,
+    )?;
+
+    let add_commands = commands.clone();
+    lua.globals().set(
+        "add_component",
+        lua.create_function(move |lua, component_name: String| {
+            let entity: Entity = lua.globals().get("entity")?;
+            let mut commands = add_commands.borrow_mut();
+
+            if let Some(registration) = scene.registry.get(&component_name) {
+                let insert_default = registration.insert_default;
+                commands.push(move |scene| insert_default(&mut scene.world, entity));
+                return Ok(());
+            }
+
+            let id = scene
+                .world
+                .component_id(&component_name)
+                .ok_or_else(|| component_not_found(&component_name))?;
+            commands.push(move |scene| {
+                let component = scene
+                    .world
+                    .component_schema(id)
+                    .unwrap()
+                    .default_component();
+                if let Some(mut entity) = scene.world.entity_mut(entity) {
+                    entity.insert_dynamic(id, component);
+                }
+            });
+            Ok(())
+        })?,
+    )?;
+
+    let remove_commands = commands.clone();
+    lua.globals().set(
+        "remove_component",
+        lua.create_function(move |lua, component_name: String| {
+            let entity: Entity = lua.globals().get("entity")?;
+            let mut commands = remove_commands.borrow_mut();
+
+            let id = match scene.registry.get(&component_name) {
+                Some(registration) => registration.id(),
+                None => scene
+                    .world
+                    .component_id(&component_name)
+                    .ok_or_else(|| component_not_found(&component_name))?,
+            };
+            commands.push(move |scene| {
+                if let Some(mut entity) = scene.world.entity_mut(entity) {
+                    if entity.has_id(id) {
+                        entity.remove_by_id(id);
                     }
-                };
+                }
+            });
+            Ok(())
+        })?,
+    )?;
+
+    // Registering only adds a schema and doesn't touch any entities so it can happen right away
+    lua.globals().set(
+        "register_component",
+        lua.create_function(move |_, (name, fields): (String, mlua::Table)| {
+            let mut fields = fields
+                .pairs::<String, FieldKind>()
+                .collect::<mlua::Result<Vec<_>>>()?;
+            // Lua tables have no order so sort them to get the same schema every time
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            let schema = fields.into_iter().fold(
+                ComponentSchema::new(name.clone()),
+                |schema, (field, kind)| schema.field(field, kind),
+            );
+
+            let scene = unsafe { &mut *scene_ptr };
+            if let Some(id) = scene.world.component_id(&name) {
+                if scene.world.component_schema(id) != Some(&schema) {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "Component \"{name}\" was already registered with different fields"
+                    )));
+                }
+            }
+
+            scene.world.register_component(schema);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "set_parent",
+        lua.create_function(
+            move |_, (entity, parent, keep_world_transform): (Entity, Entity, Option<bool>)| {
+                if !scene.is_valid_parent(entity, parent) {
+                    return Err(mlua::Error::runtime(
+                        "Cannot parent an entity to itself or its decendents",
+                    ));
+                }
+
+                let keep_world_transform = keep_world_transform.unwrap_or(false);
+                commands
+                    .borrow_mut()
+                    .set_parent(entity, parent, keep_world_transform);
+                Ok(())
+            },
+        )?,
+    )?;
+
+    // Global transforms are returned as a table with translation, rotation and scale vectors
+    lua.globals().set(
+        "get_global_transform",
+        lua.create_function(move |lua, entity: Option<Entity>| {
+            let entity = entity.map_or_else(|| lua.globals().get("entity"), Ok)?;
+            let transform = scene
+                .global_transform(entity)
+                .ok_or_else(component_removed)?
+                .to_transform();
+
+            let table = lua.create_table()?;
+            table.set("translation", Vector3(transform.translation))?;
+            table.set("rotation", Vector3(transform.rotation))?;
+            table.set("scale", Vector3(transform.scale))?;
+            Ok(table)
+        })?,
+    )?;
+
+    // Fields missing from the table keep their current global value
+    lua.globals().set(
+        "set_global_transform",
+        lua.create_function(move |lua, (table, entity): (mlua::Table, Option<Entity>)| {
+            let entity = entity.map_or_else(|| lua.globals().get("entity"), Ok)?;
+            let scene = unsafe { &mut *scene_ptr };
+            if scene.world.get::<TransformComponent>(entity).is_none() {
+                return Err(component_not_found("TransformComponent"));
             }
 
-            let value = with_components!(match_components);
-            value.ok_or_else(move || {
-                mlua::Error::RuntimeError(format!("\"{component_name}\" does not exist"))
-            })?
+            let mut transform = scene.global_transform(entity).unwrap().to_transform();
+            let get = |name: &str, value: glam::Vec3| {
+                let vector: Option<Vector3> = table.get(name)?;
+                mlua::Result::Ok(vector.map_or(value, |vector| vector.0))
+            };
+            transform.translation = get("translation", transform.translation)?;
+            transform.rotation = get("rotation", transform.rotation)?;
+            transform.scale = get("scale", transform.scale)?;
+
+            scene.set_global_transform(entity, GlobalTransform(transform.matrix()));
+            Ok(())
         })?,
     )?;
 
@@ -0,0 +1,65 @@
+use crate::{FieldValue, Scene};
+
+use super::script::Signal;
+
+/// Event sent by scripts with `Events.send(name, value)`
+/// Rust systems can read them from the world's [crate::Events] and send their own to scripts,
+/// which receive the value in the callbacks connected with `Events.connect(name, callback)`
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScriptEvent {
+    pub name: String,
+    pub value: Option<FieldValue>,
+}
+
+impl ScriptEvent {
+    pub fn new(name: impl Into<String>, value: Option<FieldValue>) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+}
+
+/// Name of the signal list that the callbacks for the event are stored in
+pub(crate) fn signal_name(event_name: &str) -> String {
+    format!("Events.{event_name}")
+}
+
+pub fn register_class(lua: &mlua::Lua, scene: *mut Scene) -> mlua::Result<()> {
+    let events_class = super::make_class(lua, "Events")?;
+
+    events_class.set(
+        "send",
+        lua.create_function(move |_, (name, value): (String, Option<FieldValue>)| {
+            // Scripts run one at a time while nothing else accesses the world
+            let scene = unsafe { &mut *scene };
+            scene.world.send_event(ScriptEvent::new(name, value));
+            Ok(())
+        })?,
+    )?;
+
+    events_class.set(
+        "connect",
+        lua.create_function(|lua, (name, callback): (String, mlua::Function)| {
+            let signals_index: mlua::Table = lua.globals().get("__signals_index")?;
+            let full_name = signal_name(&name);
+
+            // Events don't have to be declared up front so create the list on the first connect
+            let signal_list = match signals_index.get::<Option<mlua::Table>>(&*full_name)? {
+                Some(signal_list) => signal_list,
+                None => {
+                    let signal_list = lua.create_table()?;
+                    signals_index.set(full_name, &signal_list)?;
+                    signal_list
+                }
+            };
+
+            signal_list.push(Signal {
+                callback,
+                caller_entity: lua.globals().get("entity")?,
+            })
+        })?,
+    )?;
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::{scripting::vector::Vector2, Input, KeyCode, MouseButton};
+use crate::{scripting::vector::Vector2, Input, KeyCode, MouseButton, Scene};
 use std::str::FromStr;
 
 macro_rules! impl_enum_from_lua_str {
@@ -25,7 +25,7 @@ macro_rules! impl_enum_from_lua_str {
 impl_enum_from_lua_str!(KeyCode);
 impl_enum_from_lua_str!(MouseButton);
 
-pub fn register_class(lua: &mlua::Lua, input: &'static Input) -> mlua::Result<()> {
+pub fn register_class(lua: &mlua::Lua, scene: &'static Scene) -> mlua::Result<()> {
     let input_class = super::make_class(lua, "Input")?;
 
     macro_rules! set_input_funcs {
@@ -34,7 +34,7 @@ pub fn register_class(lua: &mlua::Lua, input: &'static Input) -> mlua::Result<()
             input_class.set(
                 stringify!($func_name),
                 lua.create_function(|_, code| {
-                    Ok(input.$func_name(code))
+                    Ok(get_input(scene)?.$func_name(code))
                 })?,
             )?;
         )*
@@ -48,13 +48,13 @@ pub fn register_class(lua: &mlua::Lua, input: &'static Input) -> mlua::Result<()
 
     input_class.set(
         "mouse_position",
-        lua.create_function(|_, ()| Ok(Vector2(input.mouse_position())))?,
+        lua.create_function(|_, ()| Ok(Vector2(get_input(scene)?.mouse_position())))?,
     )?;
 
     input_class.set(
         "get_vector",
         lua.create_function(|_, (negative_x, negative_y, positive_x, positive_y)| {
-            Ok(Vector2(input.get_vector(
+            Ok(Vector2(get_input(scene)?.get_vector(
                 negative_x, negative_y, positive_x, positive_y,
             )))
         })?,
@@ -62,3 +62,10 @@ pub fn register_class(lua: &mlua::Lua, input: &'static Input) -> mlua::Result<()
 
     Ok(())
 }
+
+fn get_input(scene: &Scene) -> mlua::Result<&Input> {
+    scene
+        .world
+        .resource::<Input>()
+        .ok_or_else(|| mlua::Error::runtime("Input resource is missing from the world"))
+}
@@ -1,9 +1,12 @@
 mod components;
+mod events;
 mod input;
 mod script;
 mod time;
 mod vector;
 
+pub(crate) use components::{camera_into_lua, reflect_into_lua};
+pub use events::ScriptEvent;
 pub use script::*;
 
 // Implements mlua::IntoLua and mlua::FromLua for struct with the fields
@@ -1,45 +1,52 @@
-use crate::{impl_mlua_conversion, Engine, Scene, WindowId};
+use std::{cell::RefCell, rc::Rc};
+
+use super::events::{signal_name, ScriptEvent};
+use crate::{
+    impl_mlua_conversion, Commands, Entity, EventReader, Resource, Scene, ScriptComponent,
+};
 
 pub struct Script {
     pub bytecode: Vec<u8>,
     pub source: Option<Box<str>>,
 }
 
+/// Calls the signals connected to one type of event with the events sent since its last call
+type EventBridge = Box<dyn FnMut(&ScriptingEngine) -> mlua::Result<()>>;
+
 pub struct ScriptingEngine {
     pub lua: mlua::Lua,
     // We store raw ptr because the scripts need constant access to Scene
     // Probably better way to do this that is safe
     pub(crate) scene: *mut Scene,
-    engine: *const Engine,
-    window_id: WindowId,
+    /// Structural changes made by scripts that get applied once they finish running
+    commands: Rc<RefCell<Commands<Scene>>>,
+    /// Forward the events of the world to the signals connected with `Events.connect`
+    event_bridges: Vec<EventBridge>,
 }
 
 impl ScriptingEngine {
     /// # Safety
-    /// scene and engine needs to live for the duration of ScriptingEngine
+    /// scene needs to live for the duration of ScriptingEngine
     /// TODO: perhaps don't use raw ptrs
-    pub unsafe fn new(scene: &mut Scene, engine: &Engine, window_id: WindowId) -> Self {
+    pub unsafe fn new(scene: &mut Scene) -> Self {
         Self {
             lua: mlua::Lua::default(),
             scene: scene as *mut Scene,
-            engine: engine as *const Engine,
-            window_id,
+            commands: Rc::default(),
+            event_bridges: Vec::new(),
         }
     }
 
     pub fn setup(&mut self) -> mlua::Result<()> {
         let lua = &self.lua;
         let scene = unsafe { &mut *self.scene };
-        let engine = unsafe { &*self.engine };
-        let window = engine
-            .get_window(self.window_id)
-            .ok_or(mlua::Error::runtime("Window ID invalid"))?;
 
         super::vector::Vector3::register_class(lua)?;
         super::vector::Vector2::register_class(lua)?;
-        super::input::register_class(lua, &window.input)?;
-        super::time::register_class(lua, &engine.time)?;
-        super::components::register_entity_funcs(lua, scene)?;
+        super::input::register_class(lua, scene)?;
+        super::time::register_class(lua, scene)?;
+        super::components::register_entity_funcs(lua, self.scene, self.commands.clone())?;
+        super::events::register_class(lua, self.scene)?;
 
         lua.globals().set("__signals_index", lua.create_table()?)?;
 
@@ -48,33 +55,116 @@ impl ScriptingEngine {
         main_events.set("FixedUpdate", self.make_signal("MainEvents.FixedUpdate")?)?;
         self.lua.globals().set("MainEvents", main_events)?;
 
+        let mut reader = EventReader::<ScriptEvent>::default();
+        self.event_bridges.push(Box::new(move |this| {
+            let scene = unsafe { &*this.scene };
+            let Some(events) = scene.world.events::<ScriptEvent>() else {
+                return Ok(());
+            };
+
+            let events: Vec<ScriptEvent> = reader.read(events).cloned().collect();
+            for event in events {
+                this.call_signal_with(&signal_name(&event.name), event.value)?;
+            }
+            Ok(())
+        }));
+
+        // Weak so the hook doesn't keep lua alive if the scene outlives the engine
+        let weak_lua = lua.weak();
+        let scene = unsafe { &mut *self.scene };
+        scene.world.add_event::<ScriptEvent>();
+        scene
+            .world
+            .component_hooks::<ScriptComponent>()
+            .on_remove(move |_, entity| {
+                let Some(lua) = weak_lua.try_upgrade() else {
+                    return;
+                };
+                if let Err(err) = Self::disconnect_signals(&lua, entity) {
+                    log::error!("Failed to disconnect signals of {entity}: {err}");
+                }
+            });
+
         Ok(())
     }
 
-    pub fn run(&mut self, entity_index: usize, script: &Script) -> mlua::Result<()> {
-        self.lua.globals().set("entity_index", entity_index)?;
+    /// Sets the entity the running script belongs to
+    /// `entity_index` is kept as an alias for scripts written before the global was renamed
+    fn set_entity(&self, entity: Entity) -> mlua::Result<()> {
+        self.lua.globals().set("entity", entity)?;
+        self.lua.globals().set("entity_index", entity)
+    }
+
+    pub fn run(&mut self, entity: Entity, script: &Script) -> mlua::Result<()> {
+        self.set_entity(entity)?;
         self.lua
             .load(&script.bytecode)
             .set_name(script.source.clone().unwrap_or_default())
             .exec()
     }
 
-    pub fn call_signal(&self, full_name: &'static str) -> mlua::Result<()> {
+    /// Applies the changes to the scene that were queued by scripts
+    /// Must not be called while the scene is being iterated through
+    pub fn apply_commands(&mut self) {
+        let mut commands = std::mem::take(&mut *self.commands.borrow_mut());
+        commands.apply(unsafe { &mut *self.scene });
+    }
+
+    pub fn call_signal(&self, full_name: &str) -> mlua::Result<()> {
+        self.call_signal_with(full_name, ())
+    }
+
+    /// Calls every callback connected to the signal with the arguments
+    /// Does nothing if no callback was ever connected to it
+    pub fn call_signal_with(
+        &self,
+        full_name: &str,
+        args: impl mlua::IntoLuaMulti + Clone,
+    ) -> mlua::Result<()> {
         let signal_index: mlua::Table = self.lua.globals().get("__signals_index")?;
-        let signal_list: mlua::Table = signal_index.get(full_name)?;
+        let Some(signal_list) = signal_index.get::<Option<mlua::Table>>(full_name)? else {
+            return Ok(());
+        };
 
         signal_list.for_each(move |_: usize, signal: Signal| {
-            self.lua
-                .globals()
-                .set("entity_index", signal.caller_entity_index)?;
+            self.set_entity(signal.caller_entity)?;
 
-            signal.callback.call::<()>(())?;
+            signal.callback.call::<()>(args.clone())?;
             Ok(())
         })?;
 
         Ok(())
     }
 
+    /// Makes the events of type E sent in the world call the callbacks that scripts connected
+    /// with `Events.connect(name, callback)`, the event is passed to them as the argument
+    /// [ScriptEvent]s are always bridged under their own names
+    pub fn bridge_event<E: Resource + mlua::IntoLua>(&mut self, name: &str) {
+        let full_name = signal_name(name);
+        let mut reader = EventReader::<E>::default();
+        self.event_bridges.push(Box::new(move |this| {
+            let scene = unsafe { &*this.scene };
+            let Some(events) = scene.world.events::<E>() else {
+                return Ok(());
+            };
+
+            // Collect first since callbacks can send new events
+            let events: Vec<E> = reader.read(events).cloned().collect();
+            for event in events {
+                this.call_signal_with(&full_name, event)?;
+            }
+            Ok(())
+        }));
+    }
+
+    /// Calls the callbacks connected with `Events.connect` for the events sent since the last call
+    pub fn dispatch_events(&mut self) -> mlua::Result<()> {
+        let mut bridges = std::mem::take(&mut self.event_bridges);
+        let result = bridges.iter_mut().try_for_each(|bridge| bridge(self));
+        self.event_bridges = bridges;
+        result
+    }
+
     fn make_signal(&self, full_name: &'static str) -> mlua::Result<mlua::Table> {
         let signal = self.lua.create_table()?;
 
@@ -82,14 +172,13 @@ impl ScriptingEngine {
         let signals_index: mlua::Table = self.lua.globals().get("__signals_index")?;
         signals_index.set(full_name, self.lua.create_table()?)?;
 
-        // TODO: disconnect signal when entity destroy
         let connect_func = move |lua: &mlua::Lua, callback: mlua::Function| {
             let signals_index: mlua::Table = lua.globals().get("__signals_index")?;
             let signal_list: mlua::Table = signals_index.get(full_name)?;
 
             let signal_connect_info = Signal {
                 callback,
-                caller_entity_index: lua.globals().get("entity_index")?,
+                caller_entity: lua.globals().get("entity")?,
             };
             signal_list.push(signal_connect_info)
         };
@@ -97,11 +186,33 @@ impl ScriptingEngine {
 
         Ok(signal)
     }
+
+    /// Removes every signal callback that was connected by the entity's script
+    fn disconnect_signals(lua: &mlua::Lua, entity: Entity) -> mlua::Result<()> {
+        let signals_index: mlua::Table = lua.globals().get("__signals_index")?;
+
+        signals_index.for_each(|_: mlua::Value, signal_list: mlua::Table| {
+            let remaining = signal_list
+                .sequence_values::<Signal>()
+                .filter(|signal| {
+                    signal
+                        .as_ref()
+                        .map_or(true, |signal| signal.caller_entity != entity)
+                })
+                .collect::<mlua::Result<Vec<_>>>()?;
+
+            signal_list.clear()?;
+            for signal in remaining {
+                signal_list.push(signal)?;
+            }
+            Ok(())
+        })
+    }
 }
 
-struct Signal {
-    callback: mlua::Function,
-    caller_entity_index: usize,
+pub(super) struct Signal {
+    pub callback: mlua::Function,
+    pub caller_entity: Entity,
 }
 
-impl_mlua_conversion!(Signal, [callback, caller_entity_index]);
+impl_mlua_conversion!(Signal, [callback, caller_entity]);
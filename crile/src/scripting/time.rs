@@ -1,12 +1,19 @@
-use crate::Time;
+use crate::{Scene, Time};
 
-pub fn register_class(lua: &mlua::Lua, time: &'static Time) -> mlua::Result<()> {
+pub fn register_class(lua: &mlua::Lua, scene: &'static Scene) -> mlua::Result<()> {
     let time_class = super::make_class(lua, "Time")?;
 
     time_class.set(
         "delta",
-        lua.create_function(|_, ()| Ok(time.delta().as_secs_f32()))?,
+        lua.create_function(|_, ()| Ok(get_time(scene)?.delta().as_secs_f32()))?,
     )?;
 
     Ok(())
 }
+
+fn get_time(scene: &Scene) -> mlua::Result<&Time> {
+    scene
+        .world
+        .resource::<Time>()
+        .ok_or_else(|| mlua::Error::runtime("Time resource is missing from the world"))
+}
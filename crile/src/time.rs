@@ -1,5 +1,6 @@
 use std::time::{Duration, Instant};
 
+#[derive(Clone)]
 pub struct Time {
     last_frame_time: Instant,
     elapsed: Duration,